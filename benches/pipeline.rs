@@ -0,0 +1,113 @@
+// Performance targets (release build, single core):
+//     parse   small  < 75 us     large  < 20 ms
+//     render  small  < 250 us    large  < 40 ms
+//     full    small  < 300 us    large  < 60 ms
+// 'small' is a short scene sequence, 'large' roughly a 120 page feature.
+// 'cargo bench' fails once the criterion run is done if any median misses its target.
+
+use std::time::{ Duration, Instant };
+use criterion::{ criterion_group, BenchmarkId, Criterion };
+use scripts::{ parse, render_html, CmdInfo };
+
+
+const TARGETS: [(&str, usize, [u64; 3]); 2] = [
+    ("small", 5, [75, 250, 300]),
+    ("large", 600, [20_000, 40_000, 60_000]),
+];
+
+
+fn fixture(scenes: usize) -> String {
+    let mut src = String::from("Benchmark\nA generated script\ncast Alex, Sam\n");
+
+    for i in 0..scenes {
+        src.push_str(&format!("scene INT. LOCATION {i} - DAY\n"));
+        src.push_str("direct Alex walks in and looks *around* the room, taking in every detail.\n");
+        src.push_str("alex: (Quietly) Is anyone here? I thought we agreed to meet at 10:30.\n");
+        src.push_str("SAM: Right behind you. \\\n    I have been waiting for a while.\n");
+        src.push_str("direct Sam steps out of the shadows. * staging note\n");
+        src.push_str("TODO tighten this exchange\n");
+        src.push_str("trans CUT TO\n");
+    }
+
+    src
+}
+
+
+fn benches(c: &mut Criterion) {
+    let cmd = CmdInfo::default();
+
+    for (name, scenes, _) in TARGETS {
+        let src = fixture(scenes);
+        let script = parse(&src).expect("fixture failed to parse");
+
+        c.bench_with_input(BenchmarkId::new("parse", name), &src, |b, src| {
+            b.iter(|| parse(src).unwrap())
+        });
+        c.bench_with_input(BenchmarkId::new("render", name), &script, |b, script| {
+            b.iter(|| render_html(script, &cmd).unwrap())
+        });
+        c.bench_with_input(BenchmarkId::new("full", name), &src, |b, src| {
+            b.iter(|| render_html(&parse(src).unwrap(), &cmd).unwrap())
+        });
+    }
+}
+
+
+fn median(mut f: impl FnMut()) -> Duration {
+    const SAMPLES: usize = 51;
+
+    f();
+    let mut times: Vec<Duration> = (0..SAMPLES).map(|_| {
+        let start = Instant::now();
+        f();
+        start.elapsed()
+    }).collect();
+    times.sort();
+
+    times[SAMPLES / 2]
+}
+
+
+fn gate(cmd: &CmdInfo) -> Vec<String> {
+    let mut missed = vec![];
+
+    for (name, scenes, targets) in TARGETS {
+        let src = fixture(scenes);
+        let script = parse(&src).unwrap();
+
+        let times = [
+            median(|| { parse(&src).unwrap(); }),
+            median(|| { render_html(&script, cmd).unwrap(); }),
+            median(|| { render_html(&parse(&src).unwrap(), cmd).unwrap(); }),
+        ];
+        for ((stage, time), target) in ["parse", "render", "full"].into_iter().zip(times).zip(targets) {
+            let target = Duration::from_micros(target);
+            if time > target {
+                missed.push(format!("{stage}/{name}: {time:?} over target of {target:?}"));
+            }
+        }
+    }
+
+    missed
+}
+
+
+criterion_group!(pipeline, benches);
+
+fn main() {
+    pipeline();
+    Criterion::default().configure_from_args().final_summary();
+
+    // 'cargo test' runs benches in test mode, only gate real runs
+    if !std::env::args().any(|a| a == "--bench") {
+        return
+    }
+
+    let missed = gate(&CmdInfo::default());
+    for line in &missed {
+        eprintln!("performance target missed: {line}");
+    }
+    if !missed.is_empty() {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,497 @@
+use std::{
+    collections::{ BTreeSet, HashMap },
+    hash::Hash,
+    ops::Range,
+};
+use regex::Regex;
+use serde::{ Serialize, Deserialize };
+use crate::{
+    layout::cue_base,
+    Diagnostic,
+    DialogueNumbering,
+};
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "text", rename_all = "lowercase")]
+pub enum DialogueLine {
+    Parens(String),
+    Speech(String),
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Dialogue {
+    pub name: String,
+    pub lines: Vec<DialogueLine>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub adr: bool,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Kind {
+    Montage,
+    MonEnd,
+    PageBreak,
+    End,
+    Todo{ text: String },
+    Note{ text: String },
+    Section{ depth: usize, text: String },
+    Synopsis{ text: String },
+    Direct{ text: String },
+    Parens{ text: String },
+    Speech{ text: String },
+    Lyrics{ text: String },
+    Subhead{ text: String },
+    Trans{ text: String },
+    Chyron{ text: String },
+    Sfx{ text: String },
+    Music{ text: String },
+    Header{ text: String },
+    ActTag{ text: String },
+    Act{ text: String },
+    ActEnd{ text: String },
+    Episode{ text: String },
+    ComicPage{ number: u32, text: String },
+    Panel{ number: u32, text: String },
+    Caption{ text: String },
+    Dialogue(Dialogue),
+}
+
+impl Kind {
+    // act, episode and closure lines stand between scenes rather than inside them
+    pub fn is_structure(&self) -> bool {
+        matches!(self, Kind::End|Kind::ActTag{ .. }|Kind::Act{ .. }|Kind::ActEnd{ .. }|Kind::Episode{ .. })
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Element {
+    pub line: usize,
+    #[serde(flatten)]
+    pub kind: Kind,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tag {
+    pub line: usize,
+    pub category: String,
+    pub item: String,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scene {
+    pub number: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub omitted: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub restricted: bool,
+    pub line: usize,
+    pub heading: String,
+    pub elements: Vec<Element>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<Tag>,
+}
+
+impl Scene {
+    pub fn display_number(&self) -> String {
+        self.locked.clone().unwrap_or_else(|| self.number.to_string())
+    }
+
+    pub fn cast(&self) -> Vec<&str> {
+        let mut cast: Vec<&str> = Vec::new();
+        for e in &self.elements {
+            if let Kind::Dialogue(dialogue) = &e.kind {
+                for name in dialogue.speakers() {
+                    if !cast.contains(&name) {
+                        cast.push(name);
+                    }
+                }
+            }
+        }
+        cast
+    }
+
+    pub fn setting(&self) -> &str {
+        self.heading.split_once(". ").map_or("", |(setting, _)| setting.trim())
+    }
+
+    pub fn time_of_day(&self) -> &str {
+        self.heading.rsplit_once(" - ").map_or("", |(_, time)| time.trim())
+    }
+
+    pub fn location(&self) -> &str {
+        let place = self.heading.split_once(". ").map_or(self.heading.as_str(), |(_, rest)| rest);
+        place.rsplit_once(" - ").map_or(place, |(location, _)| location).trim()
+    }
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Script {
+    pub title: String,
+    pub subtitle: String,
+    pub doctype: Option<String>,
+    pub episode: Option<String>,
+    pub author: Option<String>,
+    pub draft: Option<String>,
+    pub locations: Vec<String>,
+    pub prelude: Vec<Element>,
+    pub scenes: Vec<Scene>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Diagnostic>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub revised: BTreeSet<usize>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub redacted: BTreeSet<usize>,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Todo {
+    pub line: usize,
+    pub scene: u32,
+    pub label: String,
+    pub text: String,
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct Completions {
+    pub characters: Vec<String>,
+    pub locations: Vec<String>,
+    pub transitions: Vec<String>,
+}
+
+
+// most used first so editors can offer the likely name at the top, ties keep script order
+fn by_frequency<'a>(items: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    for item in items.filter(|i| !i.is_empty()) {
+        match counts.iter_mut().find(|(i, _)| *i == item) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((item, 1)),
+        }
+    }
+    counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    counts.into_iter().map(|(item, _)| item.to_string()).collect()
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogueCue {
+    pub line: usize,
+    pub number: u32,
+    pub scene: String,
+    pub name: String,
+    pub text: String,
+    pub adr: bool,
+    pub offscreen: bool,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActStats {
+    pub name: String,
+    pub scenes: Vec<u32>,
+    pub elements: usize,
+    pub dialogue: usize,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutlineItem {
+    Section{ line: usize, depth: usize, text: String },
+    Synopsis{ line: usize, text: String },
+    Scene{ line: usize, label: String, heading: String },
+}
+
+
+impl Dialogue {
+    pub fn speakers(&self) -> Vec<&str> {
+        cue_base(&self.name)
+            .split(['&', '/'])
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+            .collect()
+    }
+
+    pub fn offscreen(&self) -> bool {
+        let name = self.name.to_uppercase();
+        ["(V.O.)", "(O.S.)"].iter().any(|ext| name.contains(ext))
+    }
+}
+
+
+fn take<K: Eq + Hash>(counts: &mut HashMap<K, usize>, key: K) -> bool {
+    match counts.get_mut(&key) {
+        Some(n) if *n > 0 => {
+            *n -= 1;
+            true
+        }
+        _ => false,
+    }
+}
+
+
+impl Script {
+    pub fn completions(&self) -> Completions {
+        let elements = || self.prelude.iter().chain(self.scenes.iter().flat_map(|s| &s.elements));
+
+        let speakers: Vec<&str> = elements()
+            .filter_map(|e| match &e.kind {
+                Kind::Dialogue(dialogue) => Some(dialogue.speakers()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        let headings = self.scenes.iter().filter(|s| !s.omitted && !self.redacted.contains(&s.line)).map(Scene::location);
+        let transitions: Vec<String> = elements()
+            .filter_map(|e| match &e.kind {
+                Kind::Trans{ text } => Some(text.to_uppercase()),
+                _ => None,
+            })
+            .collect();
+
+        Completions{
+            characters: by_frequency(speakers.into_iter()),
+            locations: by_frequency(self.locations.iter().map(String::as_str).chain(headings)),
+            transitions: by_frequency(transitions.iter().map(String::as_str)),
+        }
+    }
+
+    pub fn push(&mut self, element: Element) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.elements.push(element);
+        } else {
+            self.prelude.push(element);
+        }
+    }
+
+    pub fn has_closure(&self) -> bool {
+        let last = self.scenes.last().map_or(&self.prelude, |s| &s.elements).iter()
+            .rev()
+            .find(|e| !matches!(e.kind, Kind::Todo{ .. }|Kind::Note{ .. }|Kind::Section{ .. }|Kind::Synopsis{ .. }|Kind::ActEnd{ .. }));
+
+        match last.map(|e| &e.kind) {
+            Some(Kind::End) => true,
+            Some(Kind::Trans{ text }|Kind::Header{ text }) => {
+                let text = text.to_uppercase();
+                text.starts_with("FADE OUT") || text.starts_with("FADE TO BLACK") || text.starts_with("THE END")
+            }
+            _ => false,
+        }
+    }
+
+    pub fn retain_scenes(&mut self, range: &Range<u32>) {
+        if !range.contains(&0) {
+            self.prelude.clear();
+        }
+        self.scenes.retain(|s| range.contains(&s.number));
+    }
+
+    pub fn retain_sides(&mut self, name: &str, context: usize) {
+        let name = name.to_uppercase();
+        let mention = Regex::new(&format!(r"(?i)\b{}\b", regex::escape(&name))).unwrap();
+        let appears: Vec<usize> = self.scenes.iter()
+            .enumerate()
+            .filter(|(_, s)| s.elements.iter().any(|e| match &e.kind {
+                Kind::Dialogue(dialogue) => dialogue.speakers().contains(&name.as_str()),
+                Kind::Direct{ text } => mention.is_match(text),
+                _ => false,
+            }))
+            .map(|(i, _)| i)
+            .collect();
+
+        let mut index = 0;
+        self.prelude.clear();
+        self.scenes.retain(|_| {
+            let keep = appears.iter().any(|&i| index + context >= i && index <= i + context);
+            index += 1;
+            keep
+        });
+    }
+
+    pub fn retain_location(&mut self, pattern: &Regex) {
+        self.prelude.clear();
+        self.scenes.retain(|s| pattern.is_match(s.location()));
+    }
+
+    pub fn split_scenes(&self) -> Vec<(String, Script)> {
+        self.scenes.iter()
+            .map(|scene| {
+                let slug: String = scene.heading.to_lowercase()
+                    .split(|c: char| !c.is_alphanumeric())
+                    .filter(|w| !w.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("-");
+                let name = format!("{}-{slug}", self.scene_label(&scene.display_number()).replace(' ', "-").to_lowercase());
+                let script = Script{ prelude: Vec::new(), scenes: vec![scene.clone()], warnings: Vec::new(), ..self.clone() };
+                (name, script)
+            })
+            .collect()
+    }
+
+    // the scene keeps its number so the placeholder shows where it sits, nothing else survives
+    pub fn redact_restricted(&mut self) {
+        for scene in self.scenes.iter_mut().filter(|s| s.restricted) {
+            scene.heading = "RESTRICTED".to_string();
+            scene.elements.clear();
+            scene.tags.clear();
+            self.redacted.insert(scene.line);
+        }
+    }
+
+    // sequential numbers replace locked ones, omitted placeholders have nothing left to hold
+    pub fn renumber(&mut self, start: u32) {
+        self.scenes.retain(|s| !s.omitted);
+        for (i, scene) in self.scenes.iter_mut().filter(|s| s.number > 0).enumerate() {
+            scene.number = start + i as u32;
+            scene.locked = None;
+        }
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = (u32, &Element)> {
+        self.prelude.iter()
+            .map(|e| (0, e))
+            .chain(self.scenes.iter().flat_map(|s| s.elements.iter().map(|e| (s.number, e))))
+    }
+
+    pub fn scene_label(&self, scene: &str) -> String {
+        match &self.episode {
+            Some(episode) => format!("EP{episode} SC{scene}"),
+            None => scene.to_string(),
+        }
+    }
+
+    pub fn outline(&self) -> Vec<OutlineItem> {
+        let structure = |elements: &[Element], outline: &mut Vec<OutlineItem>| {
+            for e in elements {
+                match &e.kind {
+                    Kind::Section{ depth, text } => outline.push(OutlineItem::Section{ line: e.line, depth: *depth, text: text.clone() }),
+                    Kind::Synopsis{ text } => outline.push(OutlineItem::Synopsis{ line: e.line, text: text.clone() }),
+                    _ => (),
+                }
+            }
+        };
+
+        let mut outline = Vec::new();
+        structure(&self.prelude, &mut outline);
+        for scene in &self.scenes {
+            outline.push(OutlineItem::Scene{ line: scene.line, label: self.scene_label(&scene.display_number()), heading: scene.heading.clone() });
+            structure(&scene.elements, &mut outline);
+        }
+
+        outline
+    }
+
+    pub fn acts(&self) -> Vec<ActStats> {
+        let mut acts: Vec<ActStats> = Vec::new();
+
+        for (scene, e) in self.elements() {
+            match &e.kind {
+                Kind::Act{ text } => acts.push(ActStats{ name: text.clone(), scenes: Vec::new(), elements: 0, dialogue: 0 }),
+                Kind::Episode{ .. }|Kind::ActEnd{ .. } => (),
+                kind => if let Some(act) = acts.last_mut() {
+                    if scene > 0 && act.scenes.last() != Some(&scene) {
+                        act.scenes.push(scene);
+                    }
+                    act.elements += 1;
+                    if let Kind::Dialogue(_) = kind {
+                        act.dialogue += 1;
+                    }
+                }
+            }
+        }
+
+        acts
+    }
+
+    pub fn mark_revisions(&mut self, old: &Script) {
+        let mut headings: HashMap<&str, usize> = HashMap::new();
+        let mut kinds: HashMap<&Kind, usize> = HashMap::new();
+        for scene in &old.scenes {
+            *headings.entry(&scene.heading).or_default() += 1;
+        }
+        for (_, e) in old.elements() {
+            *kinds.entry(&e.kind).or_default() += 1;
+        }
+
+        let mut revised = BTreeSet::new();
+        for scene in &self.scenes {
+            if !take(&mut headings, scene.heading.as_str()) {
+                revised.insert(scene.line);
+            }
+        }
+        for (_, e) in self.elements() {
+            if !take(&mut kinds, &e.kind) {
+                revised.insert(e.line);
+            }
+        }
+        self.revised = revised;
+    }
+
+    pub fn dialogue_cues(&self, numbering: DialogueNumbering) -> Vec<DialogueCue> {
+        let mut cues = Vec::new();
+        let mut number = 0;
+
+        let scenes = std::iter::once((None, &self.prelude)).chain(self.scenes.iter().map(|s| (Some(s), &s.elements)));
+        for (scene, elements) in scenes {
+            if numbering == DialogueNumbering::Scene {
+                number = 0;
+            }
+            for e in elements {
+                let Kind::Dialogue(dialogue) = &e.kind else {
+                    continue
+                };
+                number += 1;
+                let text: Vec<&str> = dialogue.lines.iter()
+                    .map(|line| match line {
+                        DialogueLine::Parens(text)|DialogueLine::Speech(text) => text.as_str(),
+                    })
+                    .collect();
+                cues.push(DialogueCue{
+                    line: e.line,
+                    number,
+                    scene: self.scene_label(&scene.map_or("0".to_string(), Scene::display_number)),
+                    name: dialogue.name.clone(),
+                    text: text.join(" "),
+                    adr: dialogue.adr,
+                    offscreen: dialogue.offscreen(),
+                });
+            }
+        }
+
+        cues
+    }
+
+    // numbered like the global dialogue list so the report lines up with the script margin
+    pub fn adr_cues(&self, offscreen: bool) -> Vec<DialogueCue> {
+        self.dialogue_cues(DialogueNumbering::Global).into_iter()
+            .filter(|cue| cue.adr || (offscreen && cue.offscreen))
+            .collect()
+    }
+
+    pub fn todos(&self) -> Vec<Todo> {
+        self.prelude.iter()
+            .map(|e| (None, e))
+            .chain(self.scenes.iter().flat_map(|s| s.elements.iter().map(move |e| (Some(s), e))))
+            .filter_map(|(scene, e)| match &e.kind {
+                Kind::Todo{ text } => Some(Todo{
+                    line: e.line,
+                    scene: scene.map_or(0, |s| s.number),
+                    label: self.scene_label(&scene.map_or("0".to_string(), Scene::display_number)),
+                    text: text.clone(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    fs,
+    fmt::Write,
+};
+use crate::{
+    ast::*,
+    layout::*,
+    parse::*,
+    pdf::gen_pdf,
+    CmdInfo,
+    HtmlError,
+};
+
+
+const CATEGORIES: [&str; 4] = ["prop", "wardrobe", "vehicle", "extra"];
+
+
+const SHEET_CSS: &str = "\
+body { font-family: 'Courier New', monospace; font-size: 11pt; margin: 0 1in; }
+.sheet { page-break-after: always; }
+.sheet h1 { font-size: 14pt; border-bottom: 2px solid #000; }
+.sheet table { width: 100%; border-collapse: collapse; }
+.sheet td { border: 1px solid #000; padding: 4px 6px; vertical-align: top; }
+.sheet td:first-child { width: 1.5in; font-weight: bold; }";
+
+
+const CALL_CSS: &str = "\
+body { font-family: 'Courier New', monospace; font-size: 11pt; margin: 0 1in; }
+h1 { font-size: 16pt; margin-bottom: 0; }
+table { width: 100%; border-collapse: collapse; margin-top: 0.2in; }
+th, td { border: 1px solid #000; padding: 4px 6px; text-align: left; vertical-align: top; }
+.total td { font-weight: bold; }";
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BreakdownSheet {
+    pub scene: String,
+    pub heading: String,
+    pub pages: String,
+    pub cast: Vec<String>,
+    pub tags: Vec<(String, Vec<String>)>,
+}
+
+
+// the four fixed categories always come first so sheets line up, others follow in order of appearance
+pub fn breakdown(script: &Script) -> Vec<BreakdownSheet> {
+    script.scenes.iter()
+        .filter(|s| !s.omitted)
+        .map(|scene| {
+            let mut tags: Vec<(String, Vec<String>)> = CATEGORIES.iter().map(|c| (c.to_string(), Vec::new())).collect();
+            for tag in &scene.tags {
+                let index = match tags.iter().position(|(c, _)| *c == tag.category) {
+                    Some(index) => index,
+                    None => {
+                        tags.push((tag.category.clone(), Vec::new()));
+                        tags.len() - 1
+                    }
+                };
+                if !tags[index].1.contains(&tag.item) {
+                    tags[index].1.push(tag.item.clone());
+                }
+            }
+
+            BreakdownSheet{
+                scene: script.scene_label(&scene.display_number()),
+                heading: scene.heading.clone(),
+                pages: page_eighths(scene),
+                cast: scene.cast().into_iter().map(str::to_string).collect(),
+                tags,
+            }
+        })
+        .collect()
+}
+
+
+pub fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+
+pub fn breakdown_csv(sheets: &[BreakdownSheet]) -> String {
+    let mut categories: Vec<&str> = CATEGORIES.to_vec();
+    for (category, _) in sheets.iter().flat_map(|s| &s.tags) {
+        if !categories.contains(&category.as_str()) {
+            categories.push(category);
+        }
+    }
+
+    let header: Vec<&str> = ["scene", "heading", "pages", "cast"].into_iter().chain(categories.iter().copied()).collect();
+    let mut csv = header.join(",") + "\n";
+
+    for sheet in sheets {
+        let mut fields = vec![sheet.scene.clone(), sheet.heading.clone(), sheet.pages.clone(), sheet.cast.join("; ")];
+        for category in &categories {
+            fields.push(sheet.tags.iter().find(|(c, _)| c == category).map_or(String::new(), |(_, items)| items.join("; ")));
+        }
+        csv.push_str(&fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+
+pub fn schedule_csv(script: &Script) -> String {
+    let mut csv = String::from("scene,int/ext,location,time,pages,characters\n");
+
+    for scene in script.scenes.iter().filter(|s| !s.omitted) {
+        let fields = [
+            script.scene_label(&scene.display_number()),
+            scene.setting().to_string(),
+            scene.location().to_string(),
+            scene.time_of_day().to_string(),
+            page_eighths(scene),
+            scene.cast().join("; "),
+        ];
+        csv.push_str(&fields.map(|f| csv_field(&f)).join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+
+pub fn breakdown_html(script: &Script, sheets: &[BreakdownSheet]) -> Result<String, HtmlError> {
+    let mut result = format!("<html><head><meta charset=\"utf-8\"/><style>{SHEET_CSS}</style></head><body>\n");
+
+    for sheet in sheets {
+        writeln!(result, "<div class=\"sheet\"><h1>{} &mdash; scene {}</h1><table>", script.title, sheet.scene)?;
+        writeln!(result, "<tr><td>Heading</td><td>{}</td></tr>", sheet.heading)?;
+        writeln!(result, "<tr><td>Pages</td><td>{}</td></tr>", sheet.pages)?;
+        writeln!(result, "<tr><td>Cast</td><td>{}</td></tr>", sheet.cast.join("<br>"))?;
+        for (category, items) in &sheet.tags {
+            writeln!(result, "<tr><td>{category}</td><td>{}</td></tr>", items.join("<br>"))?;
+        }
+        result.push_str("</table></div>\n");
+    }
+    result.push_str("</body></html>\n");
+
+    Ok(result)
+}
+
+
+// cast numbers follow the order of first appearance in the whole script, as on a day out of days
+pub fn callsheet_html(script: &Script, date: &str, labels: &[String]) -> Result<String, HtmlError> {
+    let mut cast: Vec<&str> = Vec::new();
+    for name in script.scenes.iter().flat_map(Scene::cast) {
+        if !cast.contains(&name) {
+            cast.push(name);
+        }
+    }
+    let number = |name: &str| cast.iter().position(|n| *n == name).map_or(0, |i| i + 1);
+
+    let mut scenes = Vec::new();
+    for label in labels {
+        let scene = script.scenes.iter()
+            .find(|s| s.display_number() == *label || script.scene_label(&s.display_number()) == *label)
+            .ok_or_else(|| HtmlError::SceneNotFound(label.clone()))?;
+        scenes.push(scene);
+    }
+
+    let mut result = format!("<html><head><meta charset=\"utf-8\"/><style>{CALL_CSS}</style></head><body>\n\
+                              <h1>{}</h1>\n<p>Call sheet &mdash; {date}</p>\n\
+                              <table><tr><th>Scene</th><th>Set</th><th>D/N</th><th>Pages</th><th>Cast</th></tr>\n", script.title);
+    for scene in &scenes {
+        let ids: Vec<String> = scene.cast().into_iter().map(|n| number(n).to_string()).collect();
+        writeln!(result, "<tr><td>{}</td><td>{}. {}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                 script.scene_label(&scene.display_number()), scene.setting(), scene.location(), scene.time_of_day(), page_eighths(scene), ids.join(", "))?;
+    }
+    let total: u32 = scenes.iter().map(|s| scene_eighths(s)).sum();
+    writeln!(result, "<tr class=\"total\"><td colspan=\"3\">Total</td><td>{}</td><td></td></tr></table>", format_eighths(total))?;
+
+    result.push_str("<table><tr><th>#</th><th>Cast</th><th>Scenes</th></tr>\n");
+    for name in &cast {
+        let appears: Vec<String> = scenes.iter().filter(|s| s.cast().contains(name)).map(|s| script.scene_label(&s.display_number())).collect();
+        if !appears.is_empty() {
+            writeln!(result, "<tr><td>{}</td><td>{name}</td><td>{}</td></tr>", number(name), appears.join(", "))?;
+        }
+    }
+    result.push_str("</table></body></html>\n");
+
+    Ok(result)
+}
+
+
+pub fn gen_callsheet(cmd: &CmdInfo, date: &str, scenes: &[String]) -> Result<Vec<Diagnostic>, HtmlError> {
+    let script = read_script(cmd)?;
+
+    fs::write(&cmd.html, callsheet_html(&script, date, scenes)?)?;
+    let status = gen_pdf(cmd)?;
+    if !status.success() {
+        return Err(std::io::Error::other(format!("webkit exited with {status}")).into())
+    }
+
+    Ok(script.warnings)
+}
+
+
+// csv for '.csv' outputs, anything else goes through webkit like the script itself
+pub fn gen_breakdown(cmd: &CmdInfo) -> Result<Vec<Diagnostic>, HtmlError> {
+    let script = read_script(cmd)?;
+    let sheets = breakdown(&script);
+
+    if cmd.outfile.ends_with(".csv") {
+        fs::write(&cmd.outfile, breakdown_csv(&sheets))?;
+        return Ok(script.warnings)
+    }
+
+    fs::write(&cmd.html, breakdown_html(&script, &sheets)?)?;
+    let status = gen_pdf(cmd)?;
+    if !status.success() {
+        return Err(std::io::Error::other(format!("webkit exited with {status}")).into())
+    }
+
+    Ok(script.warnings)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sheets() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             scene EXT. ROAD - NIGHT\n\
+             props revolver, \"lucky\" coin\n\
+             vehicles police car\n\
+             direct A car stops.\n\
+             ALEX: Freeze.\n\
+             SAM & ALEX: Run!\n\
+             props revolver\n\
+             scene INT. CAR - NIGHT\n\
+             direct Quiet."
+        ).expect("parse failed");
+
+        let sheets = breakdown(&script);
+        assert_eq!(sheets.len(), 2);
+        assert_eq!(sheets[0].cast, ["ALEX", "SAM"]);
+        assert_eq!(sheets[0].tags[0], ("prop".to_string(), vec!["revolver".to_string(), "\"lucky\" coin".to_string()]));
+
+        let csv = breakdown_csv(&sheets);
+        let mut rows = csv.lines();
+        assert_eq!(rows.next(), Some("scene,heading,pages,cast,prop,wardrobe,vehicle,extra"));
+        assert_eq!(rows.next(), Some("1,EXT. ROAD - NIGHT,2/8,ALEX; SAM,\"revolver; \"\"lucky\"\" coin\",,police car,"));
+
+        assert!(parse("title\nsubtitle\nprops gun").is_err());
+    }
+
+    #[test]
+    fn callsheet() {
+        let script = parse("title\nsubtitle\nscene INT. A - DAY\nSAM: Hi.\nscene EXT. B - NIGHT\nALEX: Go.\nSAM: Now.\nscene INT. C - DAY\ndirect Empty.").expect("parse failed");
+
+        let html = callsheet_html(&script, "2026-11-02", &["2".to_string(), "3".to_string()]).unwrap();
+        assert!(html.contains("<tr><td>2</td><td>EXT. B</td><td>NIGHT</td><td>2/8</td><td>2, 1</td></tr>"));
+        assert!(html.contains("<tr class=\"total\"><td colspan=\"3\">Total</td><td>3/8</td><td></td></tr>"));
+        assert!(html.contains("<tr><td>1</td><td>SAM</td><td>2</td></tr>"));
+        assert!(matches!(callsheet_html(&script, "", &["9".to_string()]), Err(HtmlError::SceneNotFound(_))));
+
+        // both tables label scenes the same way under an episode prefix
+        let episode = Script{ episode: Some("105".to_string()), ..script };
+        let html = callsheet_html(&episode, "2026-11-02", &["EP105 SC2".to_string()]).unwrap();
+        assert!(html.contains("<tr><td>EP105 SC2</td><td>EXT. B</td>"));
+        assert!(html.contains("<tr><td>1</td><td>SAM</td><td>EP105 SC2</td></tr>"));
+    }
+
+    #[test]
+    fn schedule() {
+        let script = parse("title\nsubtitle\nscene INT./EXT. CAR, MOVING - DAY\nALEX: Go.\nomitted 2\nscene EXT. ROAD - NIGHT\ndirect Empty.").expect("parse failed");
+
+        assert_eq!(schedule_csv(&script),
+                   "scene,int/ext,location,time,pages,characters\n\
+                    1,INT./EXT,\"CAR, MOVING\",DAY,1/8,ALEX\n\
+                    3,EXT,ROAD,NIGHT,1/8,\n");
+    }
+}
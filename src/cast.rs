@@ -0,0 +1,118 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::{
+    ast::*,
+    parse::*,
+    CmdInfo,
+    HtmlError,
+};
+
+
+lazy_static! {
+    static ref PAT_CAPS: Regex = Regex::new(r"\b\p{Lu}[\p{Lu}'’-]+(?:[ \t]+\p{Lu}[\p{Lu}'’-]+)*\b").unwrap();
+}
+
+
+// camera and editorial directions written in capitals inside action, never characters
+const DIRECTIONS: [&str; 14] = ["POV", "CLOSE", "ANGLE", "INSERT", "SUPER", "BACK", "SCENE", "FLASHBACK",
+                                "CONTINUOUS", "LATER", "CUT", "FADE", "ON", "TO"];
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CastIssue {
+    Silent,
+    SpeaksOnce,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CastNote {
+    pub line: usize,
+    pub scene: String,
+    pub name: String,
+    pub issue: CastIssue,
+}
+
+
+fn speaks(name: &str, speakers: &[(&str, usize, String)]) -> bool {
+    speakers.iter().any(|(speaker, _, _)| {
+        *speaker == name || name.starts_with(&format!("{speaker} ")) || speaker.starts_with(&format!("{name} "))
+    })
+}
+
+
+// characters are introduced in capitals on their first appearance in action, so any run of
+// capitalized words that never turns up as a cue is a character without lines
+pub fn cast_notes(script: &Script) -> Vec<CastNote> {
+    let scenes = std::iter::once((None, &script.prelude)).chain(script.scenes.iter().map(|s| (Some(s), &s.elements)));
+
+    let mut introduced: Vec<(&str, usize, String)> = Vec::new();
+    let mut speakers: Vec<(&str, usize, String)> = Vec::new();
+    let mut counts: Vec<usize> = Vec::new();
+    for (scene, elements) in scenes {
+        let label = script.scene_label(&scene.map_or("0".to_string(), Scene::display_number));
+        for element in elements {
+            match &element.kind {
+                Kind::Direct{ text } => for name in PAT_CAPS.find_iter(text).map(|m| m.as_str()) {
+                    if !name.split_whitespace().any(|w| DIRECTIONS.contains(&w)) && !introduced.iter().any(|(n, _, _)| *n == name) {
+                        introduced.push((name, element.line, label.clone()));
+                    }
+                }
+                Kind::Dialogue(dialogue) => for name in dialogue.speakers() {
+                    match speakers.iter().position(|(n, _, _)| *n == name) {
+                        Some(index) => counts[index] += 1,
+                        None => {
+                            speakers.push((name, element.line, label.clone()));
+                            counts.push(1);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let silent = introduced.iter()
+        .filter(|(name, _, _)| !speaks(name, &speakers))
+        .map(|(name, line, scene)| CastNote{ line: *line, scene: scene.clone(), name: name.to_string(), issue: CastIssue::Silent });
+    let once = speakers.iter()
+        .zip(&counts)
+        .filter(|(_, &count)| count == 1)
+        .map(|((name, line, scene), _)| CastNote{ line: *line, scene: scene.clone(), name: name.to_string(), issue: CastIssue::SpeaksOnce });
+
+    let mut notes: Vec<CastNote> = silent.chain(once).collect();
+    notes.sort_by_key(|n| n.line);
+    notes
+}
+
+
+pub fn gen_cast(cmd: &CmdInfo) -> Result<Vec<CastNote>, HtmlError> {
+    Ok(cast_notes(&read_script(cmd)?))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             scene INT. BAR - NIGHT\n\
+             direct MARY JANE (30s) pours a drink. The BOUNCER watches. CLOSE ON the glass.\n\
+             MARY: Last call.\n\
+             GUS: One more.\n\
+             scene INT. BAR - LATER\n\
+             direct The BOUNCER leaves.\n\
+             MARY: Told you."
+        ).expect("parse failed");
+
+        assert_eq!(cast_notes(&script), vec![
+            CastNote{ line: 4, scene: "1".to_string(), name: "BOUNCER".to_string(), issue: CastIssue::Silent },
+            CastNote{ line: 6, scene: "1".to_string(), name: "GUS".to_string(), issue: CastIssue::SpeaksOnce },
+        ]);
+    }
+}
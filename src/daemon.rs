@@ -0,0 +1,339 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{ BufRead, BufReader, Write },
+    sync::{ atomic::{ AtomicBool, Ordering }, Arc, Mutex },
+    thread,
+    time::SystemTime,
+};
+use serde_json::{ json, Value };
+use crate::{
+    ast::*,
+    html::{ render_html_cached, HtmlError, SceneCache },
+    parse::{ read_script, use_buffered_reads },
+    pdf::{ gen_pdf, render_pdf, temp_html },
+    style::HouseStyle,
+    CmdInfo,
+    Engine,
+};
+
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SCRIPT_ERROR: i64 = -32000;
+
+
+// the per-user runtime directory keeps other users away from the socket where there is one
+#[cfg(unix)]
+pub fn default_address() -> String {
+    let dir = std::env::var_os("XDG_RUNTIME_DIR").map_or_else(std::env::temp_dir, std::path::PathBuf::from);
+    dir.join("scripts.sock").display().to_string()
+}
+
+#[cfg(not(unix))]
+pub fn default_address() -> String {
+    "127.0.0.1:4729".to_string()
+}
+
+
+// a tcp port is open to every local user, so each request has to carry the token written here at startup
+#[cfg(not(unix))]
+pub fn token_path() -> std::path::PathBuf {
+    std::env::temp_dir().join("scripts-daemon.token")
+}
+
+
+type Entry = Arc<Mutex<Option<(SystemTime, Script, SceneCache)>>>;
+type Cache = Arc<Mutex<HashMap<String, Entry>>>;
+
+
+struct RpcError(i64, String);
+
+impl From<HtmlError> for RpcError {
+    fn from(err: HtmlError) -> Self {
+        Self(SCRIPT_ERROR, err.to_string())
+    }
+}
+
+
+fn param<'a>(params: &'a Value, name: &str) -> Result<&'a str, RpcError> {
+    params.get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError(INVALID_PARAMS, format!("missing string parameter '{name}'")))
+}
+
+
+fn with_script<T>(cache: &Cache, cmd: &CmdInfo, f: impl FnOnce(&Script) -> T) -> Result<T, RpcError> {
+    with_scenes(cache, cmd, |script, _| f(script))
+}
+
+
+// a changed file is parsed again, but the scenes rendered from it carry over to the next build;
+// the map is only held to look up the entry, so one script's build never waits on another's
+fn with_scenes<T>(cache: &Cache, cmd: &CmdInfo, f: impl FnOnce(&Script, &mut SceneCache) -> T) -> Result<T, RpcError> {
+    let modified = fs::metadata(&cmd.infile).and_then(|m| m.modified()).map_err(HtmlError::from)?;
+    let entry = cache.lock().unwrap().entry(cmd.infile.clone()).or_default().clone();
+    let mut entry = entry.lock().unwrap();
+
+    if entry.as_ref().is_none_or(|(cached, _, _)| *cached != modified) {
+        let script = read_script(cmd)?;
+        let scenes = entry.take().map(|(_, _, scenes)| scenes).unwrap_or_default();
+        *entry = Some((modified, script, scenes));
+    }
+    let (_, script, scenes) = entry.as_mut().unwrap();
+    Ok(f(script, scenes))
+}
+
+
+fn stats(script: &Script) -> Value {
+    let mut characters: Vec<&str> = script.elements()
+        .filter_map(|(_, e)| match &e.kind {
+            Kind::Dialogue(dialogue) => Some(dialogue.speakers()),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    characters.sort_unstable();
+    characters.dedup();
+
+    json!({
+        "scenes": script.scenes.iter().filter(|s| !s.omitted).count(),
+        "elements": script.elements().count(),
+        "dialogue": script.elements().filter(|(_, e)| matches!(e.kind, Kind::Dialogue(_))).count(),
+        "characters": characters,
+        "acts": script.acts().len(),
+        "todos": script.todos().len(),
+    })
+}
+
+
+fn call(method: &str, params: &Value, cache: &Cache, base: &CmdInfo) -> Result<Value, RpcError> {
+    let mut cmd = base.clone();
+    if matches!(method, "parse"|"stats"|"query"|"build") {
+        cmd.infile = param(params, "path")?.to_string();
+    }
+
+    match method {
+        "parse" => with_script(cache, &cmd, |s| serde_json::to_value(s).map_err(HtmlError::from))?.map_err(RpcError::from),
+        "stats" => with_script(cache, &cmd, stats),
+        "query" => match param(params, "what")? {
+            "outline" => Ok(with_script(cache, &cmd, |s| s.outline().iter().map(|item| match item {
+                OutlineItem::Section{ line, depth, text } => json!({ "type": "section", "line": line, "depth": depth, "text": text }),
+                OutlineItem::Synopsis{ line, text } => json!({ "type": "synopsis", "line": line, "text": text }),
+                OutlineItem::Scene{ line, label, heading } => json!({ "type": "scene", "line": line, "label": label, "heading": heading }),
+            }).collect())?),
+            "todos" => Ok(with_script(cache, &cmd, |s| s.todos().iter()
+                .map(|t| json!({ "line": t.line, "scene": t.label, "text": t.text }))
+                .collect())?),
+            "acts" => Ok(with_script(cache, &cmd, |s| s.acts().iter()
+                .map(|a| json!({ "name": a.name, "scenes": a.scenes, "elements": a.elements, "dialogue": a.dialogue }))
+                .collect())?),
+            other => Err(RpcError(INVALID_PARAMS, format!("unknown query '{other}'"))),
+        },
+        "build" => {
+            cmd.outfile = param(params, "output")?.to_string();
+            if !cmd.keep_intermediate {
+                cmd.html = temp_html();
+            }
+            if let Some(style) = params.get("style").and_then(Value::as_str) {
+                cmd.style = Some(HouseStyle::from_name(style).ok_or_else(|| RpcError(INVALID_PARAMS, format!("unknown house style '{style}'")))?);
+            }
+            let status = with_scenes(cache, &cmd, |s, scenes| -> Result<_, HtmlError> {
+                if cmd.engine == Engine::Native {
+                    return render_pdf(s, &cmd)
+                }
+                fs::write(&cmd.html, render_html_cached(s, &cmd, scenes)?)?;
+                gen_pdf(&cmd)
+            })??;
+            Ok(json!({ "success": status.success() }))
+        }
+        "shutdown" => Ok(Value::Null),
+        _ => Err(RpcError(METHOD_NOT_FOUND, format!("unknown method '{method}'"))),
+    }
+}
+
+
+fn respond(line: &str, cache: &Cache, cmd: &CmdInfo, token: Option<&str>) -> (Value, bool) {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(err) => return (json!({ "jsonrpc": "2.0", "id": null, "error": { "code": PARSE_ERROR, "message": err.to_string() } }), false),
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    if token.is_some_and(|token| request.get("token").and_then(Value::as_str) != Some(token)) {
+        return (json!({ "jsonrpc": "2.0", "id": id, "error": { "code": INVALID_REQUEST, "message": "missing or wrong token" } }), false)
+    }
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        return (json!({ "jsonrpc": "2.0", "id": id, "error": { "code": INVALID_REQUEST, "message": "missing method" } }), false)
+    };
+
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    let response = match call(method, &params, cache, cmd) {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(RpcError(code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+    };
+    (response, method == "shutdown")
+}
+
+
+fn serve(stream: impl std::io::Read + Write, cache: Cache, cmd: CmdInfo, token: Option<&str>) -> bool {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+
+    while reader.read_line(&mut line).is_ok_and(|n| n > 0) {
+        let (response, shutdown) = respond(line.trim(), &cache, &cmd, token);
+        line.clear();
+
+        let stream = reader.get_mut();
+        if writeln!(stream, "{response}").and_then(|_| stream.flush()).is_err() {
+            break
+        }
+        if shutdown {
+            return true
+        }
+    }
+    false
+}
+
+
+// a socket left behind by a daemon that died is replaced, anything else at the address is left alone
+#[cfg(unix)]
+pub fn run_daemon(cmd: &CmdInfo, address: &str) -> std::io::Result<()> {
+    use std::os::unix::fs::{ DirBuilderExt, FileTypeExt, PermissionsExt };
+
+    use_buffered_reads();
+    if let Ok(meta) = fs::symlink_metadata(address) {
+        if !meta.file_type().is_socket() {
+            return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, format!("{address} exists and is not a socket")))
+        }
+        if std::os::unix::net::UnixStream::connect(address).is_ok() {
+            return Err(std::io::Error::new(std::io::ErrorKind::AddrInUse, format!("a daemon is already listening on {address}")))
+        }
+        fs::remove_file(address)?;
+    }
+
+    // bound where only we can reach it and moved into place once it is private, the umask never applies
+    let private = std::path::Path::new(address).with_file_name(format!(".scripts-bind-{}", std::process::id()));
+    fs::DirBuilder::new().mode(0o700).create(&private)?;
+    let bound = private.join("sock");
+    let listener = std::os::unix::net::UnixListener::bind(&bound).and_then(|listener| {
+        fs::set_permissions(&bound, fs::Permissions::from_mode(0o600))?;
+        fs::rename(&bound, address)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_file(&bound);
+    fs::remove_dir(&private)?;
+
+    let wake = address.to_string();
+    listen(listener?.incoming(), cmd, None, move || drop(std::os::unix::net::UnixStream::connect(&wake)));
+    fs::remove_file(address)
+}
+
+
+#[cfg(not(unix))]
+pub fn run_daemon(cmd: &CmdInfo, address: &str) -> std::io::Result<()> {
+    use std::hash::{ BuildHasher, Hasher };
+
+    use_buffered_reads();
+    // the std hasher keys are random per instance, two of them make a 128 bit token
+    let random = || std::collections::hash_map::RandomState::new().build_hasher().finish();
+    let token = format!("{:016x}{:016x}", random(), random());
+    let listener = std::net::TcpListener::bind(address)?;
+    fs::write(token_path(), &token)?;
+
+    let wake = address.to_string();
+    listen(listener.incoming(), cmd, Some(token), move || drop(std::net::TcpStream::connect(&wake)));
+    fs::remove_file(token_path())
+}
+
+
+fn listen<S, W>(incoming: impl Iterator<Item = std::io::Result<S>>, cmd: &CmdInfo, token: Option<String>, wake: W)
+where
+    S: std::io::Read + Write + Send + 'static,
+    W: Fn() + Clone + Send + 'static,
+{
+    let cache: Cache = Arc::default();
+    let stop = Arc::new(AtomicBool::new(false));
+
+    // a shutdown request raises the flag and wakes the blocking accept with a dummy connection
+    for stream in incoming.flatten() {
+        if stop.load(Ordering::SeqCst) {
+            break
+        }
+        let (cache, cmd, stop, wake, token) = (cache.clone(), cmd.clone(), stop.clone(), wake.clone(), token.clone());
+        thread::spawn(move || if serve(stream, cache, cmd, token.as_deref()) {
+            stop.store(true, Ordering::SeqCst);
+            wake();
+        });
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("script.txt");
+        fs::write(&path, "title\nsubtitle\nscene EXT. LOC - DAY\nALEX: Hi\nTODO fix\nend\n").unwrap();
+        let path = path.display().to_string();
+        let cache = Cache::default();
+        let cmd = CmdInfo::default();
+
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "stats", "params": { "path": path } });
+        let (response, _) = respond(&request.to_string(), &cache, &cmd, None);
+        assert_eq!(response["result"]["scenes"], 1);
+        assert_eq!(response["result"]["characters"], json!(["ALEX"]));
+        assert_eq!(cache.lock().unwrap().len(), 1);
+
+        let request = json!({ "jsonrpc": "2.0", "id": 2, "method": "query", "params": { "path": path, "what": "todos" } });
+        let (response, _) = respond(&request.to_string(), &cache, &cmd, None);
+        assert_eq!(response["result"][0]["text"], "fix");
+
+        let (response, _) = respond(r#"{"jsonrpc":"2.0","id":3,"method":"format"}"#, &cache, &cmd, None);
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        let (response, _) = respond("not json", &cache, &cmd, None);
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+        let (_, shutdown) = respond(r#"{"jsonrpc":"2.0","id":4,"method":"shutdown"}"#, &cache, &cmd, None);
+        assert!(shutdown);
+
+        let (response, shutdown) = respond(r#"{"jsonrpc":"2.0","id":5,"method":"shutdown","token":"wrong"}"#, &cache, &cmd, Some("right"));
+        assert_eq!((response["error"]["code"].as_i64(), shutdown), (Some(INVALID_REQUEST), false));
+        let (_, shutdown) = respond(r#"{"jsonrpc":"2.0","id":6,"method":"shutdown","token":"right"}"#, &cache, &cmd, Some("right"));
+        assert!(shutdown);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn address() {
+        let temp = tempfile::tempdir().unwrap();
+        let address = temp.path().join("daemon.sock");
+        fs::write(&address, "keep").unwrap();
+
+        let err = run_daemon(&CmdInfo::default(), &address.display().to_string()).expect_err("file replaced");
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&address).unwrap(), "keep");
+        fs::remove_file(&address).unwrap();
+
+        // the socket only appears once it is private, and is gone with the bind directory after shutdown
+        use std::os::unix::fs::PermissionsExt;
+        let socket = address.display().to_string();
+        let daemon = thread::spawn(move || run_daemon(&CmdInfo::default(), &socket));
+        while !address.exists() {
+            thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert_eq!(fs::metadata(&address).unwrap().permissions().mode() & 0o777, 0o600);
+        let mut stream = std::os::unix::net::UnixStream::connect(&address).unwrap();
+        writeln!(stream, r#"{{"jsonrpc":"2.0","id":1,"method":"shutdown"}}"#).unwrap();
+        let mut response = String::new();
+        BufReader::new(&stream).read_line(&mut response).unwrap();
+        assert!(response.contains("\"result\":null"));
+        daemon.join().unwrap().expect("daemon failed");
+        assert!(!address.exists());
+        assert!(!address.with_file_name(format!(".scripts-bind-{}", std::process::id())).exists());
+    }
+}
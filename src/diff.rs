@@ -0,0 +1,196 @@
+use serde::Serialize;
+use crate::{
+    ast::*,
+    html::HtmlError,
+    parse::parse_file,
+};
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "kebab-case")]
+pub enum Change {
+    SceneAdded{ scene: String, line: usize, heading: String },
+    SceneRemoved{ scene: String, line: usize, heading: String },
+    Added{ scene: String, line: usize, text: String },
+    Removed{ scene: String, line: usize, text: String },
+    Changed{ scene: String, line: usize, old: String, new: String },
+}
+
+
+enum Op {
+    Keep(usize, usize),
+    Remove(usize),
+    Add(usize),
+}
+
+
+fn lcs<T>(old: &[T], new: &[T], same: impl Fn(&T, &T) -> bool) -> Vec<Op> {
+    let mut table = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            table[i][j] = if same(&old[i], &new[j]) {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut ops = Vec::new();
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && same(&old[i], &new[j]) {
+            ops.push(Op::Keep(i, j));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || table[i][j + 1] >= table[i + 1][j]) {
+            ops.push(Op::Add(j));
+            j += 1;
+        } else {
+            ops.push(Op::Remove(i));
+            i += 1;
+        }
+    }
+    ops
+}
+
+
+fn describe(kind: &Kind) -> String {
+    match kind {
+        Kind::Dialogue(Dialogue{ name, lines, .. }) => {
+            let lines: Vec<String> = lines.iter().map(|l| match l {
+                DialogueLine::Parens(text) => format!("({text})"),
+                DialogueLine::Speech(text) => text.clone(),
+            }).collect();
+            format!("{name}: {}", lines.join(" "))
+        }
+        kind => {
+            let value = serde_json::to_value(kind).unwrap_or_default();
+            let tag = value["kind"].as_str().unwrap_or_default().to_string();
+            match value["text"].as_str() {
+                Some(text) if !text.is_empty() => format!("{tag} {text}"),
+                _ => tag,
+            }
+        }
+    }
+}
+
+
+fn same_variant(old: &Kind, new: &Kind) -> bool {
+    match (old, new) {
+        (Kind::Dialogue(old), Kind::Dialogue(new)) => old.name == new.name,
+        _ => std::mem::discriminant(old) == std::mem::discriminant(new),
+    }
+}
+
+
+fn diff_elements(scene: &str, old: &[Element], new: &[Element], changes: &mut Vec<Change>) {
+    let ops = lcs(old, new, |a, b| a.kind == b.kind);
+
+    // within a run of edits, an element replaced by one of the same kind reads better as a single change
+    for hunk in ops.split(|op| matches!(op, Op::Keep(..))) {
+        let mut removed: Vec<&Element> = hunk.iter()
+            .filter_map(|op| match op {
+                Op::Remove(i) => Some(&old[*i]),
+                _ => None,
+            })
+            .collect();
+
+        for op in hunk {
+            let Op::Add(j) = op else { continue };
+            let element = &new[*j];
+            match removed.iter().position(|e| same_variant(&e.kind, &element.kind)) {
+                Some(p) => {
+                    let old = removed.remove(p);
+                    changes.push(Change::Changed{ scene: scene.to_string(), line: element.line, old: describe(&old.kind), new: describe(&element.kind) });
+                }
+                None => changes.push(Change::Added{ scene: scene.to_string(), line: element.line, text: describe(&element.kind) }),
+            }
+        }
+        for e in removed {
+            changes.push(Change::Removed{ scene: scene.to_string(), line: e.line, text: describe(&e.kind) });
+        }
+    }
+}
+
+
+pub fn diff_scripts(old: &Script, new: &Script) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_elements("prelude", &old.prelude, &new.prelude, &mut changes);
+
+    for op in lcs(&old.scenes, &new.scenes, |a, b| a.heading == b.heading && a.omitted == b.omitted) {
+        match op {
+            Op::Keep(i, j) => {
+                let scene = new.scene_label(&new.scenes[j].display_number());
+                diff_elements(&scene, &old.scenes[i].elements, &new.scenes[j].elements, &mut changes);
+            }
+            Op::Remove(i) => {
+                let scene = &old.scenes[i];
+                changes.push(Change::SceneRemoved{ scene: old.scene_label(&scene.display_number()), line: scene.line, heading: scene.heading.clone() });
+            }
+            Op::Add(j) => {
+                let scene = &new.scenes[j];
+                changes.push(Change::SceneAdded{ scene: new.scene_label(&scene.display_number()), line: scene.line, heading: scene.heading.clone() });
+            }
+        }
+    }
+
+    changes
+}
+
+
+pub fn gen_diff(old: &str, new: &str) -> Result<Vec<Change>, HtmlError> {
+    let (old, _) = parse_file(old, &[])?;
+    let (new, _) = parse_file(new, &[])?;
+    Ok(diff_scripts(&old, &new))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn changes() {
+        let old = parse("title\nsubtitle\n\
+                         scene EXT. LOC - DAY\n\
+                         ALEX: Hi\n\
+                         direct Alex waves.\n\
+                         scene INT. CAR - DAY\n\
+                         direct Engine on.\n\
+                         end").expect("parse failed");
+        let new = parse("title\nsubtitle\n\
+                         scene EXT. LOC - DAY\n\
+                         ALEX: Hello\n\
+                         direct Alex waves.\n\
+                         SAM: Bye\n\
+                         scene INT. BUS - DAY\n\
+                         direct Engine on.\n\
+                         end").expect("parse failed");
+
+        assert_eq!(diff_scripts(&old, &new), vec![
+            Change::Changed{ scene: "1".into(), line: 4, old: "ALEX: Hi".into(), new: "ALEX: Hello".into() },
+            Change::Added{ scene: "1".into(), line: 6, text: "SAM: Bye".into() },
+            Change::SceneAdded{ scene: "2".into(), line: 7, heading: "INT. BUS - DAY".into() },
+            Change::SceneRemoved{ scene: "2".into(), line: 6, heading: "INT. CAR - DAY".into() },
+        ]);
+    }
+
+    #[test]
+    fn described() {
+        let old = parse("title\nsubtitle\n\
+                         scene INT. CAR - DAY\n\
+                         direct Engine on.\n\
+                         end").expect("parse failed");
+        let new = parse("title\nsubtitle\n\
+                         scene INT. CAR - DAY\n\
+                         direct Engine off.").expect("parse failed");
+
+        assert_eq!(diff_scripts(&old, &new), vec![
+            Change::Changed{ scene: "1".into(), line: 4, old: "direct Engine on.".into(), new: "direct Engine off.".into() },
+            Change::Removed{ scene: "1".into(), line: 5, text: "end".into() },
+        ]);
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use serde::Deserialize;
+use serde_json::{ Map, Value };
+use yaml_rust2::{ Yaml, YamlLoader };
+use crate::parse::Diagnostic;
+
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    pub subtitle: Option<String>,
+    pub author: Option<String>,
+    pub draft: Option<String>,
+    pub doctype: Option<String>,
+    pub episode: Option<String>,
+    pub characters: Vec<String>,
+    pub aliases: BTreeMap<String, String>,
+    pub locations: Vec<String>,
+    pub variables: BTreeMap<String, String>,
+    pub keywords: BTreeMap<String, String>,
+    pub prefixes: BTreeMap<String, String>,
+}
+
+
+fn from_yaml(yaml: &Yaml) -> Value {
+    match yaml {
+        Yaml::Real(s)|Yaml::String(s) => Value::String(s.clone()),
+        Yaml::Integer(i) => Value::String(i.to_string()),
+        Yaml::Boolean(b) => Value::String(b.to_string()),
+        Yaml::Array(items) => Value::Array(items.iter().map(from_yaml).collect()),
+        Yaml::Hash(hash) => Value::Object(hash.iter()
+            .map(|(k, v)| (from_yaml(k).as_str().unwrap_or_default().to_string(), from_yaml(v)))
+            .collect()),
+        Yaml::Alias(_)|Yaml::Null|Yaml::BadValue => Value::Null,
+    }
+}
+
+
+fn from_toml(toml: &toml::Value) -> Value {
+    match toml {
+        toml::Value::String(s) => Value::String(s.clone()),
+        toml::Value::Array(items) => Value::Array(items.iter().map(from_toml).collect()),
+        toml::Value::Table(table) => Value::Object(table.iter().map(|(k, v)| (k.clone(), from_toml(v))).collect()),
+        other => Value::String(other.to_string()),
+    }
+}
+
+
+pub(crate) fn front_matter(src: &str) -> Result<Option<(FrontMatter, usize)>, Diagnostic> {
+    let mut lines = src.lines();
+    let fence = match lines.next().map(str::trim) {
+        Some(fence @ ("---"|"+++")) => fence,
+        _ => return Ok(None),
+    };
+
+    let body: Vec<&str> = lines.by_ref().take_while(|l| l.trim() != fence).collect();
+    if body.len() + 1 == src.lines().count() {
+        return Err(Diagnostic::new(1, format!("front matter is never closed with '{fence}'")))
+    }
+    let lines = body.len() + 2;
+    let body = body.join("\n");
+    let invalid = |e: &dyn std::fmt::Display| Diagnostic::new(1, format!("invalid front matter: {e}"));
+
+    let value = if fence == "---" {
+        let docs = YamlLoader::load_from_str(&body).map_err(|e| invalid(&e))?;
+        docs.first().map_or(Value::Null, from_yaml)
+    } else {
+        let table: toml::Table = body.parse().map_err(|e| invalid(&e))?;
+        from_toml(&toml::Value::Table(table))
+    };
+    let value = if value.is_null() { Value::Object(Map::new()) } else { value };
+
+    let front = serde_json::from_value(value).map_err(|e| invalid(&e))?;
+    Ok(Some((front, lines)))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let yaml = "---\ntitle: Pilot\nepisode: 105\ncharacters: [Alex, Sam]\naliases:\n  JON: JONATHAN\n---\nscene EXT. LOC - DAY";
+        let (front, lines) = front_matter(yaml).expect("yaml failed").expect("yaml not found");
+        assert_eq!(lines, 7);
+        assert_eq!(front.title.as_deref(), Some("Pilot"));
+        assert_eq!(front.episode.as_deref(), Some("105"));
+        assert_eq!(front.characters, vec!["Alex", "Sam"]);
+        assert_eq!(front.aliases.get("JON").map(String::as_str), Some("JONATHAN"));
+
+        let toml = "+++\ntitle = \"Pilot\"\nepisode = 105\n[variables]\nTOWN = \"SPRINGFIELD\"\n+++\n";
+        let (front, lines) = front_matter(toml).expect("toml failed").expect("toml not found");
+        assert_eq!(lines, 6);
+        assert_eq!(front.episode.as_deref(), Some("105"));
+        assert_eq!(front.variables.get("TOWN").map(String::as_str), Some("SPRINGFIELD"));
+
+        assert_eq!(front_matter("title\nsubtitle"), Ok(None));
+        assert_eq!(front_matter("---\ntitle: Pilot").unwrap_err().message, "front matter is never closed with '---'");
+        assert!(front_matter("---\nunknown: field\n---").unwrap_err().message.starts_with("invalid front matter"));
+    }
+}
@@ -1,11 +1,36 @@
 use std::{
     fs,
     fmt::Write,
+    io::{ self, Write as _ },
+    collections::HashMap,
+    path::Path,
 };
 use lazy_static::lazy_static;
-use thiserror::Error;
 use regex::Regex;
-use crate::CmdInfo;
+use thiserror::Error;
+use crate::{
+    ast::*,
+    layout::*,
+    outline::cards_html,
+    parse::*,
+    pdf::write_pdf,
+    progress::{ finish_progress, report },
+    render::renderer,
+    state::fnv,
+    style::*,
+    typeset::typeset,
+    verbose,
+    CmdInfo,
+    ColorCharacters,
+    Emit,
+    Engine,
+    TitlePage,
+};
+
+
+fn join_diagnostics(diagnostics: &[Diagnostic]) -> String {
+    diagnostics.iter().map(|d| d.to_string()).collect::<Vec<_>>().join("\n")
+}
 
 
 #[derive(Error, Debug)]
@@ -14,207 +39,532 @@ pub enum HtmlError {
     IoError(#[from] std::io::Error),
     #[error(transparent)]
     FormatError(#[from] std::fmt::Error),
-    #[error("line {line} - invalid syntax (expected {expected} after {after})")]
-    SyntaxError{
-        line: usize,
-        expected: String,
-        after: String,
-    },
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+    #[error("{}", join_diagnostics(.0))]
+    ParseError(Vec<Diagnostic>),
+    #[error("no scene numbered {0}")]
+    SceneNotFound(String),
+    #[error("{0}")]
+    Unsupported(&'static str),
+    #[error("failed to encrypt pdf: {0}")]
+    EncryptError(String),
     #[error("unknown html conversion error")]
     Unknown,
 }
 
 
-pub fn trim_ignored((num, line): (usize, &str)) -> (usize, &str) {
-    if let Some((start, _)) = line.split_once("* ") {
-        (num, start.trim())
-    } else {
-        (num, line.trim())
-    }
+fn inline(text: &str) -> String {
+    markup(text, [("<b>", "</b>"), ("<i>", "</i>"), ("<u>", "</u>")])
 }
 
 
-struct Segment<'a> {
-    line: usize,
-    mode: &'a str,
-    text: Vec<&'a str>,
+// bold, italic and underline markup replaced by the given open/close pairs
+pub(crate) fn markup(text: &str, tags: [(&str, &str); 3]) -> String {
+    lazy_static! {
+        static ref PAT_BOLD: Regex = Regex::new(r"\*\*(\S(?:.*?\S)?)\*\*").unwrap();
+        static ref PAT_ITALIC: Regex = Regex::new(r"\*(\S(?:.*?\S)?)\*").unwrap();
+        static ref PAT_UNDERLINE: Regex = Regex::new(r"(^|[\s(])_(\S(?:.*?\S)?)_($|[\s.,;:!?)])").unwrap();
+    }
+    let [(b, b_end), (i, i_end), (u, u_end)] = tags;
+
+    let text = text.replace("\\\\", "\u{E000}")
+                   .replace("\\*", "\u{E001}")
+                   .replace("\\_", "\u{E002}");
+    let text = PAT_BOLD.replace_all(&text, format!("{b}$1{b_end}"));
+    let text = PAT_ITALIC.replace_all(&text, format!("{i}$1{i_end}"));
+    PAT_UNDERLINE.replace_all(&text, format!("$1{u}$2{u_end}$3"))
+        .replace('\u{E000}', "\\")
+        .replace('\u{E001}', "*")
+        .replace('\u{E002}', "_")
 }
 
 
-struct Segments<'a> {
-    lines: std::vec::IntoIter<(usize, &'a str)>,
-    term: bool
+fn render_element(element: &Element, rules: &Rules, contd: bool) -> Result<String, HtmlError> {
+    match &element.kind {
+        Kind::Montage => Ok("<div class=\"header\">BEGIN MONTAGE:</div>\n".to_string()),
+        Kind::MonEnd  => Ok("<div class=\"header\">END MONTAGE.</div>\n".to_string()),
+        Kind::PageBreak => Ok("<div class=\"pagebreak\"></div>\n".to_string()),
+        Kind::End     => Ok("<div class=\"trans\">FADE OUT.</div>\n<div class=\"act\"><u>THE END</u></div>\n".to_string()),
+        Kind::Todo{ text } if text.is_empty() => Ok("<div class=\"header\">TODO ==============================</div>\n".to_string()),
+        Kind::Todo{ text }    => Ok(format!("<div class=\"header\">TODO == {}</div>\n", text.to_uppercase())),
+        Kind::Note{ text }    => Ok(format!("<div class=\"note\">{}</div>\n", inline(text))),
+        Kind::Section{ .. }|Kind::Synopsis{ .. } => Ok(String::new()),
+        Kind::Direct{ text }  => Ok(format!("<div class=\"direct\">{}</div>\n", inline(&rules.action(text)))),
+        Kind::Parens{ text }  => Ok(format!("<div class=\"parens\">({})</div>\n", inline(text))),
+        Kind::Speech{ text }  => Ok(format!("<div class=\"speech\">{}</div>\n", inline(text))),
+        Kind::Lyrics{ text }  => Ok(format!("<div class=\"lyrics\">{}</div>\n", inline(&text.replace(" / ", "<br>")))),
+        Kind::Subhead{ text } => Ok(format!("<div class=\"header\"><h2>{}</h2></div>\n", text.to_uppercase())),
+        Kind::Trans{ text }   => Ok(format!("<div class=\"trans\">{}</div>\n", text.to_uppercase())),
+        Kind::Chyron{ text }  => Ok(format!("<div class=\"direct\">CHYRON: {}</div>\n", inline(text))),
+        Kind::Sfx{ text }     => Ok(format!("<div class=\"cue\">SFX: {}</div>\n", inline(&text.to_uppercase()))),
+        Kind::Music{ text }   => Ok(format!("<div class=\"cue\">MUSIC: {}</div>\n", inline(&text.to_uppercase()))),
+        Kind::Header{ text }  => Ok(format!("<div class=\"header\">{}</div>\n", text)),
+        Kind::ActTag{ text }|Kind::Act{ text } => Ok(format!("<div class=\"act\"><h1><u>{}</u></h1></div>\n", text)),
+        Kind::ActEnd{ text }  => Ok(format!("<div class=\"act\"><u>END OF {}</u></div>\n", text)),
+        Kind::Episode{ text } => Ok(format!("<div class=\"act\"><h1>{}</h1></div>\n", text)),
+        Kind::ComicPage{ number, text } if text.is_empty() => Ok(format!("<div class=\"header\"><h2><u>PAGE {number}</u></h2></div>\n")),
+        Kind::ComicPage{ number, text } => Ok(format!("<div class=\"header\"><h2><u>PAGE {number}</u> - {}</h2></div>\n", text.to_uppercase())),
+        Kind::Panel{ number, text } if text.is_empty() => Ok(format!("<div class=\"header\">PANEL {number}</div>\n")),
+        Kind::Panel{ number, text } => Ok(format!("<div class=\"header\">PANEL {number}</div>\n<div class=\"direct\">{}</div>\n", inline(text))),
+        Kind::Caption{ text } => Ok(format!("<div class=\"name\">CAPTION</div>\n<div class=\"speech\">{}</div>\n", inline(text))),
+        Kind::Dialogue(Dialogue{ name, lines, .. }) => {
+            let mut result = String::new();
+            if contd {
+                writeln!(result, "<div class=\"name\">{} (CONT'D)</div>", name)?;
+            } else {
+                writeln!(result, "<div class=\"name\">{}</div>", name)?;
+            }
+            for line in lines {
+                match line {
+                    DialogueLine::Parens(text) => writeln!(result, "<div class=\"parens\">{}</div>", inline(text))?,
+                    DialogueLine::Speech(text) => writeln!(result, "<div class=\"speech\">{}</div>", inline(&attach_ending(text)))?,
+                }
+            }
+            Ok(result)
+        }
+    }
 }
 
-impl<'a> Segments<'a> {
-    fn new(src: &'a str) -> Self {
-        Self{ lines: src.lines()
-                        .enumerate()
-                        .map(trim_ignored)
-                        .filter(|(_, l)| !l.is_empty())
-                        .collect::<Vec<(usize, &'a str)>>()
-                        .into_iter(),
-            term: false
-        }
+
+fn render_heading(script: &Script, scene: &Scene, rules: &Rules) -> String {
+    if scene.omitted {
+        return format!("<div class=\"scene\"><h1>SCENE {} OMITTED</h1></div>\n", script.scene_label(&scene.display_number()))
+    }
+    if script.redacted.contains(&scene.line) {
+        return format!("<div class=\"pagebreak\"></div>\n<div class=\"scene\"><h1>SCENE {} RESTRICTED</h1></div>\n<div class=\"pagebreak\"></div>\n",
+                       script.scene_label(&scene.display_number()))
+    }
+    if scene.number == 0 || rules.scene_numbers == SceneNumbers::Hidden {
+        return format!("<div class=\"scene\"><h1>{}</h1></div>\n", rules.heading(&scene.heading))
     }
 
-    fn next_whole(&mut self) -> Option<(usize, Vec<&'a str>)> {
-        if self.term { return None }
+    let label = script.scene_label(&scene.locked.clone().unwrap_or_else(|| rules.scene_label(scene.number)));
+    let pad = "&nbsp;".repeat(4usize.saturating_sub(label.len()));
+    let right = if rules.scene_numbers == SceneNumbers::Both {
+        format!("<span class=\"scene-num-right\">{label}</span>")
+    } else {
+        String::new()
+    };
+    format!("<div class=\"scene\"><h1>{}{} {}{}</h1></div>\n", pad, label, rules.heading(&scene.heading), right)
+}
 
-        let (line, mut val) = self.lines.next()?;
 
-        if val == "***" { return None }
+fn render_items(result: &mut String, script: &Script, items: &[Item], rules: &Rules, cmd: &CmdInfo, counters: &mut (u32, u32), numbers: &mut HashMap<usize, u32>) -> Result<(), HtmlError> {
+    let mut block = String::new();
+    let mut lines = 0;
+    let (cues, dialogue) = counters;
 
-        let mut text = Vec::new();
+    for item in items {
+        if let Item::Heading(_) = item {
+            flush_block(result, &mut block, lines, cmd);
+            lines = 0;
+        }
 
-        while let Some(strip) = val.strip_suffix('\\') {
-            text.push(strip.trim());
-            val = self.lines.next()?.1;
-            if val == "***" {
-                self.term = true;
-                return Some((line + 1, text))
+        match item {
+            Item::Heading(scene) => {
+                if script.revised.contains(&scene.line) {
+                    block.push_str("<div class=\"rev\">*</div>");
+                }
+                block.push_str(&render_heading(script, scene, rules));
+                lines += heading_lines(scene);
+            }
+            Item::Element{ element, contd, more } => {
+                if script.revised.contains(&element.line) {
+                    block.push_str("<div class=\"rev\">*</div>");
+                }
+                match element.kind {
+                    Kind::Sfx{ .. }|Kind::Music{ .. } if rules.number_cues => {
+                        *cues += 1;
+                        write!(block, "<div class=\"num\">{cues}.</div>")?;
+                    }
+                    // the continuation of dialogue split across a page keeps the number it was given
+                    Kind::Dialogue(_) if cmd.number_dialogue.is_some() => if let Some(number) = numbers.remove(&element.line) {
+                        write!(block, "<div class=\"num\">{number}.</div>")?;
+                    }
+                    Kind::Panel{ .. } => *dialogue = 0,
+                    Kind::Dialogue(_)|Kind::Caption{ .. }|Kind::Sfx{ .. } if rules.number_dialogue => {
+                        *dialogue += 1;
+                        write!(block, "<div class=\"num\">{dialogue}.</div>")?;
+                    }
+                    _ => (),
+                }
+                match (&element.kind, render_chip(&element.kind).filter(|_| annotated(cmd))) {
+                    (_, Some(chip)) => block.push_str(&chip),
+                    (Kind::Dialogue(dialogue), None) if highlighted(cmd, dialogue) => {
+                        write!(block, "<div class=\"highlight\">{}</div>", render_element(element, rules, *contd)?)?;
+                    }
+                    (Kind::Dialogue(dialogue), None) if colored(cmd) => {
+                        let hue = character_hue(dialogue.speakers().first().copied().unwrap_or(&dialogue.name));
+                        write!(block, "<div class=\"tint\" style=\"background: hsl({hue} 70% 92%)\">{}</div>", render_element(element, rules, *contd)?)?;
+                    }
+                    (_, None) => block.push_str(&render_element(element, rules, *contd)?),
+                }
+                if *more {
+                    block.push_str("<div class=\"more\">(MORE)</div>\n");
+                }
+                lines += estimate_lines(element);
             }
         }
+    }
+    flush_block(result, &mut block, lines, cmd);
 
-        text.push(val);
+    Ok(())
+}
 
-        Some((line + 1, text))
+
+fn flush_block(result: &mut String, block: &mut String, lines: u32, cmd: &CmdInfo) {
+    if block.is_empty() {
+        return
+    }
+    if !cmd.paginate && cmd.keep_scenes.is_some_and(|keep| lines < keep) {
+        result.push_str("<div class=\"keep\">\n");
+        result.push_str(block);
+        result.push_str("</div>\n");
+    } else {
+        result.push_str(block);
     }
+    block.clear();
 }
 
-impl<'a> Iterator for Segments<'a> {
-    type Item = Segment<'a>;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        let (line, mut text) = self.next_whole()?;
-        let first = text.remove(0);
+fn annotated(cmd: &CmdInfo) -> bool {
+    cmd.emit == Emit::Html && !cmd.no_annotations
+}
 
-        if let Some((mode, rest)) = first.split_once(char::is_whitespace) {
-            text.insert(0, rest.trim());
-            Some(Segment{ line, mode, text })
-        } else {
-            Some(Segment{ line, mode: first, text: Vec::new() })
-        }
+
+fn colored(cmd: &CmdInfo) -> bool {
+    match cmd.color_characters {
+        ColorCharacters::Never => false,
+        ColorCharacters::Preview => cmd.emit == Emit::Html,
+        ColorCharacters::Always => true,
     }
 }
 
 
-#[derive(Debug, Default, Clone, PartialEq, Eq)]
-struct Context {
-    scene: u32,
-    title: String,
-    subtitle: String,
+fn highlighted(cmd: &CmdInfo, dialogue: &Dialogue) -> bool {
+    cmd.highlight.as_ref().is_some_and(|name| dialogue.speakers().iter().any(|s| s.eq_ignore_ascii_case(name)))
 }
 
 
-fn get_line(segment: Segment, ctx: &mut Context) -> Result<String, HtmlError> {
-    lazy_static! {
-        static ref PAT_HEAD: Regex = Regex::new(r"^[^a-z]+$").unwrap();
-        static ref PAT_SCENE: Regex = Regex::new(r"(INT\.|EXT\.) [^a-z]+ - [^a-z]+").unwrap();
-        static ref PAT_SPEECH: Regex = Regex::new(r"(\w+(?: \((?:O\.S\.|V\.O\.)\))?):\s+(?:(\([A-Z][^\)]*\) )?([^\(]+))+").unwrap();
-        static ref PAT_EXTRACT: Regex = Regex::new(r"\s*(\([^\)]+\))?((?:\s+[^\(]+)+)").unwrap();
-    }
-
-    let Segment{ line, mode, text } = segment;
-    let text = text.join(" ")
-                   .replace("$title", &ctx.title)
-                   .replace("$subtitle", &ctx.subtitle);
-
-    match mode {
-        "montage" if  text.is_empty() => Ok("<div class=\"header\">BEGIN MONTAGE:</div>\n".to_string()),
-        "mon-end" if  text.is_empty() => Ok("<div class=\"header\">END MONTAGE.</div>\n".to_string()),
-        "TODO"    if  text.is_empty() => Ok("<div class=\"header\">TODO ==============================</div>\n".to_string()),
-        "TODO"    if !text.is_empty() => Ok(format!("<div class=\"header\">TODO == {}</div>\n", text.to_uppercase())),
-        "direct"  if !text.is_empty() => Ok(format!("<div class=\"direct\">{}</div>\n", text)),
-        "parens"  if !text.is_empty() => Ok(format!("<div class=\"parens\">({})</div>\n", text)),
-        "speech"  if !text.is_empty() => Ok(format!("<div class=\"speech\">{}</div>\n", text)),
-        "subhead" if !text.is_empty() => Ok(format!("<div class=\"header\"><h2>{}</h2></div>\n", text.to_uppercase())),
-        "trans"   if !text.is_empty() => Ok(format!("<div class=\"trans\">{}</div>\n", text.to_uppercase())),
-        "chyron"  if !text.is_empty() => Ok(format!("<div class=\"direct\">CHYRON: {text}</div>\n")),
-        "scene"   if !text.is_empty() && PAT_SCENE.is_match(&text) => {
-            ctx.scene += 1;
-            let count = 4 - ctx.scene.to_string().len();
-            let pad = vec!["&nbsp;"; count].join("");
-            Ok(format!("<div class=\"scene\"><h1>{}{} {}</h1></div>\n", pad, ctx.scene, text.to_uppercase()))
+// hashed rather than assigned in order, so a character keeps its color across drafts
+fn character_hue(name: &str) -> u64 {
+    fnv(0xcbf29ce484222325, name.as_bytes()) % 360
+}
+
+
+fn render_chip(kind: &Kind) -> Option<String> {
+    let (class, label, text) = match kind {
+        Kind::Note{ text } => ("note", "NOTE", text),
+        Kind::Todo{ text } => ("todo", "TODO", text),
+        Kind::Section{ text, .. } => ("beat", "BEAT", text),
+        Kind::Synopsis{ text } => ("beat", "SYNOPSIS", text),
+        _ => return None,
+    };
+    Some(format!("<div class=\"chip chip-{class}\"><b>{label}</b> {}</div>\n", inline(text)))
+}
+
+
+pub(crate) fn get_rules(script: &Script, cmd: &CmdInfo) -> Rules {
+    let mut rules = cmd.style
+        .or_else(|| script.doctype.as_deref().and_then(HouseStyle::from_name))
+        .unwrap_or_default()
+        .rules();
+    rules.scene_numbers = cmd.scene_numbers;
+    rules
+}
+
+
+fn layout_opts(cmd: &CmdInfo, rules: &Rules) -> Layout {
+    Layout{
+        page_lines: cmd.paginate.then_some(PAGE_LINES),
+        contd: rules.contd && !cmd.no_contd,
+        keep_scenes: cmd.keep_scenes,
+        keep_lines: cmd.keep_lines.unwrap_or(2),
+        notes: cmd.with_notes,
+        annotations: annotated(cmd),
+    }
+}
+
+
+pub(crate) fn has_title_page(cmd: &CmdInfo) -> bool {
+    match cmd.title_page {
+        TitlePage::Auto => cmd.range.is_none(),
+        TitlePage::Always => true,
+        TitlePage::Never => false,
+    }
+}
+
+
+// the pager run without rendering anything, exact for the native engine and as close as --paginate gets for webkit
+pub fn page_count(script: &Script, cmd: &CmdInfo) -> usize {
+    if cmd.engine == Engine::Native {
+        return typeset(script, cmd).len()
+    }
+    let rules = get_rules(script, cmd);
+    let opts = Layout{ page_lines: Some(PAGE_LINES), annotations: false, ..layout_opts(cmd, &rules) };
+    let pages = layout(script, &opts).into_iter().filter(|p| !p.items.is_empty()).count();
+    pages + has_title_page(cmd) as usize
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedOutput {
+    pub path: String,
+    pub bytes: Option<usize>,
+    pub pages: Option<usize>,
+}
+
+
+// everything is rendered in memory so layout problems surface, nothing is written and webkit is not run;
+// page counts are the pager's estimate as for --pages, and webkit pdfs have no size until they are printed
+pub fn dry_run(script: &Script, cmd: &CmdInfo) -> Result<Vec<PlannedOutput>, HtmlError> {
+    if let Some(dir) = &cmd.split_scenes {
+        let mut cmd = cmd.clone();
+        if cmd.title_page == TitlePage::Auto {
+            cmd.title_page = TitlePage::Never;
         }
-        "montage"|"mon-end" => {
-            Err(HtmlError::SyntaxError{ line, expected: "newline".to_string(), after: format!("montage delimiter '{mode}'") })
+        let html = cmd.emit == Emit::Html;
+        let mut outputs = Vec::new();
+        for (name, scene) in script.split_scenes() {
+            let path = Path::new(dir).join(format!("{name}.{}", if html { "html" } else { "pdf" })).display().to_string();
+            let bytes = render_html(&scene, &cmd)?.len();
+            outputs.push(PlannedOutput{ path, bytes: html.then_some(bytes), pages: (!html).then(|| page_count(&scene, &cmd)) });
         }
-        "direct"|"parens"|"speech"|"subhead"|"trans"|"chyron" => {
-            Err(HtmlError::SyntaxError{ line, expected: "content".to_string(), after: format!("block declaration '{mode}'") })
+        return Ok(outputs)
+    }
+
+    let planned = |bytes: Option<usize>, pages: Option<usize>| PlannedOutput{ path: cmd.outfile.clone(), bytes, pages };
+    let mut outputs = match cmd.emit {
+        Emit::Pdf if cmd.engine == Engine::Native => {
+            let sheets = typeset(script, cmd);
+            vec![planned(Some(write_pdf(&sheets, cmd.watermark.as_deref()).len()), Some(sheets.len()))]
         }
-        "scene" => {
-            Err(HtmlError::SyntaxError{ line, expected: "scene heading".to_string(), after: "scene declaration".to_string() })
+        Emit::Pdf if cmd.cards => {
+            cards_html(script)?;
+            vec![planned(None, None)]
+        }
+        Emit::Pdf => {
+            render_html(script, cmd)?;
+            vec![planned(None, Some(page_count(script, cmd)))]
         }
-
         _ => {
-            let whole = format!("{} {}", mode, text).trim().to_string();
-
-            if PAT_SCENE.is_match(&whole) {
-                ctx.scene += 1;
-                let count = 4 - ctx.scene.to_string().len();
-                let pad = "&nbsp".repeat(count);
-                Ok(format!("<div class=\"scene\"><h1>{}{} {}</h1></div>\n", pad, ctx.scene, whole))
-            } else if PAT_HEAD.is_match(&whole) {
-                Ok(format!("<div class=\"header\">{}</div>", whole))
-            } else if PAT_SPEECH.is_match(&whole) {
-                let (name, content) = whole.split_once(':').unwrap();
-                let mut result = String::new();
-                writeln!(result, "<div class=\"name\">{}</div>", name.to_ascii_uppercase())?;
-                for pair in PAT_EXTRACT.captures_iter(content) {
-                    for cap in pair.iter().skip(1).flatten() {
-                        if cap.as_str().starts_with('(') {
-                            writeln!(result, "<div class=\"parens\">{}</div>", cap.as_str().trim())?;
-                        } else {
-                            writeln!(result, "<div class=\"speech\">{}</div>", cap.as_str().trim())?;
+            let mut out = Vec::new();
+            if let Some(renderer) = renderer(cmd) {
+                renderer.render(script, &mut out)?;
+            }
+            vec![planned(Some(out.len()), None)]
+        }
+    };
+    if cmd.temp && cmd.emit == Emit::Pdf && cmd.engine == Engine::Webkit {
+        let bytes = if cmd.cards { cards_html(script)? } else { render_html(script, cmd)? }.len();
+        outputs.push(PlannedOutput{ path: format!("{}.html", cmd.file_root), bytes: Some(bytes), pages: None });
+    }
+    Ok(outputs)
+}
+
+
+#[derive(Debug, Default)]
+pub struct SceneCache {
+    scenes: HashMap<u64, (String, (u32, u32))>,
+    pub hits: usize,
+}
+
+
+// a scene renders the same whenever its items, the options and the running counters it starts
+// from are unchanged; element lines only matter through revision marks and dialogue numbers
+fn scene_key(script: &Script, items: &[Item], rules: &Rules, options: u64, counters: (u32, u32), numbers: &HashMap<usize, u32>) -> u64 {
+    let mut key = fnv(options, format!("{counters:?}").as_bytes());
+    for item in items {
+        let text = match item {
+            Item::Heading(scene) => format!("{}{}", script.revised.contains(&scene.line), render_heading(script, scene, rules)),
+            Item::Element{ element, contd, more } => format!("{:?}{contd}{more}{}{:?}",
+                element.kind, script.revised.contains(&element.line), numbers.get(&element.line)),
+        };
+        key = fnv(key, text.as_bytes());
+    }
+    key
+}
+
+
+// each scene is written out as soon as it is rendered, the whole body is never held at once
+fn write_body(script: &Script, cmd: &CmdInfo, mut cache: Option<&mut SceneCache>, out: &mut dyn io::Write) -> Result<(), HtmlError> {
+    let rules = get_rules(script, cmd);
+    let opts = layout_opts(cmd, &rules);
+    let mut counters = (0, 0);
+    let mut numbers: HashMap<usize, u32> = cmd.number_dialogue
+        .map(|numbering| script.dialogue_cues(numbering).into_iter().map(|cue| (cue.line, cue.number)).collect())
+        .unwrap_or_default();
+
+    // only scenes rendered in this build are kept, so the cache never outgrows the script
+    let options = CmdInfo{ infile: String::new(), merge: Vec::new(), outfile: String::new(), html: String::new(), ..cmd.clone() };
+    let options = fnv(0xcbf29ce484222325, format!("{options:?}{rules:?}").as_bytes());
+    let mut previous = cache.as_mut().map(|c| {
+        c.hits = 0;
+        std::mem::take(&mut c.scenes)
+    }).unwrap_or_default();
+
+    let total = script.scenes.len().max(1);
+    let mut rendered = 0;
+    for (i, page) in layout(script, &opts).iter().enumerate() {
+        if i > 0 {
+            out.write_all(b"<div class=\"pagebreak\"></div>\n")?;
+        }
+        // lettering numbers restart on every page, sound cues run through the script
+        counters.1 = 0;
+        for items in page.items.chunk_by(|_, item| !matches!(item, Item::Heading(_))) {
+            match cache.as_mut() {
+                Some(cache) => {
+                    let key = scene_key(script, items, &rules, options, counters, &numbers);
+                    let (html, after) = match previous.remove(&key) {
+                        Some(cached) => {
+                            cache.hits += 1;
+                            for item in items {
+                                if let Item::Element{ element, .. } = item {
+                                    if matches!(element.kind, Kind::Dialogue(_)) {
+                                        numbers.remove(&element.line);
+                                    }
+                                }
+                            }
+                            cached
                         }
-                    }
+                        None => {
+                            let mut html = String::new();
+                            render_items(&mut html, script, items, &rules, cmd, &mut counters, &mut numbers)?;
+                            (html, counters)
+                        }
+                    };
+                    out.write_all(html.as_bytes())?;
+                    counters = after;
+                    cache.scenes.insert(key, (html, after));
                 }
-                Ok(result)
-            } else {
-                Err(HtmlError::SyntaxError { line, expected: "mode declaration".to_string(), after: "new line".to_string() })
+                None => {
+                    let mut html = String::new();
+                    render_items(&mut html, script, items, &rules, cmd, &mut counters, &mut numbers)?;
+                    out.write_all(html.as_bytes())?;
+                }
+            }
+
+            if let Some(Item::Heading(scene)) = items.first() {
+                rendered += 1;
+                report(cmd, "render", 10 + 50 * rendered / total, Some(&script.scene_label(&scene.display_number())));
             }
         }
     }
+    finish_progress(cmd);
+    if let Some(cache) = cache {
+        verbose!("reused {} of {} rendered scenes", cache.hits, cache.scenes.len());
+    }
+
+    Ok(())
 }
 
 
-pub fn gen_html(cmd: &CmdInfo) -> Result<(), HtmlError> {
-    let src = fs::read_to_string(&cmd.infile)?;
+// compiled in so the output renders the same from any install location
+pub(crate) const STYLESHEET: &str = include_str!("../res/style.css");
 
-    let mut segments = Segments::new(&src);
-    let mut ctx = Context{
-        scene: 0,
-        title: segments.next_whole().ok_or(HtmlError::SyntaxError{ line: 1, expected: "title".to_string(), after: "beginning".to_string() })?.1.join(" "),
-        subtitle: segments.next_whole().ok_or(HtmlError::SyntaxError{ line: 2, expected: "subtitle".to_string(), after: "title".to_string() })?.1.join(" "),
-    };
 
-    let mut result = if cmd.range.is_some() {
-        "<html><head><link rel=\"stylesheet\" href=\"../res/style.css\"/></head><body><div class=\"page\">\n".to_string()
+const CHIP_CSS: &str = "\
+.chip { float: right; clear: right; width: 1.8in; margin-right: -2.1in; padding: 2px 6px; border-radius: 6px; font: 9pt sans-serif; }
+.chip-note { background: #fff3b0; }
+.chip-todo { background: #ffc9c9; }
+.chip-beat { background: #c9e3ff; }";
+
+
+pub fn render_html(script: &Script, cmd: &CmdInfo) -> Result<String, HtmlError> {
+    let mut out = Vec::new();
+    write_document(script, cmd, None, &mut out)?;
+    Ok(String::from_utf8(out).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?)
+}
+
+
+// for long running builds, unchanged scenes are taken from the previous render
+pub fn render_html_cached(script: &Script, cmd: &CmdInfo, cache: &mut SceneCache) -> Result<String, HtmlError> {
+    let mut out = Vec::new();
+    write_document(script, cmd, Some(cache), &mut out)?;
+    Ok(String::from_utf8(out).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?)
+}
+
+
+pub fn write_html(script: &Script, cmd: &CmdInfo, out: &mut dyn io::Write) -> Result<(), HtmlError> {
+    write_document(script, cmd, None, out)
+}
+
+
+fn write_document(script: &Script, cmd: &CmdInfo, cache: Option<&mut SceneCache>, out: &mut dyn io::Write) -> Result<(), HtmlError> {
+    let mut style = String::new();
+    if annotated(cmd) {
+        write!(style, "<style>{CHIP_CSS}</style>")?;
+    }
+    if let Some(keep) = cmd.keep_lines {
+        write!(style, "<style>.page {{ orphans: {keep}; widows: {keep}; }}</style>")?;
+    }
+    let rules = get_rules(script, cmd);
+    if !rules.css.is_empty() {
+        write!(style, "<style>{}</style>", rules.css)?;
+    }
+    if cmd.paginate {
+        write!(style, "<style>.subtitle {{ height: auto; page-break-after: always; }}</style>")?;
+    }
+
+    let mut result = if !has_title_page(cmd) {
+        format!("<html><head><meta charset=\"utf-8\"/><style>{STYLESHEET}</style>{style}</head><body><div class=\"page\">\n")
     } else {
-        format!("<html><head><link rel=\"stylesheet\" href=\"../res/style.css\"/></head><body><div class=\"page\">\n\
-                 <div class=\"title\"><h1>{}</h1></div>\n<div class=\"subtitle\"><h2>{}</h2></div>\n", ctx.title, ctx.subtitle)
+        let mut credits = String::new();
+        if let Some(author) = &script.author {
+            write!(credits, "<p>Written by<br>{author}</p>")?;
+        }
+        if let Some(draft) = &script.draft {
+            write!(credits, "<p>{draft}</p>")?;
+        }
+        format!("<html><head><meta charset=\"utf-8\"/><style>{STYLESHEET}</style>{style}</head><body><div class=\"page\">\n\
+                 <div class=\"title\"><h1>{}</h1></div>\n<div class=\"subtitle\"><h2>{}</h2>{credits}</div>\n", script.title, script.subtitle)
     };
 
-    for segment in segments {
-        let line = get_line(segment, &mut ctx)?;
-        if let Some(range) = &cmd.range {
-            if range.contains(&ctx.scene) {
-                result.push_str(&line);
-            } else if ctx.scene > range.end {
-                break
-            }
-        } else {
-            result.push_str(&line);
-        }
+    if let Some(mark) = &cmd.watermark {
+        let mark = mark.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        result = result.replacen("<body>", &format!("<body><div class=\"watermark\">{mark}</div>"), 1);
     }
-    result.push_str("</div></body></html>");
+    out.write_all(result.as_bytes())?;
+    write_body(script, cmd, cache, out)?;
+    out.write_all(b"</div></body></html>")?;
+
+    Ok(())
+}
+
+
+// one copy per recipient next to the output, 'script.pdf' becomes 'script-jane-doe.pdf'
+pub fn recipient_output(outfile: &str, name: &str) -> String {
+    let slug = name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    match outfile.rsplit_once('.') {
+        Some((root, ext)) if !ext.contains(['/', '\\']) => format!("{root}-{slug}.{ext}"),
+        _ => format!("{outfile}-{slug}"),
+    }
+}
+
+
+pub fn gen_html(cmd: &CmdInfo) -> Result<Vec<Diagnostic>, HtmlError> {
+    report(cmd, "parse", 0, None);
+    let script = read_script(cmd)?;
+    report(cmd, "render", 10, None);
+    let mut out = io::BufWriter::new(fs::File::create(&cmd.html)?);
+    if cmd.cards {
+        out.write_all(cards_html(&script)?.as_bytes())?;
+    } else {
+        write_html(&script, cmd, &mut out)?;
+    }
+    out.flush()?;
+    verbose!("rendered {} bytes of html", fs::metadata(&cmd.html)?.len());
 
     if cmd.temp {
-        fs::write(format!("{}.html", cmd.file_root), &result)?;
+        fs::copy(&cmd.html, format!("{}.html", cmd.file_root))?;
     }
 
-    Ok(fs::write(&cmd.html, result)?)
+    Ok(script.warnings)
 }
 
 
@@ -222,55 +572,30 @@ pub fn gen_html(cmd: &CmdInfo) -> Result<(), HtmlError> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::DialogueNumbering;
 
     fn process(vals: &str) -> Vec<String> {
-        let mut ctx = Context::default();
+        let script = parse(&format!("title\nsubtitle\n{vals}")).expect("parse failed");
+        let rules = HouseStyle::default().rules();
+        let mut result = Vec::new();
 
-        Segments::new(vals)
-            .map(|s| get_line(s, &mut ctx).expect("get line failed"))
-            .collect()
-    }
-
-    #[test]
-    fn comments() {
-        let mut case = Segments::new(" \
-            \n\
-            line with content\n\
-            line with comment * comment\n\
-            \n\
-            \n\
-            * another comment");
+        for element in &script.prelude {
+            result.push(render_element(element, &rules, false).expect("render element failed"));
+        }
+        for scene in &script.scenes {
+            result.push(render_heading(&script, scene, &rules));
+            for element in &scene.elements {
+                result.push(render_element(element, &rules, false).expect("render element failed"));
+            }
+        }
 
-        assert_eq!(case.next_whole(), Some((2, vec!["line with content"])));
-        assert_eq!(case.next_whole(), Some((3, vec!["line with comment"])));
-        assert_eq!(case.next_whole(), None);
+        result
     }
 
-    #[test]
-    fn line_reach() {
-        let mut case = Segments::new(" \
-            line with content \n\
-            \n\
-            line with some content \\ \n\
-            and here some more content\n\
-            \n\
-            line with content \\ \n\
-            more content \\ \n\
-            last bit of content\n\
-            \n\
-            line with some content \\ * same as 2 but with comments \n\
-            and here some more content \n\
-            \n\
-            line with some content * 2 lines because of comment \\ \n\
-            and here some more content");
-
-        assert_eq!(case.next_whole(), Some((1,  vec!["line with content"])));
-        assert_eq!(case.next_whole(), Some((3,  vec!["line with some content", "and here some more content"])));
-        assert_eq!(case.next_whole(), Some((6,  vec!["line with content", "more content", "last bit of content"])));
-        assert_eq!(case.next_whole(), Some((10, vec!["line with some content", "and here some more content"])));
-        assert_eq!(case.next_whole(), Some((13, vec!["line with some content"])));
-        assert_eq!(case.next_whole(), Some((14, vec!["and here some more content"])));
-        assert_eq!(case.next_whole(), None);
+    fn render_body(script: &Script, cmd: &CmdInfo, cache: Option<&mut SceneCache>) -> Result<String, HtmlError> {
+        let mut out = Vec::new();
+        write_body(script, cmd, cache, &mut out)?;
+        Ok(String::from_utf8(out).expect("body is not utf-8"))
     }
 
     #[test]
@@ -314,7 +639,8 @@ mod tests {
     #[test]
     fn speech() {
         let cases = process(
-            "alex: I am speaking hello there\n\
+            "cast alex\n\
+             alex: I am speaking hello there\n\
              alex: (Mood) I am speaking hello there\n\
              alex: I am speaking (Mood) hello there"
         );
@@ -324,5 +650,265 @@ mod tests {
         assert_eq!(cases[2], "<div class=\"name\">ALEX</div>\n<div class=\"speech\">I am speaking</div>\n\
                               <div class=\"parens\">(Mood)</div>\n<div class=\"speech\">hello there</div>\n".to_string());
     }
-}
 
+    #[test]
+    fn inline() {
+        let cases = process(
+            "direct She is *very* **angry** and _means_ it * but not this\n\
+             ALEX: I *really* mean it\n\
+             direct a snake_case_name stays"
+        );
+
+        assert_eq!(cases[0], "<div class=\"direct\">She is <i>very</i> <b>angry</b> and <u>means</u> it</div>\n".to_string());
+        assert_eq!(cases[1], "<div class=\"name\">ALEX</div>\n<div class=\"speech\">I <i>really</i> mean it</div>\n".to_string());
+        assert_eq!(cases[2], "<div class=\"direct\">a snake_case_name stays</div>\n".to_string());
+    }
+
+    #[test]
+    fn escapes() {
+        let cases = process("direct A 5\\* hotel, \\*not italic\\* and a \\\\ * comment");
+
+        assert_eq!(cases[0], "<div class=\"direct\">A 5* hotel, *not italic* and a \\</div>\n".to_string());
+    }
+
+    #[test]
+    fn lyrics() {
+        let cases = process(
+            "ALEX: (Singing) Here we go\n\
+             lyrics Row, row, row your boat / Gently down the stream"
+        );
+
+        assert_eq!(cases[1], "<div class=\"lyrics\">Row, row, row your boat<br>Gently down the stream</div>\n".to_string());
+    }
+
+    #[test]
+    fn contd() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             scene EXT. LOC - DAY\n\
+             ALEX: I am speaking\n\
+             direct Alex turns around.\n\
+             ALEX (V.O.): Still speaking\n\
+             SAM: Hello\n\
+             ALEX: Back to me\n\
+             scene EXT. LOC - NIGHT\n\
+             direct Alex waits.\n\
+             ALEX: New scene"
+        ).expect("parse failed");
+
+        let body = render_body(&script, &CmdInfo::default(), None).expect("render body failed");
+
+        assert!(body.contains("<div class=\"name\">ALEX (V.O.) (CONT'D)</div>"));
+        assert_eq!(body.matches("CONT'D").count(), 1);
+
+        let cmd = CmdInfo{ no_contd: true, ..Default::default() };
+        let body = render_body(&script, &cmd, None).expect("render body failed");
+
+        assert!(!body.contains("CONT'D"));
+    }
+
+    #[test]
+    fn keep_scenes() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             direct before the first scene\n\
+             scene EXT. LOC - DAY\n\
+             direct short\n\
+             scene EXT. LOC - NIGHT\n\
+             direct a much longer block of action that wraps over more than one line in the output\n\
+             ALEX: I am speaking hello there"
+        ).expect("parse failed");
+
+        let cmd = CmdInfo{ keep_scenes: Some(6), ..Default::default() };
+        let body = render_body(&script, &cmd, None).expect("render body failed");
+
+        assert_eq!(body.matches("<div class=\"keep\">").count(), 2);
+        assert!(body.starts_with("<div class=\"keep\">\n<div class=\"direct\">before"));
+        assert!(body.ends_with("<div class=\"speech\">I am speaking hello there</div>\n"));
+
+        let cmd = CmdInfo::default();
+        let body = render_body(&script, &cmd, None).expect("render body failed");
+
+        assert!(!body.contains("keep"));
+    }
+
+    #[test]
+    fn annotations() {
+        let script = parse("title\nsubtitle\nscene EXT. LOC - DAY\nTODO fix\nnote check\n= Beat one\ndirect Hi.").expect("parse failed");
+
+        let preview = render_body(&script, &CmdInfo{ emit: Emit::Html, ..Default::default() }, None).expect("render failed");
+        assert!(preview.contains("<div class=\"chip chip-todo\"><b>TODO</b> fix</div>"));
+        assert!(preview.contains("<div class=\"chip chip-note\"><b>NOTE</b> check</div>"));
+        assert!(preview.contains("<div class=\"chip chip-beat\"><b>SYNOPSIS</b> Beat one</div>"));
+
+        let pdf = render_body(&script, &CmdInfo::default(), None).expect("render failed");
+        assert!(!pdf.contains("chip"));
+        assert!(pdf.contains("TODO == FIX"));
+
+        let clean = render_body(&script, &CmdInfo{ emit: Emit::Html, no_annotations: true, ..Default::default() }, None).expect("render failed");
+        assert_eq!(clean, pdf);
+    }
+
+    #[test]
+    fn character_colors() {
+        let script = parse("title\nsubtitle\nscene EXT. LOC - DAY\nALEX: Hi.\nSAM: Hey.\nALEX: Bye.").expect("parse failed");
+
+        let preview = render_body(&script, &CmdInfo{ emit: Emit::Html, color_characters: ColorCharacters::Preview, ..Default::default() }, None).expect("render failed");
+        let alex = format!("hsl({} 70% 92%)", character_hue("ALEX"));
+        assert_eq!(preview.matches(&alex).count(), 2);
+        assert_eq!(preview.matches("class=\"tint\"").count(), 3);
+
+        let pdf = render_body(&script, &CmdInfo{ color_characters: ColorCharacters::Preview, ..Default::default() }, None).expect("render failed");
+        assert!(!pdf.contains("tint"));
+        let forced = render_body(&script, &CmdInfo{ color_characters: ColorCharacters::Always, ..Default::default() }, None).expect("render failed");
+        assert!(forced.contains(&alex));
+    }
+
+    #[test]
+    fn highlight() {
+        let script = parse("title\nsubtitle\nscene EXT. LOC - DAY\nALEX: Hi.\nSAM: Hey.\nSAM & ALEX: Bye.").expect("parse failed");
+
+        let html = render_body(&script, &CmdInfo{ highlight: Some("ALEX".to_string()), color_characters: ColorCharacters::Always, ..Default::default() }, None).expect("render failed");
+        assert_eq!(html.matches("class=\"highlight\"").count(), 2);
+        assert_eq!(html.matches("class=\"tint\"").count(), 1);
+    }
+
+    #[test]
+    fn watermark() {
+        let script = parse("title\nsubtitle\nscene EXT. LOC - DAY\nALEX: Hi.").expect("parse failed");
+
+        let html = render_html(&script, &CmdInfo{ watermark: Some("Jane <Doe>".to_string()), ..Default::default() }).expect("render failed");
+        assert!(html.contains("<body><div class=\"watermark\">Jane &lt;Doe&gt;</div><div class=\"page\">"));
+        assert_eq!(recipient_output("out/script.pdf", "Jane Doe, Jr."), "out/script-jane-doe-jr.pdf");
+        assert_eq!(recipient_output("out.d/script", "Sam"), "out.d/script-sam");
+    }
+
+    #[test]
+    fn dialogue_numbers() {
+        let script = parse("title\nsubtitle\nscene EXT. LOC - DAY\nALEX: Hi.\nSAM: Hey.\nscene INT. CAR - DAY\nALEX: Bye.").expect("parse failed");
+
+        let scene = render_body(&script, &CmdInfo{ number_dialogue: Some(DialogueNumbering::Scene), ..Default::default() }, None).expect("render failed");
+        assert_eq!(scene.matches("<div class=\"num\">1.</div>").count(), 2);
+        assert!(!scene.contains("<div class=\"num\">3.</div>"));
+
+        let global = render_body(&script, &CmdInfo{ number_dialogue: Some(DialogueNumbering::Global), ..Default::default() }, None).expect("render failed");
+        assert!(global.contains("<div class=\"num\">3.</div>"));
+        assert_eq!(script.dialogue_cues(DialogueNumbering::Global)[2].scene, "2");
+    }
+
+    #[test]
+    fn scene_numbers() {
+        let script = parse("title\nsubtitle\nscene EXT. LOC - DAY").expect("parse failed");
+        let mut rules = HouseStyle::default().rules();
+
+        rules.scene_numbers = SceneNumbers::Both;
+        assert_eq!(render_heading(&script, &script.scenes[0], &rules),
+                   "<div class=\"scene\"><h1>&nbsp;&nbsp;&nbsp;1 EXT. LOC - DAY<span class=\"scene-num-right\">1</span></h1></div>\n");
+
+        rules.scene_numbers = SceneNumbers::Hidden;
+        assert_eq!(render_heading(&script, &script.scenes[0], &rules), "<div class=\"scene\"><h1>EXT. LOC - DAY</h1></div>\n");
+    }
+
+    #[test]
+    fn comic_numbering() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             page\n\
+             panel A rooftop at night.\n\
+             caption Gotham, 3 AM.\n\
+             ALEX: Quiet tonight.\n\
+             panel\n\
+             sfx boom"
+        ).expect("parse failed");
+
+        let cmd = CmdInfo{ style: Some(HouseStyle::Comic), ..Default::default() };
+        let body = render_body(&script, &cmd, None).expect("render body failed");
+
+        assert_eq!(body, "<div class=\"header\"><h2><u>PAGE 1</u></h2></div>\n\
+                          <div class=\"header\">PANEL 1</div>\n<div class=\"direct\">A rooftop at night.</div>\n\
+                          <div class=\"num\">1.</div><div class=\"name\">CAPTION</div>\n<div class=\"speech\">Gotham, 3 AM.</div>\n\
+                          <div class=\"num\">2.</div><div class=\"name\">ALEX</div>\n<div class=\"speech\">Quiet tonight.</div>\n\
+                          <div class=\"header\">PANEL 2</div>\n\
+                          <div class=\"num\">1.</div><div class=\"cue\">SFX: BOOM</div>\n");
+    }
+
+    #[test]
+    fn audio_numbering() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             sfx door slams\n\
+             ALEX: Who's there?\n\
+             music tense strings\n\
+             SAM: Only me."
+        ).expect("parse failed");
+
+        let cmd = CmdInfo{ style: Some(HouseStyle::Audio), ..Default::default() };
+        let body = render_body(&script, &cmd, None).expect("render body failed");
+
+        assert_eq!(body, "<div class=\"num\">1.</div><div class=\"cue\">SFX: DOOR SLAMS</div>\n\
+                          <div class=\"num\">1.</div><div class=\"name\">ALEX</div>\n<div class=\"speech\">Who's there?</div>\n\
+                          <div class=\"num\">2.</div><div class=\"cue\">MUSIC: TENSE STRINGS</div>\n\
+                          <div class=\"num\">2.</div><div class=\"name\">SAM</div>\n<div class=\"speech\">Only me.</div>\n");
+    }
+
+    #[test]
+    fn pages() {
+        let scenes: String = (1..=30).map(|i| format!("scene INT. ROOM {i} - DAY\ndirect A long beat of action that fills the line.\nALEX: Line {i}.\n")).collect();
+        let script = parse(&format!("title\nsubtitle\n{scenes}")).expect("parse failed");
+
+        assert_eq!(page_count(&script, &CmdInfo::default()), 6);
+        assert_eq!(page_count(&script, &CmdInfo{ title_page: TitlePage::Never, ..Default::default() }), 5);
+    }
+
+    #[test]
+    fn title_page() {
+        let script = parse("title\nsubtitle\nscene INT. A - DAY\ndirect Hi.").expect("parse failed");
+        let titled = |cmd: &CmdInfo| render_html(&script, cmd).expect("render failed").contains("<div class=\"title\"><h1>title</h1>");
+
+        assert!(titled(&CmdInfo::default()));
+        assert!(!titled(&CmdInfo{ range: Some(1..2), ..Default::default() }));
+        assert!(titled(&CmdInfo{ range: Some(1..2), title_page: TitlePage::Always, ..Default::default() }));
+        assert!(!titled(&CmdInfo{ title_page: TitlePage::Never, ..Default::default() }));
+    }
+
+    #[test]
+    fn dry_run_outputs() {
+        let script = parse("title\nsubtitle\nscene INT. A - DAY\ndirect Hi.\nscene INT. B - DAY\ndirect Bye.\nend").expect("parse failed");
+        let mut cmd = CmdInfo{ outfile: "out/script.pdf".to_string(), file_root: "script".to_string(), temp: true, ..Default::default() };
+
+        let outputs = dry_run(&script, &cmd).unwrap();
+        assert_eq!(outputs.iter().map(|o| (o.path.as_str(), o.bytes.is_some(), o.pages)).collect::<Vec<_>>(),
+                   vec![("out/script.pdf", false, Some(2)), ("script.html", true, None)]);
+
+        cmd.split_scenes = Some("scenes".to_string());
+        let paths: Vec<String> = dry_run(&script, &cmd).unwrap().into_iter().map(|o| o.path).collect();
+        assert_eq!(paths.len(), 2);
+        assert!(paths.iter().all(|p| p.starts_with("scenes") && p.ends_with(".pdf")));
+    }
+
+    #[test]
+    fn scene_cache() {
+        let cmd = CmdInfo{ number_dialogue: Some(DialogueNumbering::Global), ..Default::default() };
+        let draft = "title\nsubtitle\nscene INT. A - DAY\ndirect Hi.\nscene INT. B - DAY\nALEX: One.\nscene INT. C - DAY\nSAM: Two.\nend";
+        let mut cache = SceneCache::default();
+
+        let script = parse(draft).expect("parse failed");
+        let first = render_html_cached(&script, &cmd, &mut cache).expect("render failed");
+        assert_eq!(cache.hits, 0);
+        assert_eq!(render_html_cached(&script, &cmd, &mut cache).expect("render failed"), first);
+        assert_eq!(cache.hits, 3);
+
+        // moving later scenes down a line keeps them, a changed cue number does not
+        let script = parse(&draft.replace("direct Hi.", "direct Hi.\ndirect There.")).expect("parse failed");
+        assert_eq!(render_html_cached(&script, &cmd, &mut cache).expect("render failed"), render_html(&script, &cmd).expect("render failed"));
+        assert_eq!(cache.hits, 2);
+        render_html_cached(&parse(draft).expect("parse failed"), &cmd, &mut cache).expect("render failed");
+        let script = parse(&draft.replace("ALEX: One.", "ALEX: One.\nSAM: Half.")).expect("parse failed");
+        assert_eq!(render_html_cached(&script, &cmd, &mut cache).expect("render failed"), render_html(&script, &cmd).expect("render failed"));
+        assert_eq!(cache.hits, 1);
+    }
+}
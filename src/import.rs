@@ -0,0 +1,1120 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+};
+use lazy_static::lazy_static;
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+use regex::{ Captures, Regex };
+use crate::{
+    parse::{ Diagnostic, PAT_SCENE, PAT_SPEECH },
+    pdf::from_win_ansi,
+    CmdInfo,
+    HtmlError,
+};
+
+
+lazy_static! {
+    static ref PAT_HEADING: Regex = Regex::new(r"(?i)^(INT|EXT|EST|INT\.?/EXT|I/E)[. ]").unwrap();
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Block {
+    Heading(String),
+    Action(String),
+    Cue(String),
+    Parens(String),
+    Speech(String),
+    Transition(String),
+    Shot(String),
+    ActBreak(String),
+    Note(String),
+    Section(usize, String),
+    Synopsis(String),
+    Lyrics(String),
+    Include(String),
+    PageBreak,
+    End,
+}
+
+
+// a script in another program's format, classified into blocks that each remember their first line
+#[derive(Debug, Default)]
+struct Foreign {
+    title: Option<String>,
+    author: Option<String>,
+    draft: Option<String>,
+    blocks: Vec<(usize, Block)>,
+    warnings: Vec<Diagnostic>,
+    // fountain's emphasis and escapes are the same as ours, other formats have none and are taken literally
+    markup: bool,
+}
+
+
+// source in this program's format, with the foreign line every source line came from
+pub(crate) struct Imported {
+    pub text: String,
+    pub lines: Vec<usize>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+
+type Importer = fn(&[u8]) -> Result<Foreign, Diagnostic>;
+
+
+fn importer(path: &Path) -> Option<Importer> {
+    match path.extension()?.to_str()?.to_lowercase().as_str() {
+        "trelby" => Some(trelby),
+        "fountain"|"spmd"|"highland" => Some(fountain),
+        "pdf" => Some(pdf),
+        _ => None,
+    }
+}
+
+
+pub fn is_imported(path: &str) -> bool {
+    importer(Path::new(path)).is_some()
+}
+
+
+// trelby writes one line per printed line: a line break char, an element char and the text;
+// elements run until a line that ends with '.'
+fn trelby(src: &[u8]) -> Result<Foreign, Diagnostic> {
+    let src = String::from_utf8_lossy(src);
+    let mut foreign = Foreign::default();
+    let mut lines: Vec<(usize, &str)> = src.lines().enumerate().map(|(i, l)| (i + 1, l)).collect();
+    if let Some(start) = lines.iter().position(|(_, l)| l.trim_end() == "#Start-Script") {
+        lines.drain(..=start);
+    }
+
+    let mut open: Option<(usize, char, String)> = None;
+    let mut glue = " ";
+    for (num, line) in lines {
+        if line.starts_with('#') || line.is_empty() {
+            continue
+        }
+        let mut chars = line.chars();
+        let (Some(lb), Some(lt)) = (chars.next(), chars.next()) else {
+            foreign.warnings.push(Diagnostic::new(num, "unrecognized trelby line, skipped"));
+            continue
+        };
+        if !['>', '+', '&', '|', '.'].contains(&lb) || !['\\', '.', '_', ':', '(', '/', '=', '@', '%'].contains(&lt) {
+            foreign.warnings.push(Diagnostic::new(num, format!("unrecognized trelby line type '{lb}{lt}', skipped")));
+            continue
+        }
+
+        let text = chars.as_str().trim();
+        match &mut open {
+            Some((_, kind, joined)) if *kind == lt => {
+                joined.push_str(glue);
+                joined.push_str(text);
+            }
+            _ => {
+                if let Some(block) = open.take() {
+                    foreign.blocks.extend(trelby_block(block));
+                }
+                open = Some((num, lt, text.to_string()));
+            }
+        }
+        // '&' joins the next line without a space, '|' is a forced break that reads as one
+        glue = if lb == '&' { "" } else { " " };
+        if lb == '.' {
+            foreign.blocks.extend(open.take().and_then(trelby_block));
+        }
+    }
+    foreign.blocks.extend(open.and_then(trelby_block));
+
+    Ok(foreign)
+}
+
+
+fn trelby_block((line, kind, text): (usize, char, String)) -> Option<(usize, Block)> {
+    let block = match kind {
+        '\\' => Block::Heading(text),
+        '_' => Block::Cue(text),
+        ':' => Block::Speech(text),
+        '(' => Block::Parens(text),
+        '/' => Block::Transition(text),
+        '=' => Block::Shot(text),
+        '@' => Block::ActBreak(text),
+        '%' => Block::Note(text),
+        _ => Block::Action(text),
+    };
+    (!text_of(&block).is_empty()).then_some((line, block))
+}
+
+
+// the title page is 'Key: value' lines up to the first blank line, values may continue on indented lines
+fn title_page(lines: &mut Vec<(usize, &str)>, foreign: &mut Foreign) {
+    const KEYS: [&str; 10] = ["title", "credit", "author", "authors", "source", "draft date", "date", "contact", "copyright", "notes"];
+    if !lines.first().and_then(|(_, l)| l.split_once(':')).is_some_and(|(k, _)| KEYS.contains(&k.trim().to_lowercase().as_str())) {
+        return
+    }
+    let end = lines.iter().position(|(_, l)| l.trim().is_empty()).unwrap_or(lines.len());
+    let mut key = String::new();
+    for (_, line) in lines.drain(..end) {
+        let value = match line.split_once(':') {
+            Some((k, v)) if !line.starts_with([' ', '\t']) => {
+                key = k.trim().to_lowercase();
+                v.trim()
+            }
+            _ => line.trim(),
+        };
+        let value = value.trim_matches(['_', '*']).trim();
+        let field = match key.as_str() {
+            "title" => &mut foreign.title,
+            "author"|"authors" => &mut foreign.author,
+            "draft date" => &mut foreign.draft,
+            _ => continue,
+        };
+        match field {
+            Some(text) if !value.is_empty() => {
+                text.push(' ');
+                text.push_str(value);
+            }
+            None if !value.is_empty() => *field = Some(value.to_string()),
+            _ => (),
+        }
+    }
+}
+
+
+type Lines = Vec<(usize, String)>;
+
+
+// boneyard comments are dropped and [[notes]] are cut out of the text, each note with the line it started on
+fn strip_comments(lines: &[(usize, &str)]) -> (Lines, Lines) {
+    let mut kept = Vec::new();
+    let mut notes: Vec<(usize, String)> = Vec::new();
+    let mut boneyard = false;
+    let mut note = false;
+
+    for &(num, line) in lines {
+        let mut text = String::new();
+        let mut rest = line;
+        while !rest.is_empty() {
+            let (close, open) = if boneyard { ("*/", None) } else if note { ("]]", None) } else { ("", Some(["/*", "[["])) };
+            if let Some(opens) = open {
+                match opens.iter().filter_map(|o| rest.find(o).map(|i| (i, *o))).min() {
+                    Some((i, o)) => {
+                        text.push_str(&rest[..i]);
+                        rest = &rest[i + 2..];
+                        if o == "/*" {
+                            boneyard = true;
+                        } else {
+                            note = true;
+                            notes.push((num, String::new()));
+                        }
+                    }
+                    None => {
+                        text.push_str(rest);
+                        rest = "";
+                    }
+                }
+                continue
+            }
+            let end = rest.find(close);
+            let inside = &rest[..end.unwrap_or(rest.len())];
+            if note {
+                let text = &mut notes.last_mut().unwrap().1;
+                if !text.is_empty() && !inside.trim().is_empty() {
+                    text.push(' ');
+                }
+                text.push_str(inside.trim());
+            }
+            match end {
+                Some(i) => {
+                    rest = &rest[i + 2..];
+                    boneyard = false;
+                    note = false;
+                }
+                None => rest = "",
+            }
+        }
+        // a line left empty by a comment still separates paragraphs, a blank '  ' line in dialogue does not
+        kept.push((num, if line == "  " { line.to_string() } else { text.split_whitespace().collect::<Vec<_>>().join(" ") }));
+    }
+
+    (kept, notes)
+}
+
+
+fn is_cue(text: &str) -> bool {
+    let name = text.trim_start_matches('@').split('(').next().unwrap_or_default().trim_end_matches('^').trim();
+    text.starts_with('@') || (name.chars().any(char::is_alphabetic) && !name.chars().any(char::is_lowercase))
+}
+
+
+fn fountain_block(paragraph: &[(usize, String)], warnings: &mut Vec<Diagnostic>) -> Vec<(usize, Block)> {
+    lazy_static! {
+        static ref PAT_DIRECTIVE: Regex = Regex::new(r"^\{\{\s*(.*?)\s*\}\}$").unwrap();
+    }
+    let (line, first) = (paragraph[0].0, paragraph[0].1.trim());
+    let single = paragraph.len() == 1;
+    let joined = || paragraph.iter().map(|(_, l)| l.trim()).filter(|l| !l.is_empty()).collect::<Vec<_>>().join(" ");
+
+    let block = if let Some(caps) = PAT_DIRECTIVE.captures(first).filter(|_| single) {
+        match caps[1].split_once(':') {
+            Some((directive, path)) if directive.trim().eq_ignore_ascii_case("include") => Block::Include(path.trim().to_string()),
+            _ => {
+                warnings.push(Diagnostic::new(line, format!("unsupported directive '{first}' skipped")));
+                return Vec::new()
+            }
+        }
+    } else if single && first.len() >= 3 && first.bytes().all(|b| b == b'=') {
+        Block::PageBreak
+    } else if first.starts_with('#') {
+        let depth = first.len() - first.trim_start_matches('#').len();
+        Block::Section(depth, joined()[depth..].trim().to_string())
+    } else if let Some(text) = first.strip_prefix('=').filter(|_| single) {
+        Block::Synopsis(text.trim().to_string())
+    } else if first.starts_with('~') {
+        Block::Lyrics(paragraph.iter().map(|(_, l)| l.trim().trim_start_matches('~').trim()).collect::<Vec<_>>().join(" / "))
+    } else if let Some(heading) = first.strip_prefix('.').filter(|h| single && !h.starts_with('.')) {
+        Block::Heading(heading.trim().to_string())
+    } else if single && PAT_HEADING.is_match(first) {
+        Block::Heading(first.to_string())
+    } else if let Some(text) = first.strip_prefix('>').and_then(|t| t.strip_suffix('<')) {
+        if text.trim().eq_ignore_ascii_case("THE END") {
+            Block::End
+        } else {
+            warnings.push(Diagnostic::new(line, "centered text imported as action"));
+            Block::Action(text.trim().to_string())
+        }
+    } else if let Some(text) = first.strip_prefix('>').filter(|_| single) {
+        Block::Transition(text.trim().to_string())
+    } else if single && first.ends_with("TO:") && !first.chars().any(char::is_lowercase) {
+        Block::Transition(first.to_string())
+    } else if !single && is_cue(first) {
+        let mut cue = first.trim_start_matches('@').to_string();
+        if cue.ends_with('^') {
+            warnings.push(Diagnostic::new(line, "dual dialogue imported as consecutive speeches"));
+            cue = cue.trim_end_matches('^').trim().to_string();
+        }
+        let mut blocks = vec![(line, Block::Cue(cue))];
+        for (num, text) in &paragraph[1..] {
+            let text = text.trim();
+            if text.starts_with('(') {
+                blocks.push((*num, Block::Parens(text.to_string())));
+            } else if !text.is_empty() {
+                blocks.push((*num, Block::Speech(text.to_string())));
+            }
+        }
+        return blocks
+    } else {
+        Block::Action(joined().trim_start_matches('!').to_string())
+    };
+
+    vec![(line, block)]
+}
+
+
+// fountain as written by Highland, with [[notes]], /* boneyard */ and {{include: file}}
+fn fountain(src: &[u8]) -> Result<Foreign, Diagnostic> {
+    if src.starts_with(b"PK") {
+        return Err(Diagnostic::new(1, "Highland 2 documents are zip packages, export the script to '.fountain' first"))
+    }
+    let src = String::from_utf8_lossy(src);
+    let mut foreign = Foreign{ markup: true, ..Default::default() };
+    let mut lines: Vec<(usize, &str)> = src.lines().enumerate().map(|(i, l)| (i + 1, l)).collect();
+    title_page(&mut lines, &mut foreign);
+
+    let (lines, mut notes) = strip_comments(&lines);
+    notes.reverse();
+    for paragraph in lines.split(|(_, l)| l.is_empty()).filter(|p| !p.is_empty()) {
+        let blocks = fountain_block(paragraph, &mut foreign.warnings);
+        foreign.blocks.extend(blocks);
+        let last = paragraph[paragraph.len() - 1].0;
+        while let Some((num, text)) = notes.pop_if(|(num, _)| *num <= last) {
+            if !text.is_empty() {
+                foreign.blocks.push((num, Block::Note(text)));
+            }
+        }
+    }
+    foreign.blocks.extend(notes.into_iter().rev().filter(|(_, text)| !text.is_empty()).map(|(num, text)| (num, Block::Note(text))));
+
+    Ok(foreign)
+}
+
+
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+// far beyond the text of any script, a stream inflating past this is dropped
+const MAX_STREAM: usize = 16 << 20;
+
+
+fn concat(m: Matrix, n: Matrix) -> Matrix {
+    [
+        m[0] * n[0] + m[1] * n[2], m[0] * n[1] + m[1] * n[3],
+        m[2] * n[0] + m[3] * n[2], m[2] * n[1] + m[3] * n[3],
+        m[4] * n[0] + m[5] * n[2] + n[4], m[4] * n[1] + m[5] * n[3] + n[5],
+    ]
+}
+
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+
+// the content of every stream that can hold page text, in file order;
+// fonts, images and other typed streams are skipped, as are filters other than flate
+fn pdf_streams(src: &[u8]) -> Vec<Vec<u8>> {
+    lazy_static! {
+        static ref PAT_LENGTH: Regex = Regex::new(r"/Length\s+(\d+)(\s+\d+\s+R)?").unwrap();
+        static ref PAT_FILTER: Regex = Regex::new(r"/(\w+)Decode\b").unwrap();
+    }
+    let mut streams = Vec::new();
+    let mut at = 0;
+
+    while let Some(found) = find(&src[at..], b"stream") {
+        let keyword = at + found;
+        at = keyword + 6;
+        let data = match &src[at..] {
+            _ if src[..keyword].ends_with(b"end") => continue,
+            [b'\r', b'\n', ..] => at + 2,
+            [b'\n', ..]|[b'\r', ..] => at + 1,
+            _ => continue,
+        };
+        let from = keyword.saturating_sub(4096);
+        let dict = String::from_utf8_lossy(&src[from + src[from..keyword].windows(3).rposition(|w| w == b"obj").map_or(0, |i| i + 3)..keyword]);
+
+        let length = PAT_LENGTH.captures(&dict)
+            .filter(|caps| caps.get(2).is_none())
+            .and_then(|caps| caps[1].parse::<usize>().ok())
+            .and_then(|n| data.checked_add(n))
+            .filter(|end| src.get(*end..).is_some_and(|rest| rest.trim_ascii_start().starts_with(b"endstream")));
+        let Some(end) = length.or_else(|| find(&src[data..], b"endstream").map(|i| data + i)) else {
+            break
+        };
+        at = end;
+        if ["/Type", "/Subtype", "/Length1", "/Length2"].iter().any(|key| dict.contains(key)) {
+            continue
+        }
+
+        let body = src[data..end].trim_ascii_end();
+        let filters: Vec<&str> = PAT_FILTER.captures_iter(&dict).map(|caps| caps.get(1).unwrap().as_str()).collect();
+        match filters.as_slice() {
+            [] => streams.push(body.to_vec()),
+            ["Flate"] => streams.extend(decompress_to_vec_zlib_with_limit(body, MAX_STREAM).ok()),
+            _ => (),
+        }
+    }
+    streams
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Number(f32),
+    Text(Vec<u8>),
+    Array(Vec<Operand>),
+    Other,
+}
+
+
+// text placed on the page, in points from the bottom left corner
+#[derive(Debug, Clone, PartialEq)]
+struct Run {
+    x: f32,
+    y: f32,
+    width: f32,
+    size: f32,
+    text: String,
+}
+
+
+#[derive(Debug, Clone)]
+struct TextState {
+    ctm: Matrix,
+    tm: Matrix,
+    tlm: Matrix,
+    size: f32,
+    leading: f32,
+    scale: f32,
+    spacing: f32,
+    words: f32,
+}
+
+impl TextState {
+    fn next_line(&mut self, tx: f32, ty: f32) {
+        self.tlm = concat([1.0, 0.0, 0.0, 1.0, tx, ty], self.tlm);
+        self.tm = self.tlm;
+    }
+
+    // screenplays are set in courier, so every character is measured 0.6 em wide
+    fn show(&mut self, bytes: &[u8], runs: &mut Vec<Run>) {
+        let advances: Vec<f32> = bytes.iter()
+            .map(|&b| (0.6 * self.size + self.spacing + if b == b' ' { self.words } else { 0.0 }) * self.scale)
+            .collect();
+        let m = concat(self.tm, self.ctm);
+        let skip = bytes.iter().take_while(|&&b| from_win_ansi(b) == ' ').count();
+
+        // rotated text is a watermark or a margin note, never part of the script
+        if skip < bytes.len() && m[0] > 0.0 && m[1].abs() < 0.01 * m[0] {
+            let offset: f32 = advances[..skip].iter().sum();
+            runs.push(Run{
+                x: m[4] + offset * m[0],
+                y: m[5],
+                width: advances[skip..].iter().sum::<f32>() * m[0],
+                size: self.size * m[3].abs(),
+                text: bytes[skip..].iter().map(|&b| from_win_ansi(b)).collect(),
+            });
+        }
+        self.tm = concat([1.0, 0.0, 0.0, 1.0, advances.iter().sum(), 0.0], self.tm);
+    }
+}
+
+
+fn literal_string(content: &[u8], mut i: usize) -> (Vec<u8>, usize) {
+    let mut text = Vec::new();
+    let mut depth = 1;
+    while let Some(&b) = content.get(i) {
+        i += 1;
+        match b {
+            b'\\' => {
+                let Some(&escaped) = content.get(i) else { break };
+                i += 1;
+                match escaped {
+                    b'n' => text.push(b'\n'),
+                    b'r' => text.push(b'\r'),
+                    b't' => text.push(b'\t'),
+                    b'b' => text.push(8),
+                    b'f' => text.push(12),
+                    b'0'..=b'7' => {
+                        let mut code = (escaped - b'0') as u32;
+                        while let Some(&digit @ b'0'..=b'7') = content.get(i).filter(|_| code < 0o40) {
+                            code = code * 8 + (digit - b'0') as u32;
+                            i += 1;
+                        }
+                        text.push(code as u8);
+                    }
+                    b'\r' if content.get(i) == Some(&b'\n') => i += 1,
+                    b'\r'|b'\n' => (),
+                    other => text.push(other),
+                }
+            }
+            b'(' => {
+                depth += 1;
+                text.push(b);
+            }
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    break
+                }
+                text.push(b);
+            }
+            _ => text.push(b),
+        }
+    }
+    (text, i)
+}
+
+
+fn hex_string(content: &[u8], i: usize) -> (Vec<u8>, usize) {
+    let end = content[i..].iter().position(|&b| b == b'>').map_or(content.len(), |e| i + e);
+    let mut digits: Vec<u8> = content[i..end].iter().filter_map(|&b| (b as char).to_digit(16).map(|d| d as u8)).collect();
+    if digits.len() % 2 == 1 {
+        digits.push(0);
+    }
+    (digits.chunks(2).map(|pair| pair[0] * 16 + pair[1]).collect(), end + 1)
+}
+
+
+// a small interpreter for the text operators of a content stream, everything else is skipped
+fn pdf_runs(content: &[u8]) -> Vec<Run> {
+    let is_delimiter = |b: u8| b.is_ascii_whitespace() || b"()<>[]{}/%".contains(&b);
+    let mut runs = Vec::new();
+    let mut state = TextState{ ctm: IDENTITY, tm: IDENTITY, tlm: IDENTITY, size: 12.0, leading: 0.0, scale: 1.0, spacing: 0.0, words: 0.0 };
+    let mut saved: Vec<Matrix> = Vec::new();
+    let mut operands: Vec<Operand> = Vec::new();
+    let mut arrays: Vec<Vec<Operand>> = Vec::new();
+    let mut i = 0;
+
+    while let Some(&b) = content.get(i) {
+        let operand = match b {
+            _ if b.is_ascii_whitespace() => {
+                i += 1;
+                continue
+            }
+            b'%' => {
+                i += content[i..].iter().position(|&b| b == b'\n' || b == b'\r').unwrap_or(content.len() - i);
+                continue
+            }
+            b'(' => {
+                let (text, next) = literal_string(content, i + 1);
+                i = next;
+                Operand::Text(text)
+            }
+            // dictionaries only carry marked content properties
+            b'<' if content.get(i + 1) == Some(&b'<') => {
+                i += 2;
+                continue
+            }
+            b'<' => {
+                let (text, next) = hex_string(content, i + 1);
+                i = next;
+                Operand::Text(text)
+            }
+            b'[' => {
+                i += 1;
+                arrays.push(std::mem::take(&mut operands));
+                continue
+            }
+            b']' => {
+                i += 1;
+                Operand::Array(std::mem::replace(&mut operands, arrays.pop().unwrap_or_default()))
+            }
+            b'/' => {
+                i += 1 + content[i + 1..].iter().position(|&b| is_delimiter(b)).unwrap_or(content.len() - i - 1);
+                Operand::Other
+            }
+            _ if is_delimiter(b) => {
+                i += 1;
+                continue
+            }
+            _ => {
+                let start = i;
+                i += content[i..].iter().position(|&b| is_delimiter(b)).unwrap_or(content.len() - i);
+                let word = String::from_utf8_lossy(&content[start..i]);
+                if let Ok(number) = word.parse::<f32>() {
+                    Operand::Number(number)
+                } else if ["true", "false", "null"].contains(&word.as_ref()) {
+                    Operand::Other
+                } else {
+                    let numbers: Vec<f32> = operands.iter().filter_map(|o| match o { Operand::Number(n) => Some(*n), _ => None }).collect();
+                    let n = |k: usize| numbers.len().checked_sub(k).map_or(0.0, |at| numbers[at]);
+                    let matrix = || [n(6), n(5), n(4), n(3), n(2), n(1)];
+                    let text = operands.iter().rev().find_map(|o| match o { Operand::Text(t) => Some(t.as_slice()), _ => None }).unwrap_or_default();
+
+                    match word.as_ref() {
+                        "q" => saved.push(state.ctm),
+                        "Q" => state.ctm = saved.pop().unwrap_or(IDENTITY),
+                        "cm" => state.ctm = concat(matrix(), state.ctm),
+                        "BT" => (state.tm, state.tlm) = (IDENTITY, IDENTITY),
+                        "Tf" => state.size = n(1),
+                        "TL" => state.leading = n(1),
+                        "Tc" => state.spacing = n(1),
+                        "Tw" => state.words = n(1),
+                        "Tz" => state.scale = n(1) / 100.0,
+                        "Td" => state.next_line(n(2), n(1)),
+                        "TD" => {
+                            state.leading = -n(1);
+                            state.next_line(n(2), n(1));
+                        }
+                        "Tm" => (state.tm, state.tlm) = (matrix(), matrix()),
+                        "T*" => state.next_line(0.0, -state.leading),
+                        "Tj" => state.show(text, &mut runs),
+                        "'" => {
+                            state.next_line(0.0, -state.leading);
+                            state.show(text, &mut runs);
+                        }
+                        "\"" => {
+                            (state.words, state.spacing) = (n(3), n(2));
+                            state.next_line(0.0, -state.leading);
+                            state.show(text, &mut runs);
+                        }
+                        "TJ" => for item in operands.iter().rev().find_map(|o| match o { Operand::Array(items) => Some(items), _ => None }).into_iter().flatten() {
+                            match item {
+                                Operand::Text(text) => state.show(text, &mut runs),
+                                Operand::Number(shift) => state.tm = concat([1.0, 0.0, 0.0, 1.0, -shift / 1000.0 * state.size * state.scale, 0.0], state.tm),
+                                _ => (),
+                            }
+                        },
+                        // inline image data is binary, it runs up to the 'EI' operator
+                        "BI" => i += find(&content[i..], b"EI").map_or(content.len() - i, |e| e + 2),
+                        _ => (),
+                    }
+                    operands.clear();
+                    continue
+                }
+            }
+        };
+        operands.push(operand);
+    }
+    runs
+}
+
+
+// runs on one baseline make a printed line, left to right
+fn baselines(runs: &[Run]) -> Vec<Vec<Run>> {
+    let mut runs = runs.to_vec();
+    runs.sort_by(|a, b| b.y.total_cmp(&a.y).then(a.x.total_cmp(&b.x)));
+
+    let mut lines: Vec<Vec<Run>> = Vec::new();
+    for run in runs {
+        match lines.last_mut() {
+            Some(line) if (line[0].y - run.y).abs() <= run.size * 0.3 => line.push(run),
+            _ => lines.push(vec![run]),
+        }
+    }
+    for line in &mut lines {
+        line.sort_by(|a, b| a.x.total_cmp(&b.x));
+    }
+    lines
+}
+
+
+fn join(parts: &[&Run]) -> Option<Run> {
+    let mut line = (*parts.first()?).clone();
+    for part in &parts[1..] {
+        if part.x > line.x + line.width + part.size * 0.18 {
+            line.text.push(' ');
+        }
+        line.text.push_str(&part.text);
+        line.width = part.x + part.width - line.x;
+    }
+    line.text = line.text.split_whitespace().collect::<Vec<_>>().join(" ");
+    Some(line)
+}
+
+
+// scene numbers and revision marks are printed in the margins, outside the text
+fn is_margin_mark(text: &str) -> bool {
+    lazy_static! {
+        static ref PAT_MARK: Regex = Regex::new(r"^(\d+[A-Z]{0,2}\.?|\*)$").unwrap();
+    }
+    PAT_MARK.is_match(text.trim())
+}
+
+
+// the action margin is the leftmost indent that many lines share, lines in a short document may all differ
+fn left_margin(lines: &[(usize, Vec<Run>)]) -> f32 {
+    let mut starts: BTreeMap<i32, usize> = BTreeMap::new();
+    for start in lines.iter().filter_map(|(_, line)| line.iter().find(|r| !is_margin_mark(&r.text))) {
+        *starts.entry(start.x.round() as i32).or_default() += 1;
+    }
+    let enough = (lines.len() / 20).max(2);
+    starts.iter()
+        .find(|(_, count)| **count >= enough)
+        .or_else(|| starts.iter().next())
+        .map_or(108.0, |(x, _)| *x as f32)
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Indent {
+    Left,
+    Dialogue,
+    Parens,
+    Cue,
+    Right,
+}
+
+
+// indents in courier columns from the action margin, between the usual 1.0, 1.5, 2.2 and 4.0 inch steps
+fn indent(columns: f32) -> Indent {
+    match columns {
+        c if c < 5.0 => Indent::Left,
+        c if c < 13.0 => Indent::Dialogue,
+        c if c < 19.0 => Indent::Parens,
+        c if c < 32.0 => Indent::Cue,
+        _ => Indent::Right,
+    }
+}
+
+
+// a first page without scene headings, followed by pages with them, is a title page
+fn pdf_title_page(lines: &[&(usize, usize, Run)], foreign: &mut Foreign) {
+    let mut texts = lines.iter().map(|(_, _, line)| line.text.trim_matches(['_', '"']).trim());
+    foreign.title = texts.next().map(str::to_string);
+    while let Some(text) = texts.next() {
+        let lower = text.to_lowercase();
+        if lower.ends_with("by") {
+            foreign.author = texts.next().map(str::to_string);
+        } else if let Some(author) = lower.strip_prefix("by ").or_else(|| lower.strip_prefix("written by ")) {
+            foreign.author = Some(text[text.len() - author.len()..].to_string());
+        }
+    }
+}
+
+
+// experimental: a printed screenplay is read back from the position of its text alone, so styles,
+// dual dialogue and anything not set in the usual courier layout come back as plain action
+fn pdf(src: &[u8]) -> Result<Foreign, Diagnostic> {
+    lazy_static! {
+        static ref PAT_MEDIA: Regex = Regex::new(r"/MediaBox\s*\[\s*(-?[\d.]+)\s+(-?[\d.]+)\s+(-?[\d.]+)\s+(-?[\d.]+)\s*\]").unwrap();
+        static ref PAT_CONTINUED: Regex = Regex::new(r"^\(?CONTINUED(: \(\d+\))?:?\)?$").unwrap();
+    }
+    if find(&src[..src.len().min(1024)], b"%PDF-").is_none() {
+        return Err(Diagnostic::new(1, "not a pdf file"))
+    }
+    if find(src, b"/Encrypt").is_some() {
+        return Err(Diagnostic::new(1, "encrypted pdfs cannot be read, remove the password first"))
+    }
+
+    let pages: Vec<Vec<Run>> = pdf_streams(src).iter().map(|s| pdf_runs(s)).filter(|runs| !runs.is_empty()).collect();
+    let chars = pages.iter().flatten().flat_map(|r| r.text.chars());
+    let (total, unreadable) = chars.fold((0, 0), |(t, u), c| (t + 1, u + (c == '\u{FFFD}') as usize));
+    if unreadable * 10 > total {
+        return Err(Diagnostic::new(1, "the text in this pdf uses fonts without a standard encoding and cannot be read"))
+    }
+
+    // page numbers, headers and footers sit in the top and bottom margins
+    let media = String::from_utf8_lossy(src);
+    let (bottom, top) = PAT_MEDIA.captures(&media).map_or((0.0, 792.0), |caps| (caps[2].parse().unwrap_or(0.0), caps[4].parse().unwrap_or(792.0)));
+    let printed: Vec<(usize, Vec<Run>)> = pages.iter()
+        .enumerate()
+        .flat_map(|(page, runs)| {
+            let body: Vec<Run> = runs.iter().filter(|r| r.y > bottom + 54.0 && r.y < top - 54.0).cloned().collect();
+            baselines(&body).into_iter().map(move |line| (page, line))
+        })
+        .collect();
+    let left = left_margin(&printed);
+    let in_text = |r: &&Run| (left - r.size * 0.18..left + 61.0 * 0.6 * r.size).contains(&r.x);
+    let lines: Vec<(usize, usize, Run)> = printed.iter()
+        .filter_map(|(page, line)| join(&line.iter().filter(in_text).collect::<Vec<_>>()).map(|line| (*page, line)))
+        .enumerate()
+        .map(|(i, (page, line))| (i + 1, page, line))
+        .collect();
+    if lines.is_empty() {
+        return Err(Diagnostic::new(1, "no text found in the pdf, scanned pages need to be run through OCR first"))
+    }
+
+    let column = |run: &Run| (run.x - left) / (0.6 * run.size);
+    let is_heading = |(_, _, line): &&(usize, usize, Run)| indent(column(line)) == Indent::Left && PAT_HEADING.is_match(&line.text);
+    let mut foreign = Foreign::default();
+    let first_page = lines.iter().take_while(|(_, page, _)| *page == lines[0].1).collect::<Vec<_>>();
+    let skip = if !first_page.iter().any(is_heading) && lines.iter().any(|l| is_heading(&l)) {
+        pdf_title_page(&first_page, &mut foreign);
+        first_page.len()
+    } else {
+        0
+    };
+
+    let mut last: Option<(usize, f32)> = None;
+    let mut more = false;
+    for (num, page, line) in &lines[skip..] {
+        let (num, text) = (*num, line.text.as_str());
+        if text == "(MORE)" {
+            more = true;
+            continue
+        }
+        if PAT_CONTINUED.is_match(text) {
+            continue
+        }
+        let place = indent(column(line));
+        // a speech broken over a page carries on after the cue that repeats the name
+        if std::mem::take(&mut more) && place == Indent::Cue && text.contains("CONT'D") {
+            last = Some((*page, line.y));
+            continue
+        }
+        let touching = last.is_some_and(|(p, y)| p == *page && y - line.y < 1.6 * line.size);
+        last = Some((*page, line.y));
+        let in_dialogue = touching && matches!(foreign.blocks.last(), Some((_, Block::Cue(_)|Block::Parens(_)|Block::Speech(_))));
+
+        match (foreign.blocks.last_mut(), place) {
+            (Some((_, Block::Action(joined))), Indent::Left)
+                |(Some((_, Block::Speech(joined))), Indent::Dialogue) if touching && !text.starts_with('(') => {
+                joined.push(' ');
+                joined.push_str(text);
+            }
+            (Some((_, Block::Parens(joined))), Indent::Parens|Indent::Dialogue) if touching && !joined.ends_with(')') => {
+                joined.push(' ');
+                joined.push_str(text);
+            }
+            _ => foreign.blocks.push((num, match place {
+                Indent::Left if PAT_HEADING.is_match(text) => Block::Heading(text.to_string()),
+                Indent::Left => Block::Action(text.to_string()),
+                Indent::Dialogue|Indent::Parens if text.starts_with('(') && (in_dialogue || place == Indent::Parens) => Block::Parens(text.to_string()),
+                Indent::Dialogue|Indent::Parens => Block::Speech(text.to_string()),
+                Indent::Cue => Block::Cue(text.to_string()),
+                Indent::Right if !text.chars().any(char::is_lowercase) => Block::Transition(text.to_string()),
+                Indent::Right => Block::Action(text.to_string()),
+            })),
+        }
+    }
+
+    // a cue with nothing spoken under it is centered text
+    let mut blocks = Vec::new();
+    let mut rest = foreign.blocks.into_iter().peekable();
+    while let Some((line, block)) = rest.next() {
+        let speaks = rest.peek().is_some_and(|(_, next)| matches!(next, Block::Parens(_)|Block::Speech(_)));
+        let block = match block {
+            Block::Cue(text) if !speaks && text.trim_matches('_').eq_ignore_ascii_case("THE END") => {
+                // the closing transition is printed again with 'end'
+                blocks.pop_if(|(_, b): &mut (usize, Block)| matches!(b, Block::Transition(t) if t == "FADE OUT."));
+                Block::End
+            }
+            Block::Cue(text) if !speaks && text.starts_with("ACT ") => Block::ActBreak(text),
+            Block::Cue(text) if !speaks => {
+                foreign.warnings.push(Diagnostic::new(line, "centered text imported as action"));
+                Block::Action(text)
+            }
+            block => block,
+        };
+        blocks.push((line, block));
+    }
+    foreign.blocks = blocks;
+
+    Ok(foreign)
+}
+
+
+fn text_of(block: &Block) -> &str {
+    match block {
+        Block::Heading(text)|Block::Action(text)|Block::Cue(text)|Block::Parens(text)|Block::Speech(text)
+            |Block::Transition(text)|Block::Shot(text)|Block::ActBreak(text)|Block::Note(text)
+            |Block::Section(_, text)|Block::Synopsis(text)|Block::Lyrics(text)|Block::Include(text) => text,
+        Block::PageBreak|Block::End => "",
+    }
+}
+
+
+// text without markup is taken literally, so markup and comment characters are escaped
+fn literal(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('*', "\\*").replace('_', "\\_")
+}
+
+
+// markup is kept, but a lone '*' would start a comment and a trailing '\' would continue the line
+fn with_markup(text: &str) -> String {
+    lazy_static! {
+        static ref PAT_STARS: Regex = Regex::new(r"(^|\s)(\*+)(\s|$)").unwrap();
+    }
+    let mut text = PAT_STARS.replace_all(text, |caps: &Captures| format!("{}{}{}", &caps[1], caps[2].replace('*', "\\*"), &caps[3])).into_owned();
+    if (text.len() - text.trim_end_matches('\\').len()) % 2 == 1 {
+        text.push('\\');
+    }
+    text
+}
+
+
+fn unwrap_parens(text: &str) -> &str {
+    text.trim().trim_start_matches('(').trim_end_matches(')').trim()
+}
+
+
+// (CONT'D) is added back when rendering and off screen speech is always (O.S.) or (V.O.),
+// anything else in the cue has no place in this format
+fn cue_name(line: usize, cue: &str, warnings: &mut Vec<Diagnostic>) -> String {
+    let mut name = cue.to_uppercase().replace("(CONT'D)", "").replace("(CONT’D)", "").replace("(O.C.)", "(O.S.)");
+    while let Some(start) = name.rfind(" (").filter(|_| name.ends_with(')')) {
+        let extension = name[start + 1..].to_string();
+        if extension == "(O.S.)" || extension == "(V.O.)" {
+            break
+        }
+        warnings.push(Diagnostic::new(line, format!("cue extension '{extension}' of {} dropped", name[..start].trim())));
+        name.truncate(start);
+    }
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+
+// merged and included files are appended to the main script, so only the main file gets front matter
+fn write_source(foreign: Foreign, fallback: &str, header: bool) -> Imported {
+    let Foreign{ title, author, draft, blocks, mut warnings, markup } = foreign;
+    let prose = |text: &str| if markup { with_markup(text) } else { literal(text) };
+    let mut out = Imported{ text: String::new(), lines: Vec::new(), warnings: Vec::new() };
+    let push = |out: &mut Imported, line: usize, text: String| {
+        out.text.push_str(&text);
+        out.text.push('\n');
+        out.lines.push(line);
+    };
+
+    if header {
+        push(&mut out, 1, "---".to_string());
+        push(&mut out, 1, format!("title: {}", serde_json::json!(title.as_deref().unwrap_or(fallback))));
+        if let Some(author) = author {
+            push(&mut out, 1, format!("author: {}", serde_json::json!(author)));
+        }
+        if let Some(draft) = draft {
+            push(&mut out, 1, format!("draft: {}", serde_json::json!(draft)));
+        }
+        push(&mut out, 1, "---".to_string());
+    }
+
+    let mut blocks = blocks.into_iter().peekable();
+    while let Some((line, block)) = blocks.next() {
+        let text = match block {
+            Block::Heading(heading) if PAT_SCENE.is_match(&heading.to_uppercase()) => format!("scene {}", heading.to_uppercase()),
+            Block::Heading(heading) => {
+                warnings.push(Diagnostic::new(line, format!("scene heading '{heading}' needs INT./EXT. and a time of day, imported as a subheading")));
+                format!("subhead {}", heading.to_uppercase())
+            }
+            Block::Action(text) => format!("direct {}", prose(&text)),
+            Block::Cue(cue) => {
+                let name = cue_name(line, &cue, &mut warnings);
+                let mut content = Vec::new();
+                while let Some((_, part)) = blocks.next_if(|(_, b)| matches!(b, Block::Parens(_)|Block::Speech(_))) {
+                    content.push(match part {
+                        Block::Parens(text) => format!("({})", prose(unwrap_parens(&text))),
+                        part => prose(text_of(&part)),
+                    });
+                }
+                let speech = format!("{name}: {}", content.join(" "));
+                if content.is_empty() || !PAT_SPEECH.is_match(&speech) {
+                    warnings.push(Diagnostic::new(line, format!("character cue '{cue}' could not be read as dialogue, imported as action")));
+                    format!("direct {} {}", prose(&name), content.join(" ")).trim_end().to_string()
+                } else {
+                    speech
+                }
+            }
+            Block::Parens(text) => format!("parens {}", prose(unwrap_parens(&text))),
+            Block::Speech(text) => format!("speech {}", prose(&text)),
+            Block::Transition(text) => format!("trans {}", text.to_uppercase()),
+            Block::Shot(text) => format!("subhead {}", text.to_uppercase()),
+            Block::ActBreak(text) => match text.to_uppercase().strip_prefix("ACT ") {
+                Some(act) => format!("act {act}"),
+                None => format!("subhead {}", text.to_uppercase()),
+            },
+            Block::Note(text) => format!("note {}", prose(&text)),
+            Block::Section(depth, text) => format!("{} {}", "#".repeat(depth), prose(&text)),
+            Block::Synopsis(text) => format!("= {}", prose(&text)),
+            Block::Lyrics(text) => format!("lyrics {}", prose(&text)),
+            Block::Include(path) => format!("include {path}"),
+            Block::PageBreak => "pagebreak".to_string(),
+            Block::End => "end".to_string(),
+        };
+        push(&mut out, line, text);
+    }
+
+    out.warnings = warnings;
+    out
+}
+
+
+pub(crate) fn import(path: &Path, src: &[u8], header: bool) -> Option<Result<Imported, Diagnostic>> {
+    let fallback = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    importer(path).map(|import| import(src).map(|foreign| write_source(foreign, &fallback, header)))
+}
+
+
+pub fn gen_import(cmd: &CmdInfo) -> Result<Vec<Diagnostic>, HtmlError> {
+    let path = Path::new(&cmd.infile);
+    let imported = import(path, &fs::read(path)?, true)
+        .ok_or(HtmlError::Unsupported("unknown import format (expected '.trelby', '.fountain' or '.pdf')"))?
+        .map_err(|d| HtmlError::ParseError(vec![d]))?;
+    fs::write(&cmd.outfile, imported.text)?;
+    Ok(imported.warnings)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use miniz_oxide::deflate::compress_to_vec_zlib;
+    use crate::{ parse, typeset::typeset, write_pdf };
+
+    #[test]
+    fn trelby_script() {
+        let src = "#Version 3\n#Begin-Config \n#End-Config \n#Start-Script \n\
+                   .\\INT. MOTEL ROOM - NIGHT\n\
+                   >.A neon sign *buzzes* outside\n\
+                   ..the_window.\n\
+                   ._ALEX (CONT'D)\n\
+                   .(quietly)\n\
+                   .:Not again.\n\
+                   ._MAYA (ON PHONE)\n\
+                   .:It's me.\n\
+                   .\\KITCHEN\n\
+                   ./FADE OUT.\n";
+        let imported = import(Path::new("Motel.trelby"), src.as_bytes(), true).expect("not imported").expect("import failed");
+
+        assert_eq!(imported.text, "---\ntitle: \"Motel\"\n---\n\
+                                   scene INT. MOTEL ROOM - NIGHT\n\
+                                   direct A neon sign \\*buzzes\\* outside the\\_window.\n\
+                                   ALEX: (quietly) Not again.\n\
+                                   MAYA: It's me.\n\
+                                   subhead KITCHEN\n\
+                                   trans FADE OUT.\n");
+        assert_eq!(imported.lines, [1, 1, 1, 5, 6, 8, 11, 13, 14]);
+        let warnings: Vec<String> = imported.warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(warnings, ["line 11 - cue extension '(ON PHONE)' of MAYA dropped",
+                              "line 13 - scene heading 'KITCHEN' needs INT./EXT. and a time of day, imported as a subheading"]);
+
+        let script = parse(&imported.text).expect("parse failed");
+        assert_eq!(script.title, "Motel");
+        assert_eq!(script.scenes[0].elements.len(), 5);
+    }
+
+    #[test]
+    fn fountain_script() {
+        let src = "Title: _The Motel_\nAuthor: Jo Park\n\n\
+                   INT. MOTEL ROOM - NIGHT\n\n\
+                   A neon sign *buzzes* outside. [[Too loud?]] Price: 5 * 3.\n\n\
+                   /* Cut scene\nstill cut */\n\n\
+                   ALEX (cont'd)\n(quietly)\nNot **again**.\n\n\
+                   MAYA ^\nIt's me.\n\n\
+                   {{include: act-two.fountain}}\n\n\
+                   {{word-count}}\n\n\
+                   >A LONG TIME LATER<\n\n\
+                   CUT TO:\n\n\
+                   >THE END<\n";
+        let imported = import(Path::new("motel.fountain"), src.as_bytes(), true).expect("not imported").expect("import failed");
+
+        assert_eq!(imported.text, "---\ntitle: \"The Motel\"\nauthor: \"Jo Park\"\n---\n\
+                                   scene INT. MOTEL ROOM - NIGHT\n\
+                                   direct A neon sign *buzzes* outside. Price: 5 \\* 3.\n\
+                                   note Too loud?\n\
+                                   ALEX: (quietly) Not **again**.\n\
+                                   MAYA: It's me.\n\
+                                   include act-two.fountain\n\
+                                   direct A LONG TIME LATER\n\
+                                   trans CUT TO:\n\
+                                   end\n");
+        assert_eq!(imported.lines, [1, 1, 1, 1, 4, 6, 6, 11, 15, 18, 22, 24, 26]);
+        let warnings: Vec<String> = imported.warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(warnings, ["line 15 - dual dialogue imported as consecutive speeches",
+                              "line 20 - unsupported directive '{{word-count}}' skipped",
+                              "line 22 - centered text imported as action"]);
+
+        let error = import(Path::new("motel.highland"), b"PK\x03\x04", true).expect("not imported").err().expect("zip accepted");
+        assert!(error.message.starts_with("Highland 2 documents are zip packages"));
+    }
+
+    #[test]
+    fn pdf_script() {
+        let script = parse("---\ntitle: Motel\nauthor: Jo Park\n---\n\
+                            scene INT. MOTEL ROOM - NIGHT\n\
+                            direct A neon sign buzzes outside the window, on and off, long enough to wrap onto a second line.\n\
+                            ALEX: (quietly) Not again.\n\
+                            trans CUT TO:\n\
+                            scene EXT. ROAD - DAY\n\
+                            MAYA: It's me.\n\
+                            end").expect("parse failed");
+        let printed = write_pdf(&typeset(&script, &CmdInfo::default()), Some("Jo"));
+        let imported = import(Path::new("motel.pdf"), &printed, true).expect("not imported").expect("import failed");
+
+        assert_eq!(imported.text, "---\ntitle: \"MOTEL\"\nauthor: \"Jo Park\"\n---\n\
+                                   scene INT. MOTEL ROOM - NIGHT\n\
+                                   direct A neon sign buzzes outside the window, on and off, long enough to wrap onto a second line.\n\
+                                   ALEX: (quietly) Not again.\n\
+                                   trans CUT TO:\n\
+                                   scene EXT. ROAD - DAY\n\
+                                   MAYA: It's me.\n\
+                                   end\n");
+        assert!(imported.warnings.is_empty());
+
+        // other programs compress their pages and often flip the coordinates and kern the text
+        let content = b"q 1 0 0 -1 0 792 cm BT /F1 12 Tf 1 0 0 -1 108 84 Tm [(INT. ) -20 (KITCHEN - DAY)] TJ \
+                        1 0 0 -1 252 112 Tm (SAM) Tj -72 -12 TD [(Needs) -600 (salt.)] TJ ET Q";
+        let stream = compress_to_vec_zlib(content, 6);
+        let mut src = format!("%PDF-1.5\n1 0 obj\n<< /Length {} /Filter /FlateDecode >>\nstream\n", stream.len()).into_bytes();
+        src.extend(stream);
+        src.extend(b"\nendstream\nendobj\n%%EOF\n");
+        let imported = import(Path::new("kitchen.pdf"), &src, true).expect("not imported").expect("import failed");
+        assert_eq!(imported.text, "---\ntitle: \"kitchen\"\n---\nscene INT. KITCHEN - DAY\nSAM: Needs salt.\n");
+        assert_eq!(imported.lines, [1, 1, 1, 1, 2]);
+
+        let error = import(Path::new("scan.pdf"), b"%PDF-1.4\n%%EOF\n", true).expect("not imported").err().expect("empty pdf accepted");
+        assert!(error.message.starts_with("no text found"));
+    }
+
+    #[test]
+    fn pdf_limits() {
+        let src = format!("1 0 obj\n<< /Length {} >>\nstream\nBT (A) Tj ET\nendstream\nendobj\n", usize::MAX);
+        assert_eq!(pdf_streams(src.as_bytes()), [b"BT (A) Tj ET".to_vec()]);
+
+        let bomb = compress_to_vec_zlib(&vec![b' '; MAX_STREAM + 1], 9);
+        let mut src = format!("1 0 obj\n<< /Length {} /Filter /FlateDecode >>\nstream\n", bomb.len()).into_bytes();
+        src.extend(bomb);
+        src.extend(b"\nendstream\nendobj\n");
+        assert!(pdf_streams(&src).is_empty());
+    }
+}
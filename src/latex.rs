@@ -0,0 +1,146 @@
+use std::fmt::Write;
+use crate::{
+    ast::*,
+    html::{ markup, HtmlError },
+};
+
+
+// markup is swapped for private use characters first so the escaping below leaves the commands alone
+const TAGS: [(&str, &str); 3] = [("\u{E010}", "\u{E013}"), ("\u{E011}", "\u{E013}"), ("\u{E012}", "\u{E013}")];
+
+
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => result.push_str("\\textbackslash{}"),
+            '~' => result.push_str("\\textasciitilde{}"),
+            '^' => result.push_str("\\textasciicircum{}"),
+            '&'|'%'|'$'|'#'|'_'|'{'|'}' => {
+                result.push('\\');
+                result.push(c);
+            }
+            '\u{E010}' => result.push_str("\\textbf{"),
+            '\u{E011}' => result.push_str("\\textit{"),
+            '\u{E012}' => result.push_str("\\underline{"),
+            '\u{E013}' => result.push('}'),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+
+fn inline(text: &str) -> String {
+    escape(&markup(text, TAGS))
+}
+
+
+// the screenplay class has its own slugs for interior and exterior headings, with the time as an option
+fn slug(heading: &str) -> String {
+    let (command, place) = match heading.split_once(". ") {
+        Some((setting, place)) if setting.eq_ignore_ascii_case("INT") => ("intslug", place),
+        Some((setting, place)) if setting.eq_ignore_ascii_case("EXT") => ("extslug", place),
+        _ => return format!("\\slug{{{}}}", escape(&heading.to_uppercase())),
+    };
+    match place.rsplit_once(" - ") {
+        Some((location, time)) => format!("\\{command}[{}]{{{}}}", escape(&time.to_uppercase()), escape(&location.to_uppercase())),
+        None => format!("\\{command}{{{}}}", escape(&place.to_uppercase())),
+    }
+}
+
+
+fn render_element(kind: &Kind) -> String {
+    // editorial elements become comments, they belong in the source but not on the page
+    let comment = |label: &str, text: &str| format!("% {label}: {}\n", text.replace('\n', " "));
+
+    match kind {
+        Kind::Montage => "BEGIN MONTAGE:\n".to_string(),
+        Kind::MonEnd => "END MONTAGE.\n".to_string(),
+        Kind::PageBreak => "\\newpage\n".to_string(),
+        Kind::End => "\\fadeout\n\\theend\n".to_string(),
+        Kind::Todo{ text } => comment("TODO", text),
+        Kind::Note{ text } => comment("NOTE", text),
+        Kind::Section{ text, .. } => comment("BEAT", text),
+        Kind::Synopsis{ text } => comment("SYNOPSIS", text),
+        Kind::Direct{ text }|Kind::Speech{ text } => format!("{}\n", inline(text)),
+        Kind::Parens{ text } => format!("({})\n", inline(text)),
+        Kind::Lyrics{ text } => format!("\\textit{{{}}}\n", text.split(" / ").map(inline).collect::<Vec<_>>().join(" \\\\\n")),
+        Kind::Subhead{ text } => format!("\\textbf{{{}}}\n", escape(&text.to_uppercase())),
+        Kind::Trans{ text } => format!("\\begin{{flushright}}\n{}\n\\end{{flushright}}\n", escape(&text.to_uppercase())),
+        Kind::Chyron{ text } => format!("CHYRON: {}\n", inline(text)),
+        Kind::Sfx{ text } => format!("SFX: {}\n", inline(&text.to_uppercase())),
+        Kind::Music{ text } => format!("MUSIC: {}\n", inline(&text.to_uppercase())),
+        Kind::Header{ text } => format!("\\textbf{{{}}}\n", escape(text)),
+        Kind::ActTag{ text }|Kind::Act{ text } => format!("\\centretitle{{\\underline{{{}}}}}\n", escape(text)),
+        Kind::ActEnd{ text } => format!("\\centretitle{{\\underline{{END OF {}}}}}\n", escape(text)),
+        Kind::Episode{ text } => format!("\\centretitle{{{}}}\n", escape(text)),
+        Kind::ComicPage{ number, text } if text.is_empty() => format!("\\textbf{{\\underline{{PAGE {number}}}}}\n"),
+        Kind::ComicPage{ number, text } => format!("\\textbf{{\\underline{{PAGE {number}}} - {}}}\n", escape(&text.to_uppercase())),
+        Kind::Panel{ number, text } if text.is_empty() => format!("\\textbf{{PANEL {number}}}\n"),
+        Kind::Panel{ number, text } => format!("\\textbf{{PANEL {number}}}\n\n{}\n", inline(text)),
+        Kind::Caption{ text } => format!("\\begin{{dialogue}}{{CAPTION}}\n{}\n\\end{{dialogue}}\n", inline(text)),
+        Kind::Dialogue(dialogue) => {
+            let lines: Vec<String> = dialogue.lines.iter()
+                .map(|line| match line {
+                    DialogueLine::Parens(text) => format!("\\paren{{{}}}", inline(text.trim_start_matches('(').trim_end_matches(')'))),
+                    DialogueLine::Speech(text) => inline(text),
+                })
+                .collect();
+            format!("\\begin{{dialogue}}{{{}}}\n{}\n\\end{{dialogue}}\n", escape(&dialogue.name), lines.join("\n"))
+        }
+    }
+}
+
+
+pub fn latex(script: &Script) -> Result<String, HtmlError> {
+    let mut result = String::from("\\documentclass{screenplay}\n\n");
+    if script.subtitle.is_empty() {
+        writeln!(result, "\\title{{{}}}", escape(&script.title))?;
+    } else {
+        writeln!(result, "\\title{{{} \\\\ {}}}", escape(&script.title), escape(&script.subtitle))?;
+    }
+    if let Some(author) = &script.author {
+        writeln!(result, "\\author{{{}}}", escape(author))?;
+    }
+    result.push_str("\n\\begin{document}\n\\coverpage\n");
+
+    for e in &script.prelude {
+        write!(result, "\n{}", render_element(&e.kind))?;
+    }
+    for scene in &script.scenes {
+        if scene.omitted {
+            writeln!(result, "\n\\slug{{SCENE {} OMITTED}}", escape(&script.scene_label(&scene.display_number())))?;
+        } else {
+            writeln!(result, "\n{}", slug(&scene.heading))?;
+        }
+        for e in &scene.elements {
+            write!(result, "\n{}", render_element(&e.kind))?;
+        }
+    }
+
+    result.push_str("\n\\end{document}\n");
+    Ok(result)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn document() {
+        let script = parse("title\nsubtitle\nscene INT. R&D LAB - NIGHT\ndirect She spends *50%* of it.\nALEX: (quietly) Back_up the #1 file.\nend").expect("parse failed");
+        let tex = latex(&script).expect("render failed");
+
+        assert!(tex.starts_with("\\documentclass{screenplay}\n\n\\title{title \\\\ subtitle}\n"));
+        assert!(tex.contains("\n\\intslug[NIGHT]{R\\&D LAB}\n"));
+        assert!(tex.contains("\nShe spends \\textit{50\\%} of it.\n"));
+        assert!(tex.contains("\n\\begin{dialogue}{ALEX}\n\\paren{quietly}\nBack\\_up the \\#1 file.\n\\end{dialogue}\n"));
+        assert!(tex.ends_with("\\fadeout\n\\theend\n\n\\end{document}\n"));
+        assert_eq!(slug("EXT. ROAD"), "\\extslug{ROAD}");
+        assert_eq!(slug("I/E. CAR - DAY"), "\\slug{I/E. CAR - DAY}");
+    }
+}
@@ -0,0 +1,434 @@
+use std::borrow::Cow;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use crate::ast::*;
+
+
+pub const PAGE_LINES: u32 = 50;
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item<'a> {
+    Heading(&'a Scene),
+    Element{ element: Cow<'a, Element>, contd: bool, more: bool },
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Page<'a> {
+    pub items: Vec<Item<'a>>,
+    pub lines: u32,
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub page_lines: Option<u32>,
+    pub contd: bool,
+    pub keep_scenes: Option<u32>,
+    pub keep_lines: u32,
+    pub notes: bool,
+    pub annotations: bool,
+}
+
+
+fn grapheme_width(grapheme: &str) -> usize {
+    grapheme.width().min(2)
+}
+
+
+pub(crate) fn text_width(text: &str) -> usize {
+    text.graphemes(true).map(grapheme_width).sum()
+}
+
+
+pub(crate) fn wrapped_lines(text: &str, width: usize) -> u32 {
+    let mut lines = 1;
+    let mut col = 0;
+    for word in text.split_whitespace() {
+        let len = text_width(word);
+        if col > 0 && col + 1 + len <= width {
+            col += 1 + len;
+            continue
+        }
+        if col > 0 {
+            lines += 1;
+            col = 0;
+        }
+        for grapheme in word.graphemes(true) {
+            let w = grapheme_width(grapheme);
+            if col > 0 && col + w > width {
+                lines += 1;
+                col = 0;
+            }
+            col += w;
+        }
+    }
+    lines
+}
+
+
+fn dialogue_line(line: &DialogueLine) -> u32 {
+    match line {
+        DialogueLine::Parens(text) => wrapped_lines(text, 26),
+        DialogueLine::Speech(text) => wrapped_lines(text, 32) + 1,
+    }
+}
+
+
+pub(crate) fn heading_lines(scene: &Scene) -> u32 {
+    wrapped_lines(&scene.heading, 60) + 1
+}
+
+
+pub(crate) fn estimate_lines(element: &Element) -> u32 {
+    match &element.kind {
+        Kind::PageBreak|Kind::Section{ .. }|Kind::Synopsis{ .. } => 0,
+        Kind::End => 4,
+        Kind::ComicPage{ .. } => 2,
+        Kind::Panel{ text, .. } if text.is_empty() => 1,
+        Kind::Panel{ text, .. } => wrapped_lines(text, 57) + 2,
+        Kind::Caption{ text } => wrapped_lines(text, 32) + 2,
+        Kind::Montage|Kind::MonEnd|Kind::ActTag{ .. }|Kind::Act{ .. }|Kind::ActEnd{ .. }|Kind::Episode{ .. } => 2,
+        Kind::Todo{ text }|Kind::Subhead{ text }|Kind::Header{ text }|Kind::Trans{ text } => wrapped_lines(text, 60) + 1,
+        Kind::Direct{ text }|Kind::Chyron{ text }|Kind::Note{ text } => wrapped_lines(text, 57) + 1,
+        Kind::Sfx{ text }|Kind::Music{ text } => wrapped_lines(text, 50) + 1,
+        Kind::Parens{ text } => wrapped_lines(text, 26),
+        Kind::Speech{ text } => wrapped_lines(text, 32) + 1,
+        Kind::Lyrics{ text } => text.split(" / ").map(|line| wrapped_lines(line, 32)).sum::<u32>() + 1,
+        Kind::Dialogue(Dialogue{ lines, .. }) => lines.iter().fold(1, |acc, line| acc + dialogue_line(line)),
+    }
+}
+
+
+// page counts in eighths, the way schedules and breakdowns quote scene length
+pub fn scene_eighths(scene: &Scene) -> u32 {
+    let lines = heading_lines(scene) + scene.elements.iter().map(estimate_lines).sum::<u32>();
+    (lines * 8).div_ceil(PAGE_LINES).max(1)
+}
+
+
+pub fn format_eighths(eighths: u32) -> String {
+    match (eighths / 8, eighths % 8) {
+        (0, part) => format!("{part}/8"),
+        (whole, 0) => whole.to_string(),
+        (whole, part) => format!("{whole} {part}/8"),
+    }
+}
+
+
+pub fn page_eighths(scene: &Scene) -> String {
+    format_eighths(scene_eighths(scene))
+}
+
+
+pub(crate) fn cue_base(name: &str) -> &str {
+    name.split_once(" (").map_or(name, |(base, _)| base)
+}
+
+
+pub(crate) fn interrupted(text: &str) -> bool {
+    let text = text.trim_end();
+    text.ends_with("--") || text.ends_with('—')
+}
+
+
+// a line cut off (--) or trailing off (...) keeps its mark on the same line as the last word
+pub(crate) fn attach_ending(text: &str) -> String {
+    let body = text.trim_end();
+    let Some(mark) = ["--", "—", "...", "…"].into_iter().find(|m| body.ends_with(m)) else {
+        return text.to_string()
+    };
+    match body[..body.len() - mark.len()].strip_suffix(' ') {
+        Some(head) => format!("{}\u{A0}{mark}", head.trim_end()),
+        None => text.to_string(),
+    }
+}
+
+
+pub(crate) fn continued(elements: &[Element], enabled: bool) -> Vec<bool> {
+    let mut speaker: Option<&str> = None;
+    let mut interrupted = false;
+
+    elements.iter().map(|element| match &element.kind {
+        Kind::Dialogue(Dialogue{ name, .. }) => {
+            let base = cue_base(name);
+            let contd = enabled && interrupted && speaker == Some(base);
+            speaker = Some(base);
+            interrupted = false;
+            contd
+        }
+        Kind::Direct{ .. }|Kind::Chyron{ .. }|Kind::Sfx{ .. }|Kind::Music{ .. } => {
+            interrupted = true;
+            false
+        }
+        Kind::Parens{ .. }|Kind::Speech{ .. }|Kind::Lyrics{ .. }|Kind::Todo{ .. }|Kind::Note{ .. }|Kind::Section{ .. }|Kind::Synopsis{ .. } => false,
+        _ => {
+            speaker = None;
+            false
+        }
+    }).collect()
+}
+
+
+fn split_dialogue(element: &Element, room: u32) -> Option<(Element, Element)> {
+    let Kind::Dialogue(Dialogue{ name, lines, adr }) = &element.kind else {
+        return None
+    };
+
+    let mut used = 2;
+    let mut split = None;
+    for (i, line) in lines.iter().enumerate().take(lines.len().saturating_sub(1)) {
+        used += dialogue_line(line);
+        if used > room {
+            break
+        }
+        if let DialogueLine::Speech(_) = line {
+            split = Some(i + 1);
+        }
+    }
+
+    let (head, tail) = lines.split_at(split?);
+    let part = |lines: &[DialogueLine]| Element{
+        line: element.line,
+        kind: Kind::Dialogue(Dialogue{ name: name.clone(), lines: lines.to_vec(), adr: *adr }),
+    };
+
+    Some((part(head), part(tail)))
+}
+
+
+struct Pager<'a> {
+    pages: Vec<Page<'a>>,
+    page_lines: u32,
+    paginated: bool,
+}
+
+impl<'a> Pager<'a> {
+    fn current(&mut self) -> &mut Page<'a> {
+        self.pages.last_mut().unwrap()
+    }
+
+    fn remaining(&self) -> u32 {
+        self.page_lines.saturating_sub(self.pages.last().unwrap().lines)
+    }
+
+    fn is_fresh(&self) -> bool {
+        self.pages.last().unwrap().items.is_empty()
+    }
+
+    fn is_start(&self) -> bool {
+        self.pages.len() == 1 && self.is_fresh()
+    }
+
+    fn break_page(&mut self) {
+        if !self.is_fresh() {
+            self.pages.push(Page::default());
+        }
+    }
+
+    fn push(&mut self, item: Item<'a>, lines: u32) {
+        let page = self.current();
+        page.items.push(item);
+        page.lines += lines;
+    }
+
+    fn place_element(&mut self, element: &'a Element, mut contd: bool) {
+        let mut element = Cow::Borrowed(element);
+
+        loop {
+            let lines = estimate_lines(&element);
+            if lines <= self.remaining() || (self.is_fresh() && split_dialogue(&element, self.remaining()).is_none()) {
+                self.push(Item::Element{ element, contd, more: false }, lines);
+                return
+            }
+            if let Some((head, tail)) = split_dialogue(&element, self.remaining()) {
+                let lines = estimate_lines(&head) + 1;
+                self.push(Item::Element{ element: Cow::Owned(head), contd, more: true }, lines);
+                element = Cow::Owned(tail);
+                contd = true;
+            }
+            self.break_page();
+        }
+    }
+
+    fn place_block(&mut self, heading: Option<&'a Scene>, elements: &'a [Element], opts: &Layout) {
+        let shown = |element: &Element| match element.kind {
+            Kind::Note{ .. } => opts.notes || opts.annotations,
+            Kind::Section{ .. }|Kind::Synopsis{ .. } => opts.annotations,
+            _ => true,
+        };
+        let total = heading.map_or(0, heading_lines) + elements.iter().filter(|e| shown(e)).map(estimate_lines).sum::<u32>();
+        if opts.keep_scenes.is_some_and(|keep| total < keep) && total > self.remaining() {
+            self.break_page();
+        }
+
+        if let Some(scene) = heading {
+            let first = elements.iter().find(|e| shown(e)).map_or(0, |e| estimate_lines(e).min(opts.keep_lines.max(2)));
+            if heading_lines(scene) + first > self.remaining() {
+                self.break_page();
+            }
+            self.push(Item::Heading(scene), heading_lines(scene));
+        }
+
+        for (element, contd) in elements.iter().zip(continued(elements, opts.contd)) {
+            match &element.kind {
+                _ if !shown(element) => (),
+                Kind::PageBreak if self.paginated => self.break_page(),
+                Kind::Act{ .. }|Kind::Episode{ .. }|Kind::ComicPage{ .. } => {
+                    if self.paginated {
+                        self.break_page();
+                    } else if !self.is_start() {
+                        self.push(Item::Element{ element: Cow::Owned(Element{ line: element.line, kind: Kind::PageBreak }), contd: false, more: false }, 0);
+                    }
+                    self.place_element(element, contd);
+                }
+                _ => self.place_element(element, contd),
+            }
+        }
+    }
+}
+
+
+pub fn layout<'a>(script: &'a Script, opts: &Layout) -> Vec<Page<'a>> {
+    let mut pager = Pager{
+        pages: vec![Page::default()],
+        page_lines: opts.page_lines.unwrap_or(u32::MAX),
+        paginated: opts.page_lines.is_some(),
+    };
+
+    pager.place_block(None, &script.prelude, opts);
+    for scene in &script.scenes {
+        pager.place_block(Some(scene), &scene.elements, opts);
+    }
+
+    pager.pages
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn wide_text() {
+        assert_eq!(wrapped_lines("short words that fit", 20), 1);
+        assert_eq!(wrapped_lines("short words that fit easily", 20), 2);
+        assert_eq!(text_width("東京タワー"), 10);
+        assert_eq!(wrapped_lines("東京タワーの上で待っている", 10), 3);
+        assert_eq!(text_width("👨‍👩‍👧 ok"), 5);
+    }
+
+    #[test]
+    fn scene_length() {
+        let script = parse("title\nsubtitle\nscene INT. A - DAY\ndirect Hi.").expect("parse failed");
+        assert_eq!(scene_eighths(&script.scenes[0]), 1);
+        assert_eq!(format_eighths(8), "1");
+        assert_eq!(format_eighths(9), "1 1/8");
+    }
+
+    #[test]
+    fn more_contd() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             scene EXT. LOC - DAY\n\
+             direct Alex looks around.\n\
+             ALEX: First line of speech. (Beat) Second line of speech. (Beat) Third line of speech."
+        ).expect("parse failed");
+
+        let opts = Layout{ page_lines: Some(9), contd: true, keep_scenes: None, keep_lines: 2, notes: false, annotations: false };
+        let pages = layout(&script, &opts);
+
+        assert_eq!(pages.len(), 2);
+        let Some(Item::Element{ element, contd: false, more: true }) = pages[0].items.last() else {
+            panic!("expected (MORE) at the bottom of page 1")
+        };
+        assert_eq!(estimate_lines(element), 3);
+        let Some(Item::Element{ element, contd: true, more: false }) = pages[1].items.first() else {
+            panic!("expected (CONT'D) at the top of page 2")
+        };
+        assert_eq!(estimate_lines(element), 7);
+    }
+
+    #[test]
+    fn heading_not_stranded() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             direct A long opening block of action that keeps going and going for a while.\n\
+             scene EXT. LOC - DAY\n\
+             direct Alex looks around."
+        ).expect("parse failed");
+
+        let opts = Layout{ page_lines: Some(5), contd: true, keep_scenes: None, keep_lines: 2, notes: false, annotations: false };
+        let pages = layout(&script, &opts);
+
+        assert_eq!(pages.len(), 2);
+        assert!(matches!(pages[1].items.first(), Some(Item::Heading(_))));
+    }
+
+    #[test]
+    fn pagebreak() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             scene EXT. LOC - DAY\n\
+             direct Alex looks around.\n\
+             pagebreak\n\
+             direct Alex leaves."
+        ).expect("parse failed");
+
+        let pages = layout(&script, &Layout{ page_lines: Some(50), ..Default::default() });
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[1].items.len(), 1);
+
+        let pages = layout(&script, &Layout::default());
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].items.iter().any(|item| matches!(item, Item::Element{ element, .. } if element.kind == Kind::PageBreak)));
+    }
+
+    #[test]
+    fn notes() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             direct Alex looks around.\n\
+             note Should this be a dream sequence?\n\
+             direct Alex leaves."
+        ).expect("parse failed");
+
+        let pages = layout(&script, &Layout::default());
+        assert_eq!(pages[0].items.len(), 2);
+
+        let pages = layout(&script, &Layout{ notes: true, ..Default::default() });
+        assert_eq!(pages[0].items.len(), 3);
+    }
+
+    #[test]
+    fn unpaginated() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             scene EXT. LOC - DAY\n\
+             ALEX: I am speaking\n\
+             direct Alex turns around.\n\
+             ALEX: Still speaking"
+        ).expect("parse failed");
+
+        let pages = layout(&script, &Layout{ contd: true, ..Default::default() });
+
+        assert_eq!(pages.len(), 1);
+        assert!(matches!(pages[0].items.last(), Some(Item::Element{ contd: true, more: false, .. })));
+    }
+
+    #[test]
+    fn endings() {
+        assert_eq!(attach_ending("I was going to --"), "I was going to\u{A0}--");
+        assert_eq!(attach_ending("Well ..."), "Well\u{A0}...");
+        assert_eq!(attach_ending("Wait--"), "Wait--");
+        assert!(interrupted("Stop —") && !interrupted("Stop."));
+    }
+}
@@ -1,14 +1,122 @@
+mod ast;
+mod breakdown;
+mod cast;
+mod daemon;
+mod diff;
+mod front;
+mod parse;
 mod html;
+mod import;
+mod latex;
+mod layout;
+mod logging;
+mod markdown;
+mod outline;
 mod pdf;
+mod progress;
+mod render;
+mod rename;
+mod review;
+mod rtf;
+mod scenes;
+mod source;
+mod speech;
+mod spell;
+mod state;
+mod status;
+mod storage;
+mod style;
+mod token;
+mod typeset;
+mod typography;
 
-use std::ops::Range;
+use std::{ fmt, ops::Range };
+use regex::Regex;
+pub use ast::*;
+pub use breakdown::*;
+pub use cast::*;
+pub use daemon::*;
+pub use diff::*;
+pub use front::*;
+pub use parse::*;
 pub use html::*;
+pub use import::*;
+pub use latex::*;
+pub use layout::*;
+pub use logging::*;
+pub use markdown::*;
+pub use outline::*;
 pub use pdf::*;
+pub use progress::*;
+pub use render::*;
+pub use rename::*;
+pub use review::*;
+pub use rtf::*;
+pub use scenes::*;
+pub use speech::*;
+pub use spell::*;
+pub use state::*;
+pub use status::*;
+pub use storage::*;
+pub use style::*;
+pub use token::*;
+pub use typeset::*;
+pub use typography::*;
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    #[default]
+    Pdf,
+    AstJson,
+    Html,
+    DialogueTsv,
+    Read,
+    ScheduleCsv,
+    Latex,
+    Rtf,
+    Markdown,
+}
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    #[default]
+    Webkit,
+    Native,
+}
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TitlePage {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DialogueNumbering {
+    Scene,
+    #[default]
+    Global,
+}
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorCharacters {
+    #[default]
+    Never,
+    Preview,
+    Always,
+}
 
 
 #[derive(Debug, Default, Clone)]
 pub struct CmdInfo {
     pub infile: String,
+    pub merge: Vec<String>,
     pub outfile: String,
     pub html: String,
 
@@ -16,8 +124,69 @@ pub struct CmdInfo {
     pub exe_loc: String,
 
     pub range: Option<Range<u32>>,
+    pub sides: Option<String>,
+    pub highlight: Option<String>,
+    pub watermark: Option<String>,
+    pub encrypt: Option<Encryption>,
+    pub split_scenes: Option<String>,
+    pub location: Option<Regex>,
+    pub context: usize,
+    pub renumber_from: Option<u32>,
+    pub episode: Option<String>,
+    pub revised_since: Option<String>,
+    pub defines: Vec<(String, String)>,
+    pub renames: Vec<Rename>,
+    pub keep_lines: Option<u32>,
+    pub keep_scenes: Option<u32>,
+    pub emit: Emit,
+    pub engine: Engine,
+    pub title_page: TitlePage,
+    pub progress: Progress,
+    pub style: Option<HouseStyle>,
+    pub scene_numbers: SceneNumbers,
+    pub color_characters: ColorCharacters,
+    pub number_dialogue: Option<DialogueNumbering>,
+    pub normalize: Normalize,
+    pub state: StateBackend,
     pub temp: bool,
+    pub keep_intermediate: bool,
     pub nopen: bool,
+    pub viewer: Option<String>,
+    pub no_contd: bool,
+    pub paginate: bool,
+    pub pages: bool,
+    pub with_notes: bool,
+    pub no_annotations: bool,
+    pub auto_end: bool,
+    pub cards: bool,
+    pub smart_typography: bool,
+    pub clearance: bool,
+    pub check: bool,
+    pub offscreen: bool,
+    pub json: bool,
+    pub resume: bool,
+    pub force: bool,
+    pub dry_run: bool,
+}
+
+impl CmdInfo {
+    pub fn inputs(&self) -> Vec<&str> {
+        std::iter::once(self.infile.as_str()).chain(self.merge.iter().map(String::as_str)).collect()
+    }
+}
+
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct Encryption {
+    pub user: String,
+    pub owner: String,
+}
+
+// the command is logged at -vv, the passwords must not be
+impl fmt::Debug for Encryption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Encryption").field("user", &"<redacted>").field("owner", &"<redacted>").finish()
+    }
 }
 
 
@@ -26,5 +195,23 @@ pub enum Command {
     Help,
     Version,
     Convert(CmdInfo),
+    Todos(CmdInfo),
+    Outline(CmdInfo),
+    Bench(CmdInfo),
+    Rename(CmdInfo),
+    Scene(CmdInfo, SceneEdit),
+    Daemon(CmdInfo, String),
+    Diff(CmdInfo, String),
+    Batch(CmdInfo, Vec<String>),
+    Distribute(CmdInfo, Vec<String>),
+    Review(CmdInfo),
+    Adr(CmdInfo),
+    Status(CmdInfo),
+    Completions(CmdInfo),
+    Spell(CmdInfo, String, Option<String>),
+    Cast(CmdInfo),
+    Import(CmdInfo),
+    Breakdown(CmdInfo),
+    CallSheet(CmdInfo, String, Vec<String>),
 }
 
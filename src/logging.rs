@@ -0,0 +1,171 @@
+use std::{
+    fmt,
+    sync::{ atomic::{ AtomicBool, AtomicU8, Ordering }, OnceLock },
+    time::{ Duration, Instant },
+};
+use serde_json::json;
+use crate::parse::Diagnostic;
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum MessageFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    #[default]
+    Normal,
+    Verbose,
+    Debug,
+}
+
+
+static LEVEL: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+static START: OnceLock<Instant> = OnceLock::new();
+static JSON: AtomicBool = AtomicBool::new(false);
+
+
+pub fn set_verbosity(verbosity: Verbosity) {
+    START.get_or_init(Instant::now);
+    LEVEL.store(verbosity as u8, Ordering::Relaxed);
+}
+
+
+pub fn verbosity() -> Verbosity {
+    match LEVEL.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        2 => Verbosity::Verbose,
+        _ => Verbosity::Debug,
+    }
+}
+
+
+pub fn enabled(verbosity: Verbosity) -> bool {
+    self::verbosity() >= verbosity
+}
+
+
+pub fn elapsed() -> Duration {
+    START.get_or_init(Instant::now).elapsed()
+}
+
+
+pub fn set_message_format(format: MessageFormat) {
+    JSON.store(format == MessageFormat::Json, Ordering::Relaxed);
+}
+
+
+pub fn message_format() -> MessageFormat {
+    if JSON.load(Ordering::Relaxed) { MessageFormat::Json } else { MessageFormat::Human }
+}
+
+
+// diagnostics only know their line, the column is null rather than a guess
+pub fn message_json(severity: &str, file: Option<&str>, line: Option<usize>, message: &str) -> String {
+    json!({ "file": file, "line": line, "column": null, "severity": severity, "message": message }).to_string()
+}
+
+
+// diagnostics without a file come from the main input
+pub fn log_diagnostic(severity: &str, input: &str, diagnostic: &Diagnostic) {
+    match message_format() {
+        MessageFormat::Human if severity == "error" => eprintln!("ERROR: {diagnostic}"),
+        MessageFormat::Human => eprintln!("WARNING: {diagnostic}"),
+        MessageFormat::Json => {
+            let file = diagnostic.file.as_deref().unwrap_or(input);
+            println!("{}", message_json(severity, Some(file), Some(diagnostic.line), &diagnostic.message));
+        }
+    }
+}
+
+
+pub fn log_message(severity: &str, message: fmt::Arguments) {
+    match message_format() {
+        MessageFormat::Human if severity == "error" => eprintln!("ERROR: {message}"),
+        MessageFormat::Human => eprintln!("WARNING: {message}"),
+        MessageFormat::Json => println!("{}", message_json(severity, None, None, &message.to_string())),
+    }
+}
+
+
+// errors and warnings always go to stderr, --quiet only silences progress and stage output;
+// with --message-format json they are printed as json lines on stdout instead
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::log_message("error", format_args!($($arg)*))
+    };
+}
+
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log_message("warning", format_args!($($arg)*))
+    };
+}
+
+
+// progress chatter on stdout, 'progress!' leaves the line open for the 'info!' that completes it
+#[macro_export]
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if $crate::enabled($crate::Verbosity::Normal) {
+            use std::io::Write;
+            print!($($arg)*);
+            let _ = std::io::stdout().flush();
+        }
+    };
+}
+
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::enabled($crate::Verbosity::Normal) {
+            println!($($arg)*);
+        }
+    };
+}
+
+
+// pipeline stages (--verbose) and their details (-vv) go to stderr with the time since start
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::enabled($crate::Verbosity::Verbose) {
+            eprintln!("[{:>8.3}s] {}", $crate::elapsed().as_secs_f64(), format_args!($($arg)*));
+        }
+    };
+}
+
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::enabled($crate::Verbosity::Debug) {
+            eprintln!("[{:>8.3}s]   {}", $crate::elapsed().as_secs_f64(), format_args!($($arg)*));
+        }
+    };
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_messages() {
+        assert_eq!(message_json("warning", Some("a.txt"), Some(4), "no subtitle"),
+                   r#"{"column":null,"file":"a.txt","line":4,"message":"no subtitle","severity":"warning"}"#);
+        assert_eq!(message_json("error", None, None, "output file not provided"),
+                   r#"{"column":null,"file":null,"line":null,"message":"output file not provided","severity":"error"}"#);
+    }
+}
@@ -1,7 +1,9 @@
 use std::env;
+use std::path::Path;
 use std::process::ExitCode;
+use std::time::{ Instant, SystemTime };
 use std::collections::HashSet;
-use scripts::{ CmdInfo, Command };
+use scripts::{ debug, error, info, progress, warn, BuildState, CastIssue, ReviewItem, Sidecar, CmdInfo, ColorCharacters, Command, Encryption, Engine, Diagnostic, DialogueNumbering, Emit, HouseStyle, Normalize, Change, OutlineItem, Progress, SceneEdit, SceneNumbers, StateBackend, TitlePage, Verbosity, HtmlError, MessageFormat, PlannedOutput };
 
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -11,13 +13,92 @@ fn get_command(args: &[String]) -> Result<Command, String> {
     let input = args::parser!{
         ["--version"+],
         ["--help"+],
+        ["--quiet"],
+        ["--verbose"],
+        ["-vv"],
+        ["--message-format", String],
         ["-i",        String],
         ["-o",        String],
         ["--temp"],
+        ["--keep-intermediate", String],
         ["--nopen"],
-        ["--scenes"+, String]
+        ["--viewer", String],
+        ["--scenes"+, String],
+        ["--keep-lines", String],
+        ["--keep-scenes", String],
+        ["--check"],
+        ["--emit", String],
+        ["--to", String],
+        ["--engine", String],
+        ["--style", String],
+        ["--format", String],
+        ["--define", String],
+        ["--episode", String],
+        ["--title-page", String],
+        ["--no-contd"],
+        ["--renumber-from", String],
+        ["--scene-numbers", String],
+        ["--paginate"],
+        ["--pages"],
+        ["--with-notes"],
+        ["--auto-end"],
+        ["--character", String],
+        ["--replace", String],
+        ["--file", String],
+        ["--listen", String],
+        ["--revised-since", String],
+        ["--json"],
+        ["--progress", String],
+        ["--resume"],
+        ["--force"],
+        ["--dry-run"],
+        ["--context", String],
+        ["--no-annotations"],
+        ["--location", String],
+        ["--split-scenes", String],
+        ["--manifest", String],
+        ["--color-characters", String],
+        ["--highlight", String],
+        ["--watermark", String],
+        ["--recipients", String],
+        ["--encrypt"],
+        ["--user-password", String],
+        ["--owner-password", String],
+        ["--number-dialogue", String],
+        ["--offscreen"],
+        ["--cards"],
+        ["--smart-typography"],
+        ["--normalize", String],
+        ["--clearance"],
+        ["--date", String],
+        ["--state", String],
+        ["--dict", String],
+        ["--words", String]
     }.parse_manual(args);
 
+    let format = match input.get("--message-format") {
+        Some(Some(f)) => match f.as_string().unwrap().as_str() {
+            "human" => MessageFormat::Human,
+            "json" => MessageFormat::Json,
+            other => return Err(format!("invalid message format: {other} (expected 'human' or 'json')")),
+        }
+        _ => MessageFormat::Human,
+    };
+    scripts::set_message_format(format);
+
+    // json messages share stdout with nothing else, so progress output is off unless asked for
+    let verbosity = if input.has("-vv") {
+        Verbosity::Debug
+    } else if input.has("--verbose") {
+        Verbosity::Verbose
+    } else if input.has("--quiet") || format == MessageFormat::Json {
+        Verbosity::Quiet
+    } else {
+        Verbosity::Normal
+    };
+    scripts::set_verbosity(verbosity);
+    debug!("arguments: {:?}", redacted(&args[1..]));
+
     if input.has("--version") {
         return Ok(Command::Version)
     }
@@ -25,54 +106,405 @@ fn get_command(args: &[String]) -> Result<Command, String> {
         return Ok(Command::Help)
     }
 
+    let todos = args.get(1).is_some_and(|a| a == "todos");
+    let outline = args.get(1).is_some_and(|a| a == "outline");
+    let bench = args.get(1).is_some_and(|a| a == "bench");
+    let rename = args.get(1).is_some_and(|a| a == "rename");
+    let scene = args.get(1).is_some_and(|a| a == "scene");
+    let daemon = args.get(1).is_some_and(|a| a == "daemon");
+    let diff = args.get(1).is_some_and(|a| a == "diff");
+    let batch = args.get(1).is_some_and(|a| a == "batch");
+    let review = args.get(1).is_some_and(|a| a == "review");
+    let adr = args.get(1).is_some_and(|a| a == "adr");
+    let breakdown = args.get(1).is_some_and(|a| a == "breakdown");
+    let callsheet = args.get(1).is_some_and(|a| a == "callsheet");
+    let status = args.get(1).is_some_and(|a| a == "status");
+    let completions = args.get(1).is_some_and(|a| a == "completions");
+    let spell = args.get(1).is_some_and(|a| a == "spell");
+    let cast = args.get(1).is_some_and(|a| a == "cast");
+    let import = args.get(1).is_some_and(|a| a == "import");
     let mut cmd: CmdInfo = CmdInfo::default();
 
-    if let Some(Some(i)) = input.get("-i") { // Some(arg Some(param))
+    if diff {
+        let mut drafts = args.iter().skip(2).filter(|a| !a.starts_with('-'));
+        let old = drafts.next().ok_or("old draft not provided")?.clone();
+        cmd.infile = drafts.next().ok_or("new draft not provided")?.clone();
+        cmd.json = input.has("--json");
+        return Ok(Command::Diff(cmd, old))
+    }
+
+    if let Some(Some(m)) = input.get("--manifest") {
+        let manifest = m.as_string().unwrap();
+        let mut files = scripts::read_manifest(manifest).map_err(|e| format!("failed to read manifest {manifest}: {e}"))?.into_iter();
+        cmd.infile = files.next().ok_or("manifest lists no input files")?;
+        cmd.merge = files.collect();
+    } else if let Some(Some(i)) = input.get("-i") { // Some(arg Some(param))
         cmd.infile = i.as_string().unwrap().to_owned();
-    } else {
-        return Err("ERROR: input file not provided".into())
+    } else if !daemon {
+        return Err("input file not provided".into())
+    }
+    if !batch && cmd.infile.contains(',') {
+        let mut files: Vec<String> = cmd.infile.split(',').map(|i| i.trim().to_string()).collect();
+        cmd.infile = files.remove(0);
+        cmd.merge = files;
+    }
+    if (rename || scene) && !cmd.merge.is_empty() {
+        return Err("rename and scene commands take a single input file".into())
     }
     if let Some(Some(o)) = input.get("-o") {
-        cmd.infile = o.as_string().unwrap().to_owned();
-    } else {
-        return Err("ERROR: output file not provided".into())
+        cmd.outfile = o.as_string().unwrap().to_owned();
+    } else if !todos && !outline && !bench && !rename && !scene && !daemon && !review && !adr && !status && !completions && !spell && !cast && !input.has("--split-scenes") && !input.has("--pages") {
+        return Err("output file not provided".into())
     }
     cmd.temp  = input.has("--temp");
+    cmd.keep_intermediate = input.has("--keep-intermediate");
     cmd.nopen = input.has("--nopen");
+    cmd.viewer = match input.get("--viewer") {
+        Some(Some(v)) => Some(v.as_string().unwrap().to_owned()),
+        _ => env::var("SCRIPTS_VIEWER").ok().filter(|v| !v.trim().is_empty()),
+    };
+    cmd.check = input.has("--check");
+    cmd.offscreen = input.has("--offscreen");
+    cmd.no_contd = input.has("--no-contd");
+    cmd.paginate = input.has("--paginate");
+    cmd.pages = input.has("--pages");
+    cmd.with_notes = input.has("--with-notes");
+    cmd.no_annotations = input.has("--no-annotations");
+    cmd.auto_end = input.has("--auto-end");
+    cmd.cards = input.has("--cards");
+    cmd.smart_typography = input.has("--smart-typography");
+    cmd.clearance = input.has("--clearance");
+    cmd.resume = input.has("--resume");
+    cmd.force = input.has("--force");
+    cmd.dry_run = input.has("--dry-run");
+
+    let html = match input.get("--keep-intermediate") {
+        Some(Some(k)) => k.as_string().unwrap().to_owned(),
+        _ => scripts::temp_html(),
+    };
 
-    if let Some(Some(s)) = input.get("--scenes") {
+    if let Some(Some(s)) = input.get("--scenes").filter(|_| !callsheet) {
         let range = s.as_string().unwrap();
 
         if let Some(j) = range.find('-') {
-            let start: u32 = range[0..j].parse().map_err(|_| "ERROR: range argument was not integer".to_string())?;
-            let stop: u32 = range[(j+1)..range.len()].parse().map_err(|_| "ERROR: range argument was not integer".to_string())?;
+            let start: u32 = range[0..j].parse().map_err(|_| "range argument was not integer".to_string())?;
+            let stop: u32 = range[(j+1)..range.len()].parse().map_err(|_| "range argument was not integer".to_string())?;
             cmd.range = Some(start..(stop+1));
         } else {
-            let start: u32 = range.parse().map_err(|_| "ERROR: scene argument was not integer".to_string())?;
+            let start: u32 = range.parse().map_err(|_| "scene argument was not integer".to_string())?;
             cmd.range = Some(start..(start+1));
         }
     }
 
-    cmd.file_root = cmd.infile.strip_suffix(".txt").ok_or("ERROR: expected '.txt' file as input")?.to_string();
-    cmd.exe_loc = env::current_exe()
-            .unwrap()
-            .parent()
-            .unwrap()
-            .as_os_str()
-            .to_str()
-            .unwrap()
-            .to_string();
+    if let Some(Some(e)) = input.get("--emit").or(input.get("--to")) {
+        let name = e.as_string().unwrap();
+        cmd.emit = Emit::from_name(name).ok_or_else(|| format!("unknown emit format: {name}"))?;
+    }
+
+    if let Some(Some(e)) = input.get("--engine") {
+        cmd.engine = match e.as_string().unwrap().as_str() {
+            "webkit" => Engine::Webkit,
+            "native" => Engine::Native,
+            other => return Err(format!("unknown engine: {other}")),
+        };
+    }
+
+    if let Some(Some(r)) = input.get("--renumber-from") {
+        let start: u32 = r.as_string().unwrap().parse().map_err(|_| "renumber-from argument was not integer".to_string())?;
+        cmd.renumber_from = Some(start);
+    }
+
+    if let Some(Some(t)) = input.get("--title-page") {
+        cmd.title_page = match t.as_string().unwrap().as_str() {
+            "auto" => TitlePage::Auto,
+            "always" => TitlePage::Always,
+            "never" => TitlePage::Never,
+            other => return Err(format!("unknown title page mode: {other}")),
+        };
+    }
+
+    if let Some(Some(n)) = input.get("--scene-numbers") {
+        cmd.scene_numbers = match n.as_string().unwrap().as_str() {
+            "left" => SceneNumbers::Left,
+            "both" => SceneNumbers::Both,
+            "none" => SceneNumbers::Hidden,
+            other => return Err(format!("unknown scene numbering mode: {other}")),
+        };
+    }
+
+    if let Some(Some(c)) = input.get("--color-characters") {
+        cmd.color_characters = match c.as_string().unwrap().as_str() {
+            "preview" => ColorCharacters::Preview,
+            "always" => ColorCharacters::Always,
+            other => return Err(format!("unknown character color mode: {other}")),
+        };
+    }
+
+    if let Some(Some(h)) = input.get("--highlight") {
+        cmd.highlight = Some(h.as_string().unwrap().to_uppercase());
+    }
+
+    if let Some(Some(w)) = input.get("--watermark") {
+        cmd.watermark = Some(w.as_string().unwrap().to_owned());
+    }
 
-    cmd.html = format!("{}/user/temp.html", cmd.exe_loc);
+    if input.has("--encrypt") {
+        let password = |name| match input.get(name) {
+            Some(Some(p)) => Some(p.as_string().unwrap().to_owned()),
+            _ => None,
+        };
+        let user = password("--user-password").unwrap_or_default();
+        let owner = password("--owner-password").ok_or("encrypt requires --owner-password")?;
+        cmd.encrypt = Some(Encryption{ user, owner });
+    }
+
+    if let Some(Some(n)) = input.get("--number-dialogue") {
+        cmd.number_dialogue = match n.as_string().unwrap().as_str() {
+            "scene" => Some(DialogueNumbering::Scene),
+            "global" => Some(DialogueNumbering::Global),
+            other => return Err(format!("unknown dialogue numbering mode: {other}")),
+        };
+    }
+
+    if let Some(Some(n)) = input.get("--normalize") {
+        let names = n.as_string().unwrap();
+        cmd.normalize = Normalize::from_names(names).ok_or(format!("unknown normalization in: {names}"))?;
+    }
+
+    if let Some(Some(s)) = input.get("--state") {
+        cmd.state = match s.as_string().unwrap().as_str() {
+            "file" => StateBackend::File,
+            "sqlite" if cfg!(feature = "sqlite") => StateBackend::Sqlite,
+            "sqlite" => return Err("this build does not include the sqlite state backend (enable the 'sqlite' feature)".into()),
+            other => return Err(format!("unknown state backend: {other}")),
+        };
+    }
+
+    if let Some(Some(d)) = input.get("--define") {
+        for pair in d.as_string().unwrap().split(',') {
+            let (key, value) = pair.split_once('=').ok_or(format!("expected KEY=VALUE in define: {pair}"))?;
+            cmd.defines.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    if let Some(Some(c)) = input.get("--character").filter(|_| !rename) {
+        cmd.sides = Some(c.as_string().unwrap().trim().to_string());
+    } else if let Some(Some(c)) = input.get("--character") {
+        cmd.renames.extend(scripts::parse_renames(c.as_string().unwrap(), true)?);
+    }
+
+    if let Some(Some(s)) = input.get("--split-scenes") {
+        cmd.split_scenes = Some(s.as_string().unwrap().to_owned());
+    }
+
+    if let Some(Some(l)) = input.get("--location") {
+        let pattern = l.as_string().unwrap();
+        cmd.location = Some(regex::Regex::new(&format!("(?i){pattern}")).map_err(|e| format!("invalid location pattern: {e}"))?);
+    }
+
+    if let Some(Some(c)) = input.get("--context") {
+        cmd.context = c.as_string().unwrap().parse().map_err(|_| "context argument was not integer".to_string())?;
+    }
+
+    if let Some(Some(r)) = input.get("--replace") {
+        cmd.renames.extend(scripts::parse_renames(r.as_string().unwrap(), false)?);
+    }
 
+    if let Some(Some(p)) = input.get("--progress") {
+        cmd.progress = match p.as_string().unwrap().as_str() {
+            "human" => Progress::Human,
+            "json" => Progress::Json,
+            other => return Err(format!("unknown progress format: {other}")),
+        };
+    }
+
+    if let Some(Some(r)) = input.get("--revised-since") {
+        cmd.revised_since = Some(r.as_string().unwrap().to_owned());
+    }
+
+    if let Some(Some(e)) = input.get("--episode") {
+        cmd.episode = Some(e.as_string().unwrap().to_owned());
+    }
+
+    if let Some(Some(h)) = input.get("--style").or_else(|| input.get("--format")) {
+        let name = h.as_string().unwrap();
+        cmd.style = Some(HouseStyle::from_name(name).ok_or(format!("unknown house style: {name}"))?);
+    }
+
+    if let Some(Some(k)) = input.get("--keep-lines") {
+        let lines: u32 = k.as_string().unwrap().parse().map_err(|_| "keep-lines argument was not integer".to_string())?;
+        cmd.keep_lines = Some(lines);
+    }
+
+    if let Some(Some(k)) = input.get("--keep-scenes") {
+        let lines: u32 = k.as_string().unwrap().parse().map_err(|_| "keep-scenes argument was not integer".to_string())?;
+        cmd.keep_scenes = Some(lines);
+    }
+
+    if batch {
+        cmd.exe_loc = exe_loc();
+        cmd.html = html;
+        let inputs = cmd.infile.split(',').map(|i| i.trim().to_string()).collect();
+        return Ok(Command::Batch(cmd, inputs))
+    }
+
+    if daemon {
+        cmd.exe_loc = exe_loc();
+        cmd.html = html;
+        let address = match input.get("--listen") {
+            Some(Some(l)) => l.as_string().unwrap().to_owned(),
+            _ => scripts::default_address(),
+        };
+        return Ok(Command::Daemon(cmd, address))
+    }
+
+    cmd.file_root = file_root(&cmd.infile).ok_or("expected '.txt' file as input")?.to_string();
+    if cmd.outfile == cmd.infile {
+        return Err("output file would overwrite the input file".into())
+    }
+    if let Some(file) = cmd.merge.iter().find(|f| file_root(f).is_none()) {
+        return Err(format!("expected '.txt' file as input: {file}"))
+    }
+
+    if todos {
+        return Ok(Command::Todos(cmd))
+    }
+    if outline {
+        cmd.exe_loc = exe_loc();
+        cmd.html = html;
+        return Ok(Command::Outline(cmd))
+    }
+    if review {
+        return Ok(Command::Review(cmd))
+    }
+    if adr {
+        return Ok(Command::Adr(cmd))
+    }
+    if status {
+        return Ok(Command::Status(cmd))
+    }
+    if completions {
+        return Ok(Command::Completions(cmd))
+    }
+    if cast {
+        return Ok(Command::Cast(cmd))
+    }
+    if import {
+        return Ok(Command::Import(cmd))
+    }
+    if spell {
+        let dict = match input.get("--dict") {
+            Some(Some(d)) => d.as_string().unwrap().to_owned(),
+            _ => return Err("spell requires --dict".into()),
+        };
+        let words = match input.get("--words") {
+            Some(Some(w)) => Some(w.as_string().unwrap().to_owned()),
+            _ => None,
+        };
+        return Ok(Command::Spell(cmd, dict, words))
+    }
+    if callsheet {
+        let date = match input.get("--date") {
+            Some(Some(d)) => d.as_string().unwrap().to_owned(),
+            _ => return Err("callsheet requires --date".into()),
+        };
+        let mut scenes = Vec::new();
+        match input.get("--scenes") {
+            Some(Some(s)) => for item in s.as_string().unwrap().split(',').map(str::trim) {
+                match item.split_once('-').map(|(a, b)| (a.parse::<u32>(), b.parse::<u32>())) {
+                    Some((Ok(start), Ok(stop))) => scenes.extend((start..=stop).map(|n| n.to_string())),
+                    _ => scenes.push(item.to_string()),
+                }
+            }
+            _ => return Err("callsheet requires --scenes".into()),
+        }
+        cmd.exe_loc = exe_loc();
+        cmd.html = html;
+        return Ok(Command::CallSheet(cmd, date, scenes))
+    }
+    if breakdown {
+        cmd.exe_loc = exe_loc();
+        cmd.html = html;
+        return Ok(Command::Breakdown(cmd))
+    }
+    if bench {
+        return Ok(Command::Bench(cmd))
+    }
+    if rename {
+        if cmd.renames.is_empty() {
+            return Err("rename requires --character or --replace".into())
+        }
+        return Ok(Command::Rename(cmd))
+    }
+    if scene {
+        let number = args.get(3).ok_or("scene number not provided")?.clone();
+        let edit = match args.get(2).map(String::as_str) {
+            Some("insert-after") => match input.get("--file") {
+                Some(Some(f)) => SceneEdit::InsertAfter{ number, file: f.as_string().unwrap().to_owned() },
+                _ => return Err("insert-after requires --file".into()),
+            },
+            Some("delete") => SceneEdit::Delete{ number },
+            Some(other) => return Err(format!("unknown scene command: {other}")),
+            None => return Err("scene command not provided".into()),
+        };
+        return Ok(Command::Scene(cmd, edit))
+    }
+
+    cmd.exe_loc = exe_loc();
+    cmd.html = html;
+    if !input.has("--emit") && !input.has("--to") {
+        cmd.emit = Emit::from_output(&cmd.outfile).unwrap_or_default();
+    }
+
+    if let Some(Some(r)) = input.get("--recipients") {
+        if cmd.emit != Emit::Pdf {
+            return Err("recipients require pdf output".into())
+        }
+        let recipients: Vec<String> = r.as_string().unwrap().split(',').map(|n| n.trim().to_string()).filter(|n| !n.is_empty()).collect();
+        if recipients.is_empty() {
+            return Err("no recipients provided".into())
+        }
+        return Ok(Command::Distribute(cmd, recipients))
+    }
+
+    debug!("{cmd:?}");
     Ok(Command::Convert(cmd))
 }
 
+// passwords are kept out of the debug log, whether given as '--name value' or '--name=value'
+fn redacted(args: &[String]) -> Vec<&str> {
+    let mut value = false;
+    args.iter().map(|arg| {
+        let (name, inline) = arg.split_once('=').map_or((arg.as_str(), false), |(name, _)| (name, true));
+        let password = matches!(name, "--user-password"|"--owner-password");
+        if std::mem::replace(&mut value, password && !inline) || (password && inline) { "<redacted>" } else { arg.as_str() }
+    }).collect()
+}
+
+// scripts from other programs keep their own extension
+fn file_root(path: &str) -> Option<&str> {
+    if scripts::is_imported(path) {
+        return path.rsplit_once('.').map(|(root, _)| root)
+    }
+    path.strip_suffix(".txt")
+}
+
+fn exe_loc() -> String {
+    env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .as_os_str()
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
 fn _get_command(args: &[String]) -> Result<Command, String> {
     let valid: HashSet<&str> = ["-v", "--version", "-h", "--help", "--temp", "--nopen", "-i", "-o", "-s", "--scenes"].into_iter().collect();
     for a in args {
         if a.starts_with('-') && !valid.contains(a.as_str()) {
-            return Err(format!("ERROR: invalid option specified: {a}"))
+            return Err(format!("invalid option specified: {a}"))
         }
     }
 
@@ -86,14 +518,14 @@ fn _get_command(args: &[String]) -> Result<Command, String> {
     let mut cmd: CmdInfo = CmdInfo::default();
 
     if let Some(i) = args.iter().position(|s| s == "-i") {
-        cmd.infile = args.get(i + 1).ok_or("ERROR: input file not provided")?.clone();
+        cmd.infile = args.get(i + 1).ok_or("input file not provided")?.clone();
     } else {
-        return Err("ERROR: input file not provided".to_string())
+        return Err("input file not provided".to_string())
     }
     if let Some(i) = args.iter().position(|s| s == "-o") {
-        cmd.outfile = args.get(i + 1).ok_or("ERROR: output file not provided")?.clone();
+        cmd.outfile = args.get(i + 1).ok_or("output file not provided")?.clone();
     } else {
-        return Err("ERROR: output file not provided".to_string())
+        return Err("output file not provided".to_string())
     }
 
     if args.contains(&"--temp".to_string()) {
@@ -105,19 +537,19 @@ fn _get_command(args: &[String]) -> Result<Command, String> {
     }
 
     if let Some(i) = args.iter().position(|s| s == "-s" || s == "--scenes") {
-        let range = args.get(i + 1).ok_or("ERROR: scene spec declared but not provided")?.clone();
+        let range = args.get(i + 1).ok_or("scene spec declared but not provided")?.clone();
 
         if let Some(j) = range.find('-') {
-            let start: u32 = range[0..j].parse().map_err(|_| "ERROR: range argument was not integer".to_string())?;
-            let stop: u32 = range[(j+1)..range.len()].parse().map_err(|_| "ERROR: range argument was not integer".to_string())?;
+            let start: u32 = range[0..j].parse().map_err(|_| "range argument was not integer".to_string())?;
+            let stop: u32 = range[(j+1)..range.len()].parse().map_err(|_| "range argument was not integer".to_string())?;
             cmd.range = Some(start..(stop+1));
         } else {
-            let start: u32 = range.parse().map_err(|_| "ERROR: scene argument was not integer".to_string())?;
+            let start: u32 = range.parse().map_err(|_| "scene argument was not integer".to_string())?;
             cmd.range = Some(start..(start+1));
         }
     }
 
-    cmd.file_root = cmd.infile.strip_suffix(".txt").ok_or("ERROR: expected '.txt' file as input")?.to_string();
+    cmd.file_root = cmd.infile.strip_suffix(".txt").ok_or("expected '.txt' file as input")?.to_string();
 
     cmd.exe_loc = env::current_exe()
             .unwrap()
@@ -128,7 +560,7 @@ fn _get_command(args: &[String]) -> Result<Command, String> {
             .unwrap()
             .to_string();
 
-    cmd.html = format!("{}/user/temp.html", cmd.exe_loc);
+    cmd.html = scripts::temp_html();
 
     Ok(Command::Convert(cmd))
 }
@@ -139,32 +571,204 @@ fn cmd_help() -> ExitCode {
     println!(r#"
 Synopsis:
     scripts [OPTIONS] -i <input file> -o <output file>
+    scripts [OPTIONS] -i <input file>,<input file>... -o <output file>
+    scripts [OPTIONS] --manifest <manifest file> -o <output file>
+    scripts [OPTIONS] --pages -i <input file>
+    scripts [OPTIONS] --recipients <name,name...> -i <input file> -o <output file>
+    scripts todos [--check] -i <input file>
+    scripts outline -i <input file> [-o <output '.md' or '.pdf' file>]
+    scripts review -i <input file>
+    scripts status -i <input file> [-o <output file>]
+    scripts completions -i <input file> [-o <output '.json' file>]
+    scripts cast -i <input file>
+    scripts import -i <'.trelby', '.fountain' or '.pdf' file> -o <output '.txt' file>
+    scripts spell --dict <dictionary> [--words <word list>] [--check] -i <input file>
+    scripts breakdown -i <input file> -o <output '.csv' or '.pdf' file>
+    scripts callsheet --date <date> --scenes <scene,scene...> -i <input file> -o <output file>
+    scripts adr [--offscreen] -i <input file> [-o <output '.tsv' file>]
+    scripts scene insert-after <number> --file <scene file> -i <input file>
+    scripts scene delete <number> -i <input file>
+    scripts batch [--resume] [OPTIONS] -i <input file>,<input file>... -o <output directory>
+    scripts diff [--json] <old draft> <new draft>
+    scripts daemon [--listen <address>]
+    scripts rename [--check] -i <input file> --character <OLD=NEW,...> --replace <old=new,...>
 
 Options:
     -i <path to source>     Path to input '.txt' file, formatted in provided specification
-    -o <path to output>     Path to output '.pdf' file
+    -i <path>,<path>...     Merge several '.txt' files into one script, in the given order
+    -o <path to output>     Path to output '.pdf' file (outline: '.md' for a Markdown outline)
+        --manifest <path>   Merge the '.txt' files listed in a manifest, one per line (relative to the manifest)
         --temp              Include intermediate html in output
-    -s, --scenes <range>    Output selected scenes without title page
+        --keep-intermediate <path>
+                            Write the intermediate html to <path> and keep it, instead of a temporary file that is
+                            removed once the pdf is generated
+        --no-contd          Do not mark continued dialogue with (CONT'D)
+        --paginate          Compute page breaks natively, splitting dialogue with (MORE)/(CONT'D)
+        --pages             Print the estimated page count from the native page breaks, without generating a pdf
+        --engine <engine>   PDF backend: 'webkit' (default, html through wkhtmltopdf) or 'native' (typeset directly:
+                            55 lines per page, courier 12pt, fixed indents, (MORE)/(CONT'D) and widow/orphan control)
+        --with-notes        Render 'note' elements instead of omitting them
+        --clearance         Include scenes marked 'restricted' instead of replacing them with a placeholder page
+        --cards             Output index cards instead of the script: heading, synopsis and length in eighths, six per page
+        --smart-typography  Curl quotes and apostrophes, and turn '--' into em dashes and '...' into ellipses in action and dialogue
+        --auto-end          Close the script with FADE OUT. / THE END if it does not end with a closure (see --check)
+    -s, --scenes <range>    Output selected scenes only
+                            (callsheet) Scenes shot that day, such as 4,12A,15-17
+        --date <date>       (callsheet) Shoot date printed on the call sheet
+        --dict <path>       (spell) Hunspell dictionary, the '.dic' file with its '.aff' alongside (en_US.dic)
+        --words <path>      (spell) Project word list, one per line, for names and places; defaults to '<input>.words.txt'
+        --renumber-from <n> Number output scenes sequentially from n instead of their original or locked numbers, dropping omitted scenes
+        --scene-numbers <m> Scene numbers: 'left' (default), 'both' margins (shooting script) or 'none'
+        --title-page <mode> Title page: 'auto' (default, omitted for scene ranges), 'always' or 'never'
+        --progress <format> Progress output: 'human' (default, a bar with the scene being rendered when stdout is a
+                            terminal) or 'json' events with phase, percent and scene on stdout, and batch adds a
+                            'done' event with the input and output of each file
+        --emit <format>     Output format: 'pdf' (default), 'ast-json' (the script with its warnings and revised and
+                            redacted lines, which are left out when empty), 'html' (preview with annotations),
+                            'dialogue-tsv' (numbered speeches with scene and character, for dubbing and ADR)
+                            'read' (plain text for text-to-speech table reads, see --normalize)
+                            'schedule-csv' (one row per scene with location, time, length and cast)
+                            'latex' (a document for the 'screenplay' class, notes kept as comments)
+                            'rtf' (Courier with named screenplay paragraph styles, for submissions)
+                            or 'markdown' (scene headings, bold cues and quoted dialogue, for wikis and reviews);
+                            without it the format follows the output extension (.json, .html, .tsv, .csv, .tex, .rtf, .md, else pdf)
+        --to <format>       Same as --emit
+        --normalize <list>  Spoken forms in 'read' output: any of 'numbers', 'currency', 'abbreviations', or 'all' (default) / 'none'
+        --number-dialogue <scope>
+                            Number every speech in the margin and dialogue list: 'scene' (restarting each scene) or 'global'
+        --color-characters <when>
+                            Tint dialogue with a stable color per character: 'preview' (html output only) or 'always'
+        --highlight <name>  Shade the character's dialogue and bold their speech, for an actor's table read copy
+        --watermark <text>  Print the text faintly across every page and 'Prepared for <text>' in the footer
+        --encrypt           Lock the pdf with qpdf (bundled or on the PATH) using the passwords below, no pdf is left if this fails
+        --owner-password <password>
+                            (encrypt) Password required to change permissions
+        --user-password <password>
+                            (encrypt) Password required to open the pdf, none by default
+        --recipients <name,...>
+                            Write one watermarked copy per recipient, named after the output ('script-jane-doe.pdf')
+        --no-annotations    Leave notes, TODOs and beat labels out of the margin of the html preview
+        --define <k=v,...>  Define variables expanded as $KEY in the text, overrides 'define'
+        --character <name>  Output sides: only the scenes in which the character speaks or is named in action
+        --split-scenes <d>  Write one file per scene into directory d, named by scene number and heading
+        --location <pat>    Output only scenes whose location matches the text or regular expression (case insensitive)
+        --context <n>       Include n scenes around each selected scene (with --character)
+        --character <A=B>   Rename characters in cues, action, dialogue and cast/alias lists (rename only)
+        --replace <a=b>     Replace text in script content, leaving keywords and comments alone (rename only)
+        --listen <address>  Socket path (or host:port on Windows) for the daemon's line-delimited JSON-RPC API, by default
+                            scripts.sock in $XDG_RUNTIME_DIR or the temp dir, only open to its owner. On Windows every request
+                            carries a 'token' read from scripts-daemon.token in the temp dir
+                            Methods: parse, build, query (outline, todos, acts), stats and shutdown, each given a 'path'
+        --file <path>       Scenes to insert, locked as 12A, 12B... after the given scene (scene insert-after only)
+        --revised-since <f> Mark scenes and elements changed since an earlier draft with '*' in the right margin
+        --episode <number>  Prefix scene numbers with an episode number (EP105 SC12), overrides 'episode-number'
+        --style <pack>      House style: 'us-feature' (default), 'bbc', 'multicam', 'audio' or 'comic', overrides 'doctype'
+        --format <pack>     Same as --style; 'sitcom' is accepted as a name for 'multicam'
+        --keep-lines <n>    Minimum lines of a block kept together across a page break (default 2)
+        --keep-scenes <n>   Push scenes shorter than n lines to the next page instead of splitting them
+        --state <backend>   Where build state and review notes are kept: 'file' (default, json next to the outputs)
+                            or 'sqlite' (one '.scripts-state.db' per directory, needs the 'sqlite' feature)
+        --dry-run           Parse and lay out, then list the files that would be written with their size and estimated
+                            page count, without writing anything, running webkit or opening a viewer
+        --nopen             Do not open the output once it is generated
+        --viewer <program>  Open the output with <program>, which may include arguments, instead of the default app;
+                            the SCRIPTS_VIEWER environment variable sets it for every run
+        --force             Rebuild even if the output is up to date with its input, options, stylesheet and version
+        --resume            (batch) Skip outputs whose input and options are unchanged since the last build
+        --json              (diff) Print changes as JSON
+        --offscreen         (adr) Also list every V.O. and O.S. line, not only those tagged '#adr'
+        --check             (todos) Exit with an error if any TODO remains
+                            (spell) Exit with an error if any word is misspelled
+                            (rename) Report replacements without writing any file
+                            (convert) Warn when the script does not end with a closure, for delivery drafts
+        --message-format <f>
+                            Errors and warnings as 'human' text on stderr (default) or 'json' lines on stdout with
+                            file, line, column (null, as diagnostics only carry lines), severity and message, for
+                            editors and CI (implies --quiet)
+        --quiet             Only print errors and warnings, no progress output (webkit is run with --quiet)
+        --verbose           Also print each pipeline stage with the time since start on stderr
+    -vv                     Also print the parsed options, stage details and the webkit command line
     -v, --version           Show version information
     -h, --help              Show documentation
 
 Format guide:
     scene   [CONTENT]               Begin new scene
+    scene   [CONTENT] #[NUMBER]#    Begin new scene with a locked number such as 11A, kept across changes
+    omitted [NUMBER]                Locked scene that was deleted, rendered as SCENE n OMITTED
     trans   [CONTENT]               Transition annotation
     direct  [CONTENT]               Action lines
     subhead [CONTENT]               Subheading
     chyron  [CONTENT]               Title or text
+    sfx     [CONTENT]               Sound effect cue (numbered in the audio style)
+    music   [CONTENT]               Music cue (numbered in the audio style)
+    page    [CONTENT]               Comic page, numbered automatically and starting a new sheet
+    panel   [CONTENT]               Comic panel with an optional description, numbered per page
+    caption [CONTENT]               Comic caption box
     parens  [CONTENT]               Parenthetical
     speech  [CONTENT]               Character speech
+    lyrics  [CONTENT]               Sung lines in italics, separated by ' / '
+    note    [CONTENT]               Editorial note, omitted unless --with-notes is given
+    #       [CONTENT]               Section header for the outline (## for subsections), not rendered
+    =       [CONTENT]               Synopsis line for the outline, not rendered
+    doctype [STYLE]                 Select the house style for this script
+    episode-number [NUMBER]         Episode number used in compound scene numbers
+    {{[CATEGORY]: [ITEM]}}            Inline breakdown tag in action, printed as the item alone ({{prop: revolver}})
+    restricted                      Mark the current scene for limited distribution (see --clearance)
+    props [ITEM], [ITEM]...         Breakdown tags for the current scene, also 'wardrobe', 'vehicles' and 'extras'
+    cast [NAME], [NAME]...          Declare characters whose cues may be written in lowercase
+    alias [NAME], ... = [NAME]      Write cues with these names as the given character
+    define [KEY] = [VALUE]          Define a variable expanded wherever $KEY appears
+    include [PATH]                  Insert another '.txt' file here (relative to this file, without title lines)
+    cold-open                       Cold open act tag
+    prologue                        Begin un-numbered pre-title material
+    prologue-end                    End the prologue, scene numbering starts after it
+    tag                             Closing tag act tag
+    act     [NAME]                  Begin a new act on a fresh page, closing the previous one with END OF ACT
+    episode [TITLE]                 Begin a new episode on a fresh page
     montage                         Begin scene montage
     mon-end                         End scene montage
-    [NAME]: [CONTENT]               Named character speech
+    pagebreak                       Start a new page
+    end                             Close the script with FADE OUT. / THE END
+    [NAME]: [CONTENT]               Named character speech (NAME in capitals or declared in 'cast')
     [NAME]: ([PARENS]) [CONTENT]    Named character speech with parenthetical
-    *                               Inline comment
+    [NAME]: [CONTENT] #adr          Speech to be re-recorded, listed by 'scripts adr'
+    [NAME] & [NAME]: [CONTENT]      Speech shared by several characters
+    *[TEXT]* **[TEXT]** _[TEXT]_    Italic, bold and underlined text in action and dialogue
+    *                               Inline comment (after whitespace or at line start)
+    \* \\ \_                        Literal asterisk, backslash and underscore
     ***                             File tail comment
 
 Notes:
-    Title and subtitle MUST be provided in any 2 lines before regular content
+    Title and subtitle are the first 2 lines; a line starting with a mode keyword or scene heading there means they are
+    missing, and the title is taken from the file name
+    Metadata may instead be given in a front matter block at the very top, fenced by '---' (YAML) or '+++' (TOML),
+    with the keys title, subtitle, author, draft, doctype, episode, characters, aliases, locations and variables
+    Keywords and scene prefixes can be written in another language by mapping them in the front matter,
+    as in 'keywords: {{ szene: scene, aktion: direct }}' and 'prefixes: {{ INNEN.: INT., AUSSEN.: EXT. }}';
+    output always uses the standard terms ('include' is not remapped)
+    Consecutive scene headings are checked for continuity: CONTINUOUS at a different location, or DAY and NIGHT
+    back to back at the same location without a transition between them, produce a warning
+    Dialogue ending in '--' (cut off) or '...' (trailing off) keeps the mark on the line of its last word; a line
+    starting with '--' must follow one that was cut off, and a speaker cannot pick up their own cut off line
+    Merged inputs form one script with continuous scene numbering; only the first file has title lines
+    The multicam style uppercases action, underlines entrances and exits, and warns when a scene comes before the
+    first act or cold open
+    Review steps through warnings and TODOs; items marked resolved are kept in '<input>.notes.json' and stay resolved
+    while their message and scene are unchanged; builds with the 'tui' feature review full screen with single keys
+    Status summarizes sources, recorded builds (stale when older than a source), warnings, TODOs and notes,
+    and counts scenes added (unlocked) or omitted since scene numbers were last locked
+    Completions prints character names, locations and transitions as JSON for editor plugins, most used first
+    Cast lists characters introduced in capitals in action who never speak, and cues that speak only once
+    Trelby scripts ('.trelby') are read directly as input; import writes them out as editable source, and cue
+    extensions or headings this format cannot express are reported as warnings
+    Fountain and Highland scripts ('.fountain', '.spmd', '.highland' saved as text) are read the same way; '[[notes]]'
+    become notes, boneyard is dropped, '{{{{include: file}}}}' is followed, and dual dialogue or centered text become warnings
+    Screenplay PDFs are imported experimentally: the text is classified by its indentation, so styles and dual dialogue
+    are lost, warnings count printed lines from the first page, and scanned or encrypted PDFs cannot be read
+    Spell checks action and dialogue; cue names and scene locations are always accepted, as are numbers and ALL CAPS
+    PDF bookmarks list the title, every act and every scene heading
+    The daemon keeps each script's rendered scenes, so a 'build' after an edit only renders the scenes that changed
+    Scene editing commands lock every scene number in the source first, deleted scenes become 'omitted'
     Any segment may be continued on a new line using a backslash '\' character
     Empty lines may be placed anywhere for readability, as they will be ignored"#);
 
@@ -180,35 +784,932 @@ fn cmd_version() -> ExitCode {
     0.into()
 }
 
+fn print_warnings(input: &str, warnings: &[Diagnostic]) {
+    for warning in warnings {
+        scripts::log_diagnostic("warning", input, warning);
+    }
+}
+
+// parse errors are listed one by one for --message-format json, everything else is a single message
+fn print_error(input: &str, context: &str, err: &HtmlError) {
+    match err {
+        HtmlError::ParseError(errors) if scripts::message_format() == MessageFormat::Json => for error in errors {
+            scripts::log_diagnostic("error", input, error);
+        }
+        err => error!("{context}: {err}"),
+    }
+}
+
+fn cmd_todos(cmd: CmdInfo) -> ExitCode {
+    let todos = match scripts::gen_todos(&cmd) {
+        Ok(todos) => todos,
+        Err(err) => {
+            print_error(&cmd.infile, "failed to read todos", &err);
+            return 2.into()
+        }
+    };
+
+    for todo in &todos {
+        println!("line {:<6} scene {:<4} {}", todo.line, todo.label, todo.text);
+    }
+    println!("{} TODO(s) found", todos.len());
+
+    if cmd.check && !todos.is_empty() {
+        return 6.into()
+    }
+
+    0.into()
+}
+
+fn cmd_cast(cmd: CmdInfo) -> ExitCode {
+    let notes = match scripts::gen_cast(&cmd) {
+        Ok(notes) => notes,
+        Err(err) => {
+            print_error(&cmd.infile, "failed to read cast", &err);
+            return 2.into()
+        }
+    };
+
+    for note in &notes {
+        let issue = match note.issue {
+            CastIssue::Silent => "never speaks",
+            CastIssue::SpeaksOnce => "speaks once",
+        };
+        println!("line {:<6} scene {:<4} {:<16} {issue}", note.line, note.scene, note.name);
+    }
+    println!("{} cast note(s) found", notes.len());
+
+    0.into()
+}
+
+fn cmd_import(cmd: CmdInfo) -> ExitCode {
+    progress!("Importing script...\t");
+
+    match scripts::gen_import(&cmd) {
+        Ok(warnings) => {
+            info!("complete");
+            print_warnings(&cmd.infile, &warnings);
+        }
+        Err(err) => {
+            print_error(&cmd.infile, "failed to import script", &err);
+            return 2.into();
+        }
+    }
+
+    0.into()
+}
+
+fn cmd_spell(cmd: CmdInfo, dict: String, words: Option<String>) -> ExitCode {
+    let misspellings = match scripts::gen_spelling(&cmd, &dict, words.as_deref()) {
+        Ok(misspellings) => misspellings,
+        Err(err) => {
+            print_error(&cmd.infile, "failed to check spelling", &err);
+            return 2.into()
+        }
+    };
+
+    for m in &misspellings {
+        match &m.suggestion {
+            Some(suggestion) => println!("line {:<6} scene {:<4} {} (did you mean '{suggestion}'?)", m.line, m.scene, m.word),
+            None => println!("line {:<6} scene {:<4} {}", m.line, m.scene, m.word),
+        }
+    }
+    println!("{} misspelling(s) found", misspellings.len());
+
+    if cmd.check && !misspellings.is_empty() {
+        return 6.into()
+    }
+
+    0.into()
+}
+
+fn cmd_adr(cmd: CmdInfo) -> ExitCode {
+    let cues = match scripts::gen_adr(&cmd) {
+        Ok(cues) => cues,
+        Err(err) => {
+            print_error(&cmd.infile, "failed to read dialogue", &err);
+            return 2.into()
+        }
+    };
+
+    if !cmd.outfile.is_empty() {
+        if let Err(err) = std::fs::write(&cmd.outfile, scripts::dialogue_tsv(&cues)) {
+            error!("failed to write ADR report: {err}");
+            return 2.into()
+        }
+    }
+    for cue in &cues {
+        println!("cue {:<5} scene {:<4} {:<16} {}", cue.number, cue.scene, cue.name, cue.text);
+    }
+    println!("{} ADR line(s) found", cues.len());
+
+    0.into()
+}
+
+fn cmd_completions(cmd: CmdInfo) -> ExitCode {
+    let json = match scripts::gen_completions(&cmd) {
+        Ok(json) => json,
+        Err(err) => {
+            print_error(&cmd.infile, "failed to read completions", &err);
+            return 2.into()
+        }
+    };
+
+    if cmd.outfile.is_empty() {
+        println!("{json}");
+    } else if let Err(err) = std::fs::write(&cmd.outfile, json) {
+        error!("failed to write completions: {err}");
+        return 2.into()
+    }
+
+    0.into()
+}
+
+fn cmd_status(cmd: CmdInfo) -> ExitCode {
+    let status = match scripts::gen_status(&cmd) {
+        Ok(status) => status,
+        Err(err) => {
+            print_error(&cmd.infile, "failed to read project status", &err);
+            return 2.into()
+        }
+    };
+    let now = SystemTime::now();
+    let age = |time: Option<SystemTime>| time
+        .map(|t| scripts::format_age(now.duration_since(t).unwrap_or_default()))
+        .unwrap_or_else(|| "missing".to_string());
+
+    println!("{}", status.title);
+    println!("\nSources:");
+    for source in &status.sources {
+        println!("    {:<40} {:>6} lines   modified {}", source.path, source.lines, age(source.modified));
+    }
+    println!("\nOutputs:");
+    if status.outputs.is_empty() {
+        println!("    no recorded builds");
+    }
+    for output in &status.outputs {
+        println!("    {:<40} built {:<12}{}", output.path, age(output.modified), if output.stale { " (stale)" } else { "" });
+    }
+
+    let scenes = status.scenes;
+    println!("\nScenes:   {} ({} pages)", scenes.scenes, status.pages);
+    if scenes.frozen > 0 || scenes.omitted > 0 {
+        println!("          {} added, {} omitted since scene numbers were locked", scenes.added, scenes.omitted);
+    } else {
+        println!("          scene numbers not locked");
+    }
+    println!("Warnings: {}", status.warnings.len());
+    println!("TODOs:    {} open, {} resolved", status.todos, status.resolved);
+    println!("Notes:    {}", status.notes);
+
+    0.into()
+}
+
+fn cmd_breakdown(cmd: CmdInfo) -> ExitCode {
+    match scripts::gen_breakdown(&cmd) {
+        Ok(warnings) => {
+            print_warnings(&cmd.infile, &warnings);
+            open_output(&cmd)
+        }
+        Err(err) => {
+            print_error(&cmd.infile, "failed to generate breakdown", &err);
+            2.into()
+        }
+    }
+}
+
+fn cmd_callsheet(cmd: CmdInfo, date: String, scenes: Vec<String>) -> ExitCode {
+    match scripts::gen_callsheet(&cmd, &date, &scenes) {
+        Ok(warnings) => {
+            print_warnings(&cmd.infile, &warnings);
+            open_output(&cmd)
+        }
+        Err(err) => {
+            print_error(&cmd.infile, "failed to generate call sheet", &err);
+            2.into()
+        }
+    }
+}
+
+fn cmd_outline(cmd: CmdInfo) -> ExitCode {
+    if !cmd.outfile.is_empty() {
+        return match scripts::gen_outline_file(&cmd) {
+            Ok(warnings) => {
+                print_warnings(&cmd.infile, &warnings);
+                open_output(&cmd)
+            }
+            Err(err) => {
+                print_error(&cmd.infile, "failed to generate outline", &err);
+                2.into()
+            }
+        }
+    }
+
+    let outline = match scripts::gen_outline(&cmd) {
+        Ok(outline) => outline,
+        Err(err) => {
+            print_error(&cmd.infile, "failed to read outline", &err);
+            return 2.into()
+        }
+    };
+
+    let mut indent = 0;
+    for item in &outline {
+        match item {
+            OutlineItem::Section{ depth, text, .. } => {
+                println!("{:w$}{} {}", "", "#".repeat(*depth), text, w = (depth - 1) * 2);
+                indent = depth * 2;
+            }
+            OutlineItem::Synopsis{ text, .. } => println!("{:indent$}= {}", "", text),
+            OutlineItem::Scene{ label, heading, .. } => println!("{:indent$}{:<4} {}", "", label, heading),
+        }
+    }
+
+    0.into()
+}
+
+fn cmd_bench(cmd: CmdInfo) -> ExitCode {
+    const RUNS: u32 = 20;
+
+    let src = match std::fs::read_to_string(&cmd.infile) {
+        Ok(src) => src,
+        Err(err) => {
+            error!("failed to read input: {err}");
+            return 2.into()
+        }
+    };
+
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        if let Err(errors) = scripts::parse(&src) {
+            error!("failed to parse input: {}", scripts::HtmlError::ParseError(errors));
+            return 2.into()
+        }
+    }
+    let parse = start.elapsed() / RUNS;
+
+    let script = scripts::parse(&src).unwrap();
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        if let Err(err) = scripts::render_html(&script, &cmd) {
+            print_error(&cmd.infile, "failed to render html", &err);
+            return 2.into()
+        }
+    }
+    let render = start.elapsed() / RUNS;
+
+    println!("{} bytes, {} scenes, {RUNS} runs", src.len(), script.scenes.len());
+    println!("parse   {parse:?}");
+    println!("render  {render:?}");
+    println!("total   {:?}", parse + render);
+
+    0.into()
+}
+
+fn cmd_rename(cmd: CmdInfo) -> ExitCode {
+    let changed = match scripts::gen_rename(&cmd) {
+        Ok(changed) => changed,
+        Err(err) => {
+            print_error(&cmd.infile, "failed to rename", &err);
+            return 2.into()
+        }
+    };
+
+    for (file, count) in &changed {
+        println!("{count:<6} replacement(s) in {file}");
+    }
+    if changed.is_empty() {
+        println!("no replacements made");
+    } else if cmd.check {
+        println!("files left unchanged (--check)");
+    }
+
+    0.into()
+}
+
+fn cmd_scene(cmd: CmdInfo, edit: SceneEdit) -> ExitCode {
+    if let Err(err) = scripts::gen_scene_edit(&cmd, &edit) {
+        print_error(&cmd.infile, "failed to edit scenes", &err);
+        return 2.into()
+    }
+
+    match edit {
+        SceneEdit::InsertAfter{ number, file } => println!("inserted {file} after scene {number}"),
+        SceneEdit::Delete{ number } => println!("scene {number} marked as omitted"),
+    }
+
+    0.into()
+}
+
+fn cmd_daemon(cmd: CmdInfo, address: String) -> ExitCode {
+    info!("Listening on {address}");
+
+    if let Err(err) = scripts::run_daemon(&cmd, &address) {
+        error!("failed to run daemon: {err}");
+        return 2.into()
+    }
+
+    0.into()
+}
+
+fn cmd_diff(cmd: CmdInfo, old: String) -> ExitCode {
+    let changes = match scripts::gen_diff(&old, &cmd.infile) {
+        Ok(changes) => changes,
+        Err(err) => {
+            print_error(&cmd.infile, "failed to compare drafts", &err);
+            return 2.into()
+        }
+    };
+
+    if cmd.json {
+        println!("{}", serde_json::to_string_pretty(&changes).unwrap());
+        return 0.into()
+    }
+
+    for change in &changes {
+        match change {
+            Change::SceneAdded{ scene, line, heading } => println!("+ scene {scene:<4} line {line:<6} {heading}"),
+            Change::SceneRemoved{ scene, line, heading } => println!("- scene {scene:<4} line {line:<6} {heading}"),
+            Change::Added{ scene, line, text } => println!("+       {scene:<4} line {line:<6} {text}"),
+            Change::Removed{ scene, line, text } => println!("-       {scene:<4} line {line:<6} {text}"),
+            Change::Changed{ scene, line, old, new } => println!("~       {scene:<4} line {line:<6} {old}\n{:29}-> {new}", ""),
+        }
+    }
+    println!("{} change(s) found", changes.len());
+
+    0.into()
+}
+
+fn cmd_distribute(cmd: CmdInfo, recipients: Vec<String>) -> ExitCode {
+    let script = match scripts::read_script(&cmd) {
+        Ok(script) => script,
+        Err(err) => {
+            print_error(&cmd.infile, "failed to read script", &err);
+            return 2.into()
+        }
+    };
+    print_warnings(&cmd.infile, &script.warnings);
+
+    let mut failed = 0;
+    for name in recipients {
+        let mut copy = cmd.clone();
+        copy.outfile = scripts::recipient_output(&cmd.outfile, &name);
+        copy.watermark = Some(name);
+
+        if cmd.dry_run {
+            match scripts::dry_run(&script, &copy) {
+                Ok(outputs) => print_planned(&outputs),
+                Err(err) => {
+                    error!("failed to generate {}: {err}", copy.outfile);
+                    failed += 1;
+                }
+            }
+            continue
+        }
+
+        match scripts::render_pdf(&script, &copy) {
+            Ok(code) if code.success() => info!("{:<32} -> {}", copy.watermark.unwrap_or_default(), copy.outfile),
+            Ok(code) => {
+                error!("failed to generate {}: webkit exited with {code}", copy.outfile);
+                failed += 1;
+            }
+            Err(err) => {
+                error!("failed to generate {}: {err}", copy.outfile);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        error!("{failed} recipient(s) failed");
+        return 4.into()
+    }
+    0.into()
+}
+
+fn cmd_batch(cmd: CmdInfo, inputs: Vec<String>) -> ExitCode {
+    let dir = Path::new(&cmd.outfile);
+    if !cmd.dry_run {
+        if let Err(err) = std::fs::create_dir_all(dir) {
+            error!("failed to create output directory: {err}");
+            return 2.into()
+        }
+    }
+
+    let storage = scripts::storage(cmd.state);
+    let state_path = dir.join(scripts::STATE_FILE);
+    let mut state = if cmd.resume { BuildState::load(&*storage, &state_path) } else { BuildState::default() };
+    let mut failed = 0;
+    let total = inputs.len();
+    let human = cmd.progress == Progress::Human;
+
+    for (n, infile) in inputs.into_iter().enumerate() {
+        let job = format!("[{}/{total}]", n + 1);
+        let mut file = cmd.clone();
+        file.file_root = match file_root(&infile) {
+            Some(root) => root.to_string(),
+            None => {
+                error!("expected '.txt' file as input: {infile}");
+                failed += 1;
+                continue
+            }
+        };
+        let stem = Path::new(&infile).file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        file.outfile = dir.join(format!("{stem}.pdf")).display().to_string();
+        if Path::new(&file.outfile) == Path::new(&infile) {
+            error!("output file would overwrite the input file: {infile}");
+            failed += 1;
+            continue
+        }
+        file.infile = infile;
+
+        let key = match scripts::build_key(&file) {
+            Ok(key) => key,
+            Err(err) => {
+                error!("failed to read {}: {err}", file.infile);
+                failed += 1;
+                continue
+            }
+        };
+        if cmd.resume && state.is_current(&file.outfile, &key) {
+            scripts::report_done(&file, &file.infile, &file.outfile);
+            if human {
+                info!("{job} {:<32} up to date", file.infile);
+            }
+            continue
+        }
+
+        if cmd.dry_run {
+            let planned = scripts::read_script(&file).and_then(|script| {
+                print_warnings(&file.infile, &script.warnings);
+                scripts::dry_run(&script, &file)
+            });
+            match planned {
+                Ok(outputs) => print_planned(&outputs),
+                Err(err) => {
+                    print_error(&file.infile, &format!("failed to build {}", file.infile), &err);
+                    failed += 1;
+                }
+            }
+            continue
+        }
+
+        scripts::set_progress_label(&format!("{job} {} ", file.infile));
+        let built = if file.engine == Engine::Native {
+            scripts::gen_native(&file)
+                .map(|warnings| print_warnings(&file.infile, &warnings))
+                .map_err(|err| err.to_string())
+        } else {
+            scripts::gen_html(&file)
+                .map_err(|err| err.to_string())
+                .and_then(|warnings| {
+                    print_warnings(&file.infile, &warnings);
+                    scripts::gen_pdf(&file).map_err(|err| err.to_string())
+                })
+                .and_then(|code| if code.success() { Ok(()) } else { Err(format!("webkit exited with {code}")) })
+        };
+
+        match built {
+            Ok(()) => {
+                scripts::report_done(&file, &file.infile, &file.outfile);
+                if human {
+                    info!("{job} {:<32} -> {}", file.infile, file.outfile);
+                }
+                state.record(&file.outfile, key);
+                if let Err(err) = state.save(&*storage, &state_path) {
+                    warn!("failed to record build state: {err}");
+                }
+            }
+            Err(err) => {
+                scripts::report_error(&file, "build", &err);
+                error!("failed to build {}: {err}", file.infile);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        error!("{failed} build(s) failed, rerun with --resume to skip completed outputs");
+        return 4.into()
+    }
+
+    0.into()
+}
+
+struct Review {
+    infile: String,
+    items: Vec<ReviewItem>,
+    index: usize,
+    sidecar: Sidecar,
+    storage: Box<dyn scripts::Storage>,
+    path: String,
+}
+
+impl Review {
+    fn item(&self) -> &ReviewItem {
+        &self.items[self.index]
+    }
+
+    fn file(&self) -> &str {
+        self.item().file.as_deref().unwrap_or(&self.infile)
+    }
+
+    fn title(&self) -> String {
+        let item = self.item();
+        let resolved = if self.sidecar.is_resolved(item) { " (resolved)" } else { "" };
+        format!("[{}/{}] {} {} line {}{resolved}: {}", self.index + 1, self.items.len(), item.kind.to_uppercase(), self.file(), item.line, item.message)
+    }
+
+    fn next(&mut self) {
+        self.index = (self.index + 1).min(self.items.len() - 1);
+    }
+
+    fn previous(&mut self) {
+        self.index = self.index.saturating_sub(1);
+    }
+
+    fn resolve(&mut self) -> Result<(), HtmlError> {
+        if !self.sidecar.is_resolved(self.item()) {
+            self.sidecar.resolved.push(self.item().clone());
+            self.sidecar.save(&*self.storage, &self.path)?;
+        }
+        self.next();
+        Ok(())
+    }
+
+    fn left(&self) -> usize {
+        self.items.iter().filter(|i| !self.sidecar.is_resolved(i)).count()
+    }
+}
+
+
+fn review_prompt(review: &mut Review) -> Result<(), HtmlError> {
+    let stdin = std::io::stdin();
+    loop {
+        println!("\n{}", review.title());
+        for (line, text) in scripts::context(review.file(), review.item().line, 2) {
+            let marker = if line == review.item().line { ">" } else { " " };
+            println!("{marker} {line:<6} {text}");
+        }
+        print!("[n]ext [p]revious [r]esolve [q]uit > ");
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+
+        let mut key = String::new();
+        if stdin.read_line(&mut key).map_or(true, |n| n == 0) {
+            return Ok(())
+        }
+        match key.trim() {
+            "" | "n" => review.next(),
+            "p" => review.previous(),
+            "r" => review.resolve()?,
+            "q" => return Ok(()),
+            other => println!("unknown key: {other}"),
+        }
+    }
+}
+
+
+// a full screen view that takes single keys, the terminal is restored however the loop ends
+#[cfg(feature = "tui")]
+fn review_screen(review: &mut Review) -> Result<(), HtmlError> {
+    use crossterm::{
+        cursor::{ Hide, MoveTo, Show },
+        event::{ read, Event, KeyCode, KeyEventKind },
+        execute, queue,
+        style::{ Print, Stylize },
+        terminal::{ disable_raw_mode, enable_raw_mode, size, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen },
+    };
+
+    let mut out = std::io::stdout();
+    enable_raw_mode()?;
+    execute!(out, EnterAlternateScreen, Hide)?;
+
+    let result = (|| -> Result<(), HtmlError> {
+        loop {
+            let (width, height) = size()?;
+            let radius = (height as usize).saturating_sub(4) / 2;
+            queue!(out, Clear(ClearType::All), MoveTo(0, 0), Print(review.title().chars().take(width as usize).collect::<String>().bold()))?;
+            for (row, (line, text)) in scripts::context(review.file(), review.item().line, radius).into_iter().enumerate() {
+                let text: String = format!("{line:<6} {text}").chars().take((width as usize).saturating_sub(2)).collect();
+                queue!(out, MoveTo(0, row as u16 + 2))?;
+                if line == review.item().line {
+                    queue!(out, Print("> "), Print(text.reverse()))?;
+                } else {
+                    queue!(out, Print("  "), Print(text))?;
+                }
+            }
+            queue!(out, MoveTo(0, height.saturating_sub(1)), Print("n/→ next  p/← previous  r resolve  q quit".dim()))?;
+            std::io::Write::flush(&mut out)?;
+
+            let Event::Key(key) = read()? else {
+                continue
+            };
+            if key.kind != KeyEventKind::Press {
+                continue
+            }
+            match key.code {
+                KeyCode::Char('n')|KeyCode::Right|KeyCode::Down|KeyCode::Enter => review.next(),
+                KeyCode::Char('p')|KeyCode::Left|KeyCode::Up => review.previous(),
+                KeyCode::Char('r') => review.resolve()?,
+                KeyCode::Char('q')|KeyCode::Esc => return Ok(()),
+                _ => (),
+            }
+        }
+    })();
+
+    execute!(out, Show, LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+    result
+}
+
+
+fn cmd_review(cmd: CmdInfo) -> ExitCode {
+    let storage = scripts::storage(cmd.state);
+    let path = Sidecar::path(&cmd);
+    let sidecar = Sidecar::load(&*storage, &path);
+    let items: Vec<ReviewItem> = match scripts::review_items(&cmd) {
+        Ok(items) => items.into_iter().filter(|i| !sidecar.is_resolved(i)).collect(),
+        Err(err) => {
+            print_error(&cmd.infile, "failed to read review items", &err);
+            return 2.into()
+        }
+    };
+    if items.is_empty() {
+        println!("nothing to review");
+        return 0.into()
+    }
+
+    let mut review = Review{ infile: cmd.infile.clone(), items, index: 0, sidecar, storage, path };
+    #[cfg(feature = "tui")]
+    let result = if std::io::IsTerminal::is_terminal(&std::io::stdin()) { review_screen(&mut review) } else { review_prompt(&mut review) };
+    #[cfg(not(feature = "tui"))]
+    let result = review_prompt(&mut review);
+
+    if let Err(err) = result {
+        error!("failed to review {}: {err}", cmd.infile);
+        return 2.into()
+    }
+    println!("{} item(s) left to review", review.left());
+    0.into()
+}
+
+fn cmd_emit(cmd: CmdInfo) -> ExitCode {
+    let label = cmd.emit.format().label;
+    progress!("Generating {label}...\t");
+
+    match scripts::gen_output(&cmd) {
+        Ok(warnings) => {
+            info!("complete");
+            print_warnings(&cmd.infile, &warnings);
+        }
+        Err(err) => {
+            print_error(&cmd.infile, &format!("failed to generate {label}"), &err);
+            return 2.into();
+        }
+    }
+
+    0.into()
+}
+
+fn cmd_split(mut cmd: CmdInfo, dir: String) -> ExitCode {
+    let script = match scripts::read_script(&cmd) {
+        Ok(script) => script,
+        Err(err) => {
+            print_error(&cmd.infile, "failed to read script", &err);
+            return 2.into()
+        }
+    };
+    print_warnings(&cmd.infile, &script.warnings);
+    if let Err(err) = std::fs::create_dir_all(&dir) {
+        error!("failed to create output directory: {err}");
+        return 2.into()
+    }
+    if cmd.title_page == TitlePage::Auto {
+        cmd.title_page = TitlePage::Never;
+    }
+
+    let html = cmd.emit == Emit::Html;
+    for (name, scene) in script.split_scenes() {
+        cmd.outfile = Path::new(&dir).join(format!("{name}.{}", if html { "html" } else { "pdf" })).display().to_string();
+
+        let result = if html {
+            scripts::render_html(&scene, &cmd)
+                .and_then(|page| std::fs::write(&cmd.outfile, page).map_err(Into::into))
+                .map_err(|err| err.to_string())
+        } else {
+            scripts::render_pdf(&scene, &cmd)
+                .map_err(|err| err.to_string())
+                .and_then(|code| if code.success() { Ok(()) } else { Err(format!("webkit exited with {code}")) })
+        };
+        match result {
+            Ok(()) => println!("{}", cmd.outfile),
+            Err(err) => {
+                error!("failed to write {}: {err}", cmd.outfile);
+                return 4.into()
+            }
+        }
+    }
+
+    0.into()
+}
+
+fn cmd_emit_preview(mut cmd: CmdInfo) -> ExitCode {
+    progress!("Generating preview...\t");
+    cmd.html = cmd.outfile.clone();
+
+    match scripts::gen_html(&cmd) {
+        Ok(warnings) => {
+            info!("complete");
+            print_warnings(&cmd.infile, &warnings);
+        }
+        Err(err) => {
+            print_error(&cmd.infile, "failed to generate preview", &err);
+            return 2.into();
+        }
+    }
+
+    open_output(&cmd)
+}
+
+fn cmd_pages(cmd: CmdInfo) -> ExitCode {
+    let script = match scripts::read_script(&cmd) {
+        Ok(script) => script,
+        Err(err) => {
+            print_error(&cmd.infile, "failed to read script", &err);
+            return 2.into()
+        }
+    };
+    print_warnings(&cmd.infile, &script.warnings);
+    println!("{} page(s)", scripts::page_count(&script, &cmd));
+
+    0.into()
+}
+
+fn print_planned(outputs: &[PlannedOutput]) {
+    for output in outputs {
+        let size = output.bytes.map(|b| format!("{b} bytes")).into_iter();
+        let pages = output.pages.map(|p| format!("{p} page(s)")).into_iter();
+        println!("would write {:<40} {}", output.path, size.chain(pages).collect::<Vec<_>>().join(", "));
+    }
+}
+
+fn cmd_dry_run(cmd: CmdInfo) -> ExitCode {
+    let script = match scripts::read_script(&cmd) {
+        Ok(script) => script,
+        Err(err) => {
+            print_error(&cmd.infile, "failed to read script", &err);
+            return 2.into()
+        }
+    };
+    print_warnings(&cmd.infile, &script.warnings);
+
+    match scripts::dry_run(&script, &cmd) {
+        Ok(outputs) => print_planned(&outputs),
+        Err(err) => {
+            print_error(&cmd.infile, "failed to render script", &err);
+            return 2.into()
+        }
+    }
+
+    0.into()
+}
+
 fn cmd_convert(cmd: CmdInfo) -> ExitCode {
-    print!("Generating html...\t");
+    if cmd.pages {
+        return cmd_pages(cmd)
+    }
+    if cmd.dry_run {
+        return cmd_dry_run(cmd)
+    }
+    if !matches!(cmd.emit, Emit::Pdf | Emit::Html) {
+        return cmd_emit(cmd)
+    }
+    if let Some(dir) = cmd.split_scenes.clone() {
+        return cmd_split(cmd, dir)
+    }
+    if cmd.emit == Emit::Html {
+        return cmd_emit_preview(cmd)
+    }
+
+    let human = cmd.progress == Progress::Human;
+    let storage = scripts::storage(cmd.state);
+    let state_path = scripts::state_path(&cmd.outfile);
+    let mut state = BuildState::load(&*storage, &state_path);
+    let key = scripts::build_key(&cmd).ok();
 
-    if let Err(err) = scripts::gen_html(&cmd) {
-        eprintln!("ERROR: falied to generate html: {err}");
-        return 2.into();
+    if !cmd.force && key.as_deref().is_some_and(|k| state.is_current(&cmd.outfile, k)) {
+        scripts::report(&cmd, "done", 100, None);
+        if human {
+            info!("{} is up to date", cmd.outfile);
+        }
+        return open_output(&cmd)
     }
 
-    println!("complete");
-    println!("Invoking webkit:\n");
+    if cmd.engine == Engine::Native {
+        if human {
+            progress!("Typesetting pdf...\t");
+        }
+        match scripts::gen_native(&cmd) {
+            Ok(warnings) => {
+                if human {
+                    info!("complete");
+                }
+                print_warnings(&cmd.infile, &warnings);
+            }
+            Err(err) => {
+                scripts::report_error(&cmd, "render", &err.to_string());
+                print_error(&cmd.infile, "failed to generate pdf", &err);
+                return 2.into()
+            }
+        }
+        if let Some(key) = key {
+            state.record(&cmd.outfile, key);
+            if let Err(err) = state.save(&*storage, &state_path) {
+                warn!("failed to record build state: {err}");
+            }
+        }
+        scripts::report(&cmd, "done", 100, None);
+        return open_output(&cmd)
+    }
+
+    if human {
+        progress!("Generating html...\t");
+    }
+
+    match scripts::gen_html(&cmd) {
+        Ok(warnings) => {
+            if human {
+                info!("complete");
+            }
+            print_warnings(&cmd.infile, &warnings);
+        }
+        Err(err) => {
+            scripts::report_error(&cmd, "render", &err.to_string());
+            print_error(&cmd.infile, "failed to generate html", &err);
+            return 2.into();
+        }
+    }
+    if human {
+        info!("Invoking webkit:\n");
+    }
+    scripts::report(&cmd, "pdf", 60, None);
 
     match  scripts::gen_pdf(&cmd) {
+        Err(err @ HtmlError::EncryptError(_)) => {
+            scripts::report_error(&cmd, "encrypt", &err.to_string());
+            error!("{err}");
+            return 4.into()
+        }
         Err(err) => {
-            eprintln!("ERROR: falied to invoke webkit: {err}");
+            scripts::report_error(&cmd, "pdf", &err.to_string());
+            error!("failed to invoke webkit: {err}");
             return 3.into()
         }
         Ok(code) => if code.success() {
-            println!("\nConversion completed successfully");
+            if let Some(key) = key {
+                state.record(&cmd.outfile, key);
+                if let Err(err) = state.save(&*storage, &state_path) {
+                    warn!("failed to record build state: {err}");
+                }
+            }
+            scripts::report(&cmd, "done", 100, None);
+            if human {
+                info!("\nConversion completed successfully");
+            }
         } else {
-            eprintln!("ERROR: falied to generate pdf: {code}");
+            scripts::report_error(&cmd, "pdf", &code.to_string());
+            error!("failed to generate pdf: {code}");
             return 4.into()
         }
     }
 
-    if !cmd.nopen {
-        if let Err(err) = open::that(cmd.outfile) {
-            eprintln!("ERROR: falied to open pdf in default app: {err}");
-            return 5.into()
+    open_output(&cmd)
+}
+
+fn open_output(cmd: &CmdInfo) -> ExitCode {
+    if cmd.nopen {
+        return 0.into()
+    }
+
+    // the viewer may carry its own arguments, the output is passed last
+    if let Some(viewer) = &cmd.viewer {
+        let mut parts = viewer.split_whitespace();
+        let program = parts.next().unwrap_or_default();
+        debug!("opening {} with {viewer}", cmd.outfile);
+        match std::process::Command::new(program).args(parts).arg(&cmd.outfile).status() {
+            Ok(status) if status.success() => (),
+            Ok(status) => {
+                error!("viewer {program} exited with {status}");
+                return 5.into()
+            }
+            Err(err) => {
+                error!("failed to open output in {program}: {err}");
+                return 5.into()
+            }
         }
+    } else if let Err(err) = open::that(&cmd.outfile) {
+        error!("failed to open output in default app: {err}");
+        return 5.into()
     }
 
     0.into()
@@ -219,7 +1720,7 @@ fn main() -> ExitCode {
     let args: Vec<_> = std::env::args().collect();
 
     if args.len() == 1 {
-        eprintln!("ERROR: no arguments found");
+        error!("no arguments found");
         return cmd_help()
     }
 
@@ -229,10 +1730,28 @@ fn main() -> ExitCode {
                 Command::Help => cmd_help(),
                 Command::Version => cmd_version(),
                 Command::Convert(c) => cmd_convert(c),
+                Command::Todos(c) => cmd_todos(c),
+                Command::Outline(c) => cmd_outline(c),
+                Command::Bench(c) => cmd_bench(c),
+                Command::Rename(c) => cmd_rename(c),
+                Command::Scene(c, e) => cmd_scene(c, e),
+                Command::Daemon(c, a) => cmd_daemon(c, a),
+                Command::Diff(c, o) => cmd_diff(c, o),
+                Command::Batch(c, i) => cmd_batch(c, i),
+                Command::Distribute(c, r) => cmd_distribute(c, r),
+                Command::Review(c) => cmd_review(c),
+                Command::Adr(c) => cmd_adr(c),
+                Command::Status(c) => cmd_status(c),
+                Command::Completions(c) => cmd_completions(c),
+                Command::Spell(c, d, w) => cmd_spell(c, d, w),
+                Command::Cast(c) => cmd_cast(c),
+                Command::Import(c) => cmd_import(c),
+                Command::Breakdown(c) => cmd_breakdown(c),
+                Command::CallSheet(c, d, s) => cmd_callsheet(c, d, s),
             }
         }
         Err(err) => {
-            eprintln!("{err}");
+            error!("{err}");
             1.into()
         }
     }
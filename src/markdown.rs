@@ -0,0 +1,113 @@
+use std::fmt::Write;
+use crate::{
+    ast::*,
+    html::HtmlError,
+};
+
+
+// bold, italic and escapes are already markdown, only text that would start a heading or quote needs care;
+// underline has no markdown form and reads as emphasis
+fn paragraph(text: &str) -> String {
+    match text.chars().next() {
+        Some('#'|'>') => format!("\\{text}"),
+        _ => text.to_string(),
+    }
+}
+
+
+fn quote<S: AsRef<str>>(lines: &[S]) -> String {
+    lines.iter().map(|l| format!("> {}", l.as_ref())).collect::<Vec<_>>().join("  \n")
+}
+
+
+// notes, TODOs and beats stay in the source as comments, visible in review tools but not when rendered
+fn render_element(kind: &Kind) -> String {
+    let comment = |label: &str, text: &str| format!("<!-- {label}: {} -->", text.replace("--", "- -"));
+
+    match kind {
+        Kind::Montage => "BEGIN MONTAGE:".to_string(),
+        Kind::MonEnd => "END MONTAGE.".to_string(),
+        Kind::PageBreak => "---".to_string(),
+        Kind::End => "FADE OUT.\n\n**THE END**".to_string(),
+        Kind::Todo{ text } => comment("TODO", text),
+        Kind::Note{ text } => comment("NOTE", text),
+        Kind::Section{ text, .. } => comment("BEAT", text),
+        Kind::Synopsis{ text } => comment("SYNOPSIS", text),
+        Kind::Direct{ text } => paragraph(text),
+        Kind::Parens{ text } => quote(&[format!("*({text})*")]),
+        Kind::Speech{ text } => quote(&[text]),
+        Kind::Lyrics{ text } => quote(&text.split(" / ").map(|l| format!("*{l}*")).collect::<Vec<_>>()),
+        Kind::Subhead{ text } => format!("### {}", text.to_uppercase()),
+        Kind::Trans{ text } => paragraph(&text.to_uppercase()),
+        Kind::Chyron{ text } => format!("CHYRON: {text}"),
+        Kind::Sfx{ text } => format!("SFX: {}", text.to_uppercase()),
+        Kind::Music{ text } => format!("MUSIC: {}", text.to_uppercase()),
+        Kind::Header{ text } => format!("**{text}**"),
+        Kind::ActTag{ text }|Kind::Act{ text }|Kind::Episode{ text } => format!("# {text}"),
+        Kind::ActEnd{ text } => format!("**END OF {text}**"),
+        Kind::ComicPage{ number, text } if text.is_empty() => format!("### PAGE {number}"),
+        Kind::ComicPage{ number, text } => format!("### PAGE {number} - {}", text.to_uppercase()),
+        Kind::Panel{ number, text } if text.is_empty() => format!("**PANEL {number}**"),
+        Kind::Panel{ number, text } => format!("**PANEL {number}**\n\n{}", paragraph(text)),
+        Kind::Caption{ text } => format!("**CAPTION**\n{}", quote(&[text])),
+        Kind::Dialogue(dialogue) => {
+            let lines: Vec<String> = dialogue.lines.iter()
+                .map(|line| match line {
+                    DialogueLine::Parens(text) => format!("*{text}*"),
+                    DialogueLine::Speech(text) => text.clone(),
+                })
+                .collect();
+            format!("**{}**\n{}", dialogue.name.to_uppercase(), quote(&lines))
+        }
+    }
+}
+
+
+pub fn markdown(script: &Script) -> Result<String, HtmlError> {
+    let mut result = format!("# {}\n", script.title);
+    if !script.subtitle.is_empty() {
+        write!(result, "\n*{}*\n", script.subtitle)?;
+    }
+    if let Some(author) = &script.author {
+        write!(result, "\nby {author}\n")?;
+    }
+
+    for e in &script.prelude {
+        write!(result, "\n{}\n", render_element(&e.kind))?;
+    }
+    for scene in &script.scenes {
+        let label = script.scene_label(&scene.display_number());
+        if scene.omitted {
+            write!(result, "\n## {label}. OMITTED\n")?;
+        } else if scene.number == 0 {
+            write!(result, "\n## {}\n", scene.heading.to_uppercase())?;
+        } else {
+            write!(result, "\n## {label}. {}\n", scene.heading.to_uppercase())?;
+        }
+        for e in &scene.elements {
+            write!(result, "\n{}\n", render_element(&e.kind))?;
+        }
+    }
+
+    Ok(result)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn document() {
+        let script = parse("title\nsubtitle\nscene INT. HALL - DAY\ndirect # signs everywhere.\nnote fix -- later\nALEX: (quietly) It's **mine**.\nend").expect("parse failed");
+
+        assert_eq!(markdown(&script).expect("render failed"), "# title\n\n*subtitle*\n\n\
+                                                               ## 1. INT. HALL - DAY\n\n\
+                                                               \\# signs everywhere.\n\n\
+                                                               <!-- NOTE: fix - - later -->\n\n\
+                                                               **ALEX**\n> *(quietly)*  \n> It's **mine**.\n\n\
+                                                               FADE OUT.\n\n**THE END**\n");
+    }
+}
@@ -0,0 +1,147 @@
+use std::{
+    fs,
+    fmt::Write,
+};
+use crate::{
+    ast::*,
+    layout::*,
+    parse::*,
+    pdf::gen_pdf,
+    CmdInfo,
+    HtmlError,
+};
+
+
+const OUTLINE_CSS: &str = "\
+body { font-family: 'Courier New', monospace; font-size: 12pt; margin: 0 1in; }
+h2, h3, h4 { margin: 0.3in 0 0.1in 0; }
+.outline-scene { margin: 0.12in 0 0 0; }
+.outline-synopsis { margin: 0.04in 0 0 0.5in; font-style: italic; }";
+
+
+const CARDS_CSS: &str = "\
+body { font-family: 'Courier New', monospace; font-size: 11pt; margin: 0 0.75in; }
+.sheet { page-break-after: always; }
+.card { display: inline-block; vertical-align: top; box-sizing: border-box; width: 3.4in; height: 2.9in; margin: 0 0.1in 0.2in 0; padding: 0.15in; border: 1px solid #000; overflow: hidden; }
+.card-heading { font-weight: bold; border-bottom: 1px solid #000; padding-bottom: 0.05in; }
+.card-synopsis { margin-top: 0.1in; }
+.card-pages { float: right; font-weight: normal; }";
+
+
+const CARDS_PER_SHEET: usize = 6;
+
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+
+pub fn outline_markdown(script: &Script) -> String {
+    let mut result = format!("# {}\n", script.title);
+    if !script.subtitle.is_empty() {
+        result.push_str(&format!("\n*{}*\n", script.subtitle));
+    }
+
+    for item in script.outline() {
+        match item {
+            OutlineItem::Section{ depth, text, .. } => result.push_str(&format!("\n{} {text}\n", "#".repeat(depth + 1))),
+            OutlineItem::Scene{ label, heading, .. } => result.push_str(&format!("\n{label}. {heading}\n")),
+            OutlineItem::Synopsis{ text, .. } => result.push_str(&format!("   {text}\n")),
+        }
+    }
+
+    result
+}
+
+
+pub fn outline_html(script: &Script) -> Result<String, HtmlError> {
+    let mut result = format!("<html><head><meta charset=\"utf-8\"/><style>{OUTLINE_CSS}</style></head><body>\n<h1>{}</h1>\n", escape(&script.title));
+    if !script.subtitle.is_empty() {
+        writeln!(result, "<p><i>{}</i></p>", escape(&script.subtitle))?;
+    }
+
+    for item in script.outline() {
+        match item {
+            OutlineItem::Section{ depth, text, .. } => {
+                let level = (depth + 1).min(4);
+                writeln!(result, "<h{level}>{}</h{level}>", escape(&text))?;
+            }
+            OutlineItem::Scene{ label, heading, .. } => writeln!(result, "<p class=\"outline-scene\"><b>{label}.</b> {}</p>", escape(&heading))?,
+            OutlineItem::Synopsis{ text, .. } => writeln!(result, "<p class=\"outline-synopsis\">{}</p>", escape(&text))?,
+        }
+    }
+    result.push_str("</body></html>\n");
+
+    Ok(result)
+}
+
+
+pub fn cards_html(script: &Script) -> Result<String, HtmlError> {
+    let mut result = format!("<html><head><meta charset=\"utf-8\"/><style>{CARDS_CSS}</style></head><body>\n");
+
+    for sheet in script.scenes.chunks(CARDS_PER_SHEET) {
+        result.push_str("<div class=\"sheet\">\n");
+        for scene in sheet {
+            let synopsis: Vec<String> = scene.elements.iter()
+                .filter_map(|e| match &e.kind {
+                    Kind::Synopsis{ text } => Some(escape(text)),
+                    _ => None,
+                })
+                .collect();
+
+            writeln!(result, "<div class=\"card\"><div class=\"card-heading\">{}. {}<span class=\"card-pages\">{}</span></div>\
+                              <div class=\"card-synopsis\">{}</div></div>",
+                     script.scene_label(&scene.display_number()), escape(&scene.heading), page_eighths(scene), synopsis.join("<br>"))?;
+        }
+        result.push_str("</div>\n");
+    }
+    result.push_str("</body></html>\n");
+
+    Ok(result)
+}
+
+
+// markdown for '.md' outputs, anything else goes through webkit like the script itself
+pub fn gen_outline_file(cmd: &CmdInfo) -> Result<Vec<Diagnostic>, HtmlError> {
+    let script = read_script(cmd)?;
+
+    if cmd.outfile.ends_with(".md") {
+        fs::write(&cmd.outfile, outline_markdown(&script))?;
+        return Ok(script.warnings)
+    }
+
+    fs::write(&cmd.html, outline_html(&script)?)?;
+    let status = gen_pdf(cmd)?;
+    if !status.success() {
+        return Err(std::io::Error::other(format!("webkit exited with {status}")).into())
+    }
+
+    Ok(script.warnings)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown() {
+        let script = parse("title\nsubtitle\n# Act One\nscene INT. A - DAY\n= They meet.\ndirect Hi.\nscene EXT. B - NIGHT").expect("parse failed");
+
+        assert_eq!(outline_markdown(&script), "# title\n\n*subtitle*\n\n## Act One\n\n1. INT. A - DAY\n   They meet.\n\n2. EXT. B - NIGHT\n");
+        assert!(outline_html(&script).unwrap().contains("<p class=\"outline-scene\"><b>2.</b> EXT. B - NIGHT</p>"));
+    }
+
+    #[test]
+    fn cards() {
+        let scenes: String = (1..=7).map(|i| format!("scene INT. ROOM {i} - DAY\n= Beat {i}.\ndirect Hi.\n")).collect();
+        let script = parse(&format!("title\nsubtitle\n{scenes}")).expect("parse failed");
+
+        let html = cards_html(&script).unwrap();
+        assert_eq!(html.matches("class=\"sheet\"").count(), 2);
+        assert_eq!(html.matches("class=\"card\"").count(), 7);
+        assert!(html.contains("7. INT. ROOM 7 - DAY<span class=\"card-pages\">1/8</span>"));
+        assert!(html.contains("<div class=\"card-synopsis\">Beat 7.</div>"));
+    }
+}
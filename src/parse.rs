@@ -0,0 +1,1818 @@
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    fs,
+    fmt,
+    io::{ self, BufRead },
+    ops::Range,
+    path::Path,
+    rc::Rc,
+    collections::{ HashMap, HashSet },
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{ Serialize, Deserialize };
+use crate::{
+    ast::*,
+    front::*,
+    import::is_imported,
+    layout::{ cue_base, interrupted },
+    source::{ expand_all, reads_includes },
+    typography::smarten,
+    debug,
+    verbose,
+    CmdInfo,
+    HouseStyle,
+    HtmlError,
+};
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    pub line: usize,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(line: usize, message: impl Into<String>) -> Self {
+        Self{ file: None, line, message: message.into() }
+    }
+
+    fn syntax(line: usize, expected: &str, after: &str) -> Self {
+        Self::new(line, format!("invalid syntax (expected {expected} after {after})"))
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{file} ")?;
+        }
+        write!(f, "line {} - {}", self.line, self.message)
+    }
+}
+
+
+pub(crate) fn comment_start(line: &str) -> Option<usize> {
+    let bytes = line.as_bytes();
+    let mut open = false;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            i += 2;
+            continue
+        } else if bytes[i] != b'*' {
+            i += 1;
+            continue
+        }
+
+        let run = bytes[i..].iter().take_while(|&&b| b == b'*').count();
+        let before = i.checked_sub(1).map(|j| bytes[j]);
+        let after = bytes.get(i + run).copied();
+
+        if open && before.is_some_and(|b| !b.is_ascii_whitespace()) {
+            open = false;
+        } else if after == Some(b' ') && before.is_none_or(|b| b.is_ascii_whitespace()) {
+            return Some(i)
+        } else if run <= 2 && after.is_some_and(|b| !b.is_ascii_whitespace()) {
+            open = true;
+        }
+        i += run;
+    }
+
+    None
+}
+
+
+pub(crate) fn strip_continuation(line: &str) -> Option<&str> {
+    let escapes = line.len() - line.trim_end_matches('\\').len();
+    if escapes % 2 == 1 {
+        Some(&line[..line.len() - 1])
+    } else {
+        None
+    }
+}
+
+
+pub fn trim_ignored((num, line): (usize, &str)) -> (usize, &str) {
+    if let Some(i) = comment_start(line) {
+        (num, line[..i].trim())
+    } else {
+        (num, line.trim())
+    }
+}
+
+
+pub(crate) struct Segment<'a> {
+    line: usize,
+    mode: Cow<'a, str>,
+    text: Vec<Cow<'a, str>>,
+}
+
+
+type Lines<'a> = Box<dyn Iterator<Item = (usize, Cow<'a, str>)> + 'a>;
+
+
+// lines borrowed from a source in memory stay borrowed, lines streamed from a reader are owned
+fn narrow<'a>(text: Cow<'a, str>, f: impl FnOnce(&str) -> &str) -> Cow<'a, str> {
+    match text {
+        Cow::Borrowed(text) => Cow::Borrowed(f(text)),
+        Cow::Owned(text) => Cow::Owned(f(&text).to_string()),
+    }
+}
+
+
+pub(crate) struct Segments<'a> {
+    lines: Lines<'a>,
+    read: usize,
+    term: bool,
+    tail: Option<usize>,
+    pending: Option<(usize, Vec<Cow<'a, str>>)>,
+    warnings: Vec<Diagnostic>,
+}
+
+impl<'a> Segments<'a> {
+    #[cfg(test)]
+    fn new(src: &'a str) -> Self {
+        Self::skipping(src, 0)
+    }
+
+    fn skipping(src: &'a str, skip: usize) -> Self {
+        Self::from_lines(Box::new(src.lines().map(Cow::Borrowed).enumerate().skip(skip)))
+    }
+
+    fn from_lines(lines: Lines<'a>) -> Self {
+        Self{ lines, read: 0, term: false, tail: None, pending: None, warnings: Vec::new() }
+    }
+
+    // comments are stripped and empty lines skipped, the count includes every line read
+    fn next_line(&mut self) -> Option<(usize, Cow<'a, str>)> {
+        loop {
+            let (num, line) = self.lines.next()?;
+            self.read = num + 1;
+            let line = narrow(line, |l| trim_ignored((num, l)).1);
+            if !line.is_empty() {
+                return Some((num, line))
+            }
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> (Vec<Diagnostic>, usize) {
+        let mut skipped = Vec::new();
+        while let Some((num, _)) = self.next_line() {
+            skipped.push(num + 1);
+        }
+        if let (Some(tail), Some(first), Some(last)) = (self.tail, skipped.first(), skipped.last()) {
+            self.warnings.push(Diagnostic::new(
+                tail,
+                format!("{} line(s) after the '***' tail marker are not rendered (lines {first}-{last})", skipped.len()),
+            ));
+        }
+        (self.warnings, self.read)
+    }
+
+    fn push_back(&mut self, whole: (usize, Vec<Cow<'a, str>>)) {
+        self.pending = Some(whole);
+    }
+
+    pub(crate) fn next_whole(&mut self) -> Option<(usize, Vec<Cow<'a, str>>)> {
+        if let Some(whole) = self.pending.take() { return Some(whole) }
+        if self.term { return None }
+
+        let (line, mut val) = self.next_line()?;
+
+        if val == "***" {
+            self.term = true;
+            self.tail = Some(line + 1);
+            return None
+        }
+
+        let mut text = Vec::new();
+
+        while strip_continuation(&val).is_some() {
+            text.push(narrow(val, |v| strip_continuation(v).unwrap_or(v).trim()));
+            let (next, next_val) = self.next_line()?;
+            val = next_val;
+            if val == "***" {
+                self.term = true;
+                self.tail = Some(next + 1);
+                self.warnings.push(Diagnostic::new(line + 1, "continued segment is cut short by the '***' tail marker"));
+                return Some((line + 1, text))
+            }
+        }
+
+        text.push(val);
+
+        Some((line + 1, text))
+    }
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = Segment<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (line, mut text) = self.next_whole()?;
+        let first = text.remove(0);
+
+        match first.find(char::is_whitespace) {
+            Some(split) => {
+                text.insert(0, narrow(first.clone(), |f| f[split..].trim()));
+                Some(Segment{ line, mode: narrow(first, |f| &f[..split]), text })
+            }
+            None => Some(Segment{ line, mode: first, text: Vec::new() }),
+        }
+    }
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Context {
+    title: String,
+    subtitle: String,
+    cast: HashSet<String>,
+    vars: HashMap<String, String>,
+    aliases: HashMap<String, String>,
+    keywords: HashMap<String, String>,
+    prefixes: Vec<(String, String)>,
+}
+
+impl Context {
+    pub(crate) fn new(front: &FrontMatter) -> Self {
+        let vars = front.variables.clone().into_iter().collect();
+        let cast = front.characters.iter().chain(front.aliases.keys()).map(|n| n.to_uppercase()).collect();
+        let aliases = front.aliases.iter().map(|(k, v)| (k.to_uppercase(), v.to_uppercase())).collect();
+        let keywords = front.keywords.clone().into_iter().collect();
+        let mut prefixes: Vec<(String, String)> = front.prefixes.iter().map(|(k, v)| (k.to_uppercase(), v.to_uppercase())).collect();
+        prefixes.sort_by_key(|(local, _)| std::cmp::Reverse(local.len()));
+        Self{ title: String::new(), subtitle: String::new(), cast, vars, aliases, keywords, prefixes }
+    }
+
+    pub(crate) fn keyword<'a>(&'a self, mode: &'a str) -> &'a str {
+        self.keywords.get(mode).map_or(mode, String::as_str)
+    }
+
+    // localized scene prefixes are swapped for the standard ones, so output stays INT./EXT.
+    pub(crate) fn heading(&self, text: &str) -> String {
+        for (local, standard) in &self.prefixes {
+            if let Some(rest) = text.get(local.len()..).filter(|rest| rest.starts_with(' ')) {
+                if text[..local.len()].to_uppercase() == *local {
+                    return format!("{standard}{rest}")
+                }
+            }
+        }
+        text.to_string()
+    }
+
+    fn normalize(&self, name: &str) -> String {
+        let alias = |part: &str| {
+            let trimmed = part.trim();
+            match self.aliases.get(trimmed) {
+                Some(target) => part.replace(trimmed, target),
+                None => part.to_string(),
+            }
+        };
+
+        let base = cue_base(name);
+        let mut result = String::new();
+        let mut rest = base;
+        while let Some(i) = rest.find(['&', '/']) {
+            result.push_str(&alias(&rest[..i]));
+            result.push_str(&rest[i..i + 1]);
+            rest = &rest[i + 1..];
+        }
+        result.push_str(&alias(rest));
+        result.push_str(&name[base.len()..]);
+        result
+    }
+
+    fn expand(&self, text: &str) -> String {
+        lazy_static! {
+            static ref PAT_VAR: Regex = Regex::new(r"\$([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+        }
+
+        PAT_VAR.replace_all(text, |caps: &regex::Captures| match &caps[1] {
+            "title" => self.title.clone(),
+            "subtitle" => self.subtitle.clone(),
+            name => self.vars.get(name).cloned().unwrap_or_else(|| caps[0].to_string()),
+        }).into_owned()
+    }
+}
+
+
+#[derive(Debug, Default, Clone)]
+pub struct ParseOptions {
+    pub title: Option<String>,
+    pub defines: Vec<(String, String)>,
+    pub style: Option<HouseStyle>,
+}
+
+
+enum Block {
+    Scene(String),
+    Omitted(String),
+    Element(Kind),
+    Doctype(String),
+    Episode(String),
+    Cast(Vec<String>),
+    Prologue(bool),
+    Define(String, String),
+    Alias(Vec<String>, String),
+    Tags(String, Vec<String>),
+    Restricted,
+}
+
+
+lazy_static! {
+    // a full width colon marks a CJK cue rather than a header, as those have no lowercase either
+    pub(crate) static ref PAT_HEAD: Regex = Regex::new(r"^[^\p{Ll}：]+$").unwrap();
+    static ref PAT_LOCKED: Regex = Regex::new(r"^(.*?)\s*#([0-9]+[A-Z]*)#$").unwrap();
+    static ref PAT_LABEL: Regex = Regex::new(r"^[0-9]+[A-Z]*$").unwrap();
+    static ref PAT_TAG: Regex = Regex::new(r"\{\s*([A-Za-z][A-Za-z -]*?)\s*:\s*([^{}]*?)\s*\}").unwrap();
+    static ref PAT_ADR: Regex = Regex::new(r"(?i)\s*#adr\s*$").unwrap();
+    pub(crate) static ref PAT_SCENE: Regex = Regex::new(r"(INT\.|EXT\.) \P{Ll}+ - \P{Ll}+").unwrap();
+    // CJK text separates the cue with a full width colon and no space
+    pub(crate) static ref PAT_SPEECH: Regex = Regex::new(r"^([\w.'’#&/-]+(?: [\w.'’#&/-]+)*(?: \((?:O\.S\.|V\.O\.)\))?)(?::\s+|：\s*)\S").unwrap();
+    pub(crate) static ref PAT_NAME: Regex = Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").unwrap();
+    // scripts without case (CJK, Arabic, Hebrew...) count as capitals
+    pub(crate) static ref PAT_CAPS: Regex = Regex::new(r"^\P{Ll}*[\p{Lu}\p{Lo}]\P{Ll}*$").unwrap();
+}
+
+
+pub(crate) fn split_cue(text: &str) -> Option<(&str, &str)> {
+    text.split_once([':', '：'])
+}
+
+
+pub(crate) fn dialogue_spans(content: &str) -> Result<Vec<(bool, Range<usize>)>, usize> {
+    let mut spans = Vec::new();
+    let mut push = |parens: bool, start: usize, end: usize| {
+        let text = &content[start..end];
+        let start = start + (text.len() - text.trim_start().len());
+        let end = end - (text.len() - text.trim_end().len());
+        if start < end {
+            spans.push((parens, start..end));
+        }
+    };
+
+    let mut depth = 0;
+    let mut open = 0;
+    let mut last = 0;
+
+    for (i, c) in content.char_indices() {
+        match c {
+            '(' if depth == 0 => {
+                push(false, last, i);
+                open = i;
+                depth = 1;
+            }
+            '(' => depth += 1,
+            ')' if depth == 1 => {
+                push(true, open, i + 1);
+                last = i + 1;
+                depth = 0;
+            }
+            ')' if depth > 1 => depth -= 1,
+            _ => {}
+        }
+    }
+
+    if depth > 0 {
+        return Err(open)
+    }
+    push(false, last, content.len());
+
+    Ok(spans)
+}
+
+
+fn get_block(segment: Segment, ctx: &Context) -> Result<Block, Diagnostic> {
+    let Segment{ line, mode, text } = segment;
+    let mode = ctx.expand(&mode);
+    let mode = ctx.keyword(&mode);
+    let text = ctx.expand(&text.join(" "));
+
+    let kind = match mode {
+        "montage" if  text.is_empty() => Kind::Montage,
+        "mon-end" if  text.is_empty() => Kind::MonEnd,
+        "pagebreak" if text.is_empty() => Kind::PageBreak,
+        "end"     if  text.is_empty() => Kind::End,
+        "TODO"                        => Kind::Todo{ text },
+        "note"    if !text.is_empty() => Kind::Note{ text },
+        "="       if !text.is_empty() => Kind::Synopsis{ text },
+        _ if mode.bytes().all(|b| b == b'#') && !text.is_empty() => Kind::Section{ depth: mode.len(), text },
+        "direct"  if !text.is_empty() => Kind::Direct{ text },
+        "parens"  if !text.is_empty() => Kind::Parens{ text },
+        "speech"  if !text.is_empty() => Kind::Speech{ text },
+        "lyrics"  if !text.is_empty() => Kind::Lyrics{ text },
+        "subhead" if !text.is_empty() => Kind::Subhead{ text },
+        "trans"   if !text.is_empty() => Kind::Trans{ text },
+        "chyron"  if !text.is_empty() => Kind::Chyron{ text },
+        "sfx"     if !text.is_empty() => Kind::Sfx{ text },
+        "music"   if !text.is_empty() => Kind::Music{ text },
+        "cold-open" if text.is_empty() => Kind::ActTag{ text: "COLD OPEN".to_string() },
+        "tag"     if  text.is_empty() => Kind::ActTag{ text: "TAG".to_string() },
+        "act"     if !text.is_empty() => Kind::Act{ text: format!("ACT {}", text.to_uppercase()) },
+        "episode" if !text.is_empty() => Kind::Episode{ text: text.to_uppercase() },
+        "page"                        => Kind::ComicPage{ number: 0, text },
+        "panel"                       => Kind::Panel{ number: 0, text },
+        "caption" if !text.is_empty() => Kind::Caption{ text },
+        "scene"   if !text.is_empty() && PAT_SCENE.is_match(&ctx.heading(&text)) => {
+            return Ok(Block::Scene(ctx.heading(&text).to_uppercase()))
+        }
+        "omitted" if PAT_LABEL.is_match(&text) => {
+            return Ok(Block::Omitted(text))
+        }
+        "doctype" if HouseStyle::from_name(&text).is_some() => {
+            return Ok(Block::Doctype(text))
+        }
+        "episode-number" if !text.is_empty() => {
+            return Ok(Block::Episode(text))
+        }
+        "prologue" if text.is_empty() => {
+            return Ok(Block::Prologue(true))
+        }
+        "prologue-end" if text.is_empty() => {
+            return Ok(Block::Prologue(false))
+        }
+        "restricted" if text.is_empty() => {
+            return Ok(Block::Restricted)
+        }
+        "restricted" => {
+            return Err(Diagnostic::syntax(line, "newline", "scene restriction"))
+        }
+        "prologue"|"prologue-end" => {
+            return Err(Diagnostic::syntax(line, "newline", &format!("prologue delimiter '{mode}'")))
+        }
+        "define" if text.split_once('=').is_some_and(|(k, _)| PAT_NAME.is_match(k.trim())) => {
+            let (key, value) = text.split_once('=').unwrap();
+            return Ok(Block::Define(key.trim().to_string(), value.trim().to_string()))
+        }
+        "define" => {
+            return Err(Diagnostic::syntax(line, "'NAME = VALUE'", "variable definition"))
+        }
+        "alias" if text.split_once('=').is_some_and(|(k, v)| !k.trim().is_empty() && !v.trim().is_empty()) => {
+            let (names, target) = text.split_once('=').unwrap();
+            let names = names.split(',').map(|n| n.trim().to_uppercase()).filter(|n| !n.is_empty()).collect();
+            return Ok(Block::Alias(names, target.trim().to_uppercase()))
+        }
+        "alias" => {
+            return Err(Diagnostic::syntax(line, "'NAME = CHARACTER'", "character alias"))
+        }
+        "props"|"wardrobe"|"vehicles"|"extras" if !text.is_empty() => {
+            let category = match mode {
+                "props" => "prop",
+                "vehicles" => "vehicle",
+                "extras" => "extra",
+                _ => "wardrobe",
+            };
+            return Ok(Block::Tags(category.to_string(), text.split(',').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect()))
+        }
+        "props"|"wardrobe"|"vehicles"|"extras" => {
+            return Err(Diagnostic::syntax(line, "items", "breakdown tags"))
+        }
+        "cast" if !text.is_empty() => {
+            return Ok(Block::Cast(text.split(',').map(|n| n.trim().to_uppercase()).filter(|n| !n.is_empty()).collect()))
+        }
+        "montage"|"mon-end" => {
+            return Err(Diagnostic::syntax(line, "newline", &format!("montage delimiter '{mode}'")))
+        }
+        "pagebreak" => {
+            return Err(Diagnostic::syntax(line, "newline", "page break"))
+        }
+        "end" => {
+            return Err(Diagnostic::syntax(line, "newline", "closure 'end'"))
+        }
+        "cold-open"|"tag" => {
+            return Err(Diagnostic::syntax(line, "newline", &format!("act tag '{mode}'")))
+        }
+        "doctype" => {
+            return Err(Diagnostic::new(line, format!("unknown doctype '{text}'")))
+        }
+        "episode-number" => {
+            return Err(Diagnostic::syntax(line, "episode number", "episode declaration"))
+        }
+        "cast" => {
+            return Err(Diagnostic::syntax(line, "character names", "cast declaration"))
+        }
+        "include" => {
+            return Err(Diagnostic::new(line, "'include' is only supported when reading a script file"))
+        }
+        "act" => {
+            return Err(Diagnostic::syntax(line, "act name", "act declaration"))
+        }
+        "episode" => {
+            return Err(Diagnostic::syntax(line, "episode title", "episode declaration"))
+        }
+        "direct"|"parens"|"speech"|"lyrics"|"subhead"|"trans"|"chyron"|"sfx"|"music"|"caption"|"note"|"=" => {
+            return Err(Diagnostic::syntax(line, "content", &format!("block declaration '{mode}'")))
+        }
+        "scene" => {
+            return Err(Diagnostic::syntax(line, "scene heading", "scene declaration"))
+        }
+        "omitted" => {
+            return Err(Diagnostic::syntax(line, "scene number", "omitted scene"))
+        }
+        _ if mode.bytes().all(|b| b == b'#') => {
+            return Err(Diagnostic::syntax(line, "content", "section header"))
+        }
+
+        _ => {
+            let whole = format!("{} {}", mode, text).trim().to_string();
+            let heading = ctx.heading(&whole);
+
+            if PAT_SCENE.is_match(&heading) {
+                return Ok(Block::Scene(heading.to_uppercase()))
+            } else if PAT_HEAD.is_match(&whole) {
+                Kind::Header{ text: whole }
+            } else if PAT_SPEECH.is_match(&whole) {
+                let (name, content) = split_cue(&whole).unwrap();
+                let base = cue_base(name);
+                let known = PAT_CAPS.is_match(base)
+                    || base.split(['&', '/']).all(|n| ctx.cast.contains(&n.trim().to_uppercase()));
+                if !known && base.contains(' ') {
+                    return Err(Diagnostic::syntax(line, "mode declaration", "new line"))
+                } else if !known {
+                    return Err(Diagnostic::new(line, format!("unknown character '{base}' (declare it with 'cast' or write the cue in capitals)")))
+                }
+                let adr = PAT_ADR.is_match(content);
+                let content = PAT_ADR.replace(content, "");
+                let spans = dialogue_spans(&content)
+                    .map_err(|_| Diagnostic::syntax(line, "closing ')'", "parenthetical"))?;
+                let lines = spans.into_iter()
+                    .map(|(parens, span)| if parens {
+                        DialogueLine::Parens(content[span].to_string())
+                    } else {
+                        DialogueLine::Speech(content[span].to_string())
+                    })
+                    .collect();
+                Kind::Dialogue(Dialogue{ name: ctx.normalize(&name.to_uppercase()), lines, adr })
+            } else {
+                return Err(Diagnostic::syntax(line, "mode declaration", "new line"))
+            }
+        }
+    };
+
+    Ok(Block::Element(kind))
+}
+
+
+const MODES: &[&str] = &[
+    "scene", "direct", "parens", "speech", "lyrics", "subhead", "trans", "chyron", "sfx", "music",
+    "caption", "note", "TODO", "montage", "mon-end", "pagebreak", "end", "cold-open", "tag", "act",
+    "episode", "page", "panel", "prologue", "prologue-end", "doctype", "episode-number", "cast", "alias", "include", "define", "omitted", "=",
+    "props", "wardrobe", "vehicles", "extras", "restricted",
+];
+
+
+fn looks_like_content(text: &[Cow<str>], ctx: &Context) -> bool {
+    let first = text.first().map(AsRef::as_ref).unwrap_or_default();
+    let mode = first.split_whitespace().next().unwrap_or_default();
+
+    MODES.contains(&ctx.keyword(mode))
+        || (!mode.is_empty() && mode.bytes().all(|b| b == b'#'))
+        || PAT_SCENE.is_match(&ctx.heading(first))
+}
+
+
+// the title and subtitle are the first two segments, unless the script starts straight away
+fn next_header<'a>(segments: &mut Segments<'a>, ctx: &Context) -> Option<(usize, Vec<Cow<'a, str>>)> {
+    match segments.next_whole() {
+        Some(whole) if looks_like_content(&whole.1, ctx) => {
+            segments.push_back(whole);
+            None
+        }
+        whole => whole,
+    }
+}
+
+
+// the lines the title and subtitle start on, for tools that work on the source text
+pub(crate) fn header_lines(src: &str, skip: usize, front: &FrontMatter, ctx: &Context) -> (Option<usize>, Option<usize>) {
+    if front.title.is_some() {
+        return (None, None)
+    }
+    let mut segments = Segments::skipping(src, skip);
+    let title = next_header(&mut segments, ctx).map(|(line, _)| line);
+    let subtitle = title.and_then(|_| next_header(&mut segments, ctx)).map(|(line, _)| line);
+    (title, subtitle)
+}
+
+
+fn get_header(segments: &mut Segments, fallback: &str, front: &FrontMatter) -> Context {
+    let mut ctx = Context::new(front);
+
+    if let Some(title) = &front.title {
+        ctx.title = title.clone();
+        ctx.subtitle = front.subtitle.clone().unwrap_or_default();
+        return ctx
+    }
+
+    let title = next_header(segments, &ctx).map(|(_, text)| text.join(" "));
+    let subtitle = title.as_ref().and_then(|_| next_header(segments, &ctx)).map(|(_, text)| text.join(" "));
+
+    if title.is_none() {
+        segments.warnings.push(Diagnostic::new(1, format!("no title line found, using '{fallback}' (the first two lines are the title and subtitle)")));
+    } else if subtitle.is_none() {
+        segments.warnings.push(Diagnostic::new(2, "no subtitle line found (the first two lines are the title and subtitle)"));
+    }
+
+    ctx.title = title.unwrap_or_else(|| fallback.to_string());
+    ctx.subtitle = subtitle.or_else(|| front.subtitle.clone()).unwrap_or_default();
+    ctx
+}
+
+
+fn tag_category(name: &str) -> String {
+    match name.to_lowercase().as_str() {
+        "props" => "prop".to_string(),
+        "vehicles" => "vehicle".to_string(),
+        "extras" => "extra".to_string(),
+        name => name.to_string(),
+    }
+}
+
+
+// inline tags leave their item in the text, so the sentence still reads naturally
+fn take_tags(text: &mut String, line: usize) -> Vec<Tag> {
+    if !text.contains('{') {
+        return Vec::new()
+    }
+    let tags = PAT_TAG.captures_iter(text)
+        .map(|caps| Tag{ line, category: tag_category(&caps[1]), item: caps[2].to_string() })
+        .collect();
+    *text = PAT_TAG.replace_all(text, "$2").into_owned();
+    tags
+}
+
+
+// the first part of the location, so that 'HOUSE - KITCHEN' continues from 'HOUSE - HALL'
+fn place(scene: &Scene) -> &str {
+    scene.location().split(" - ").next().unwrap_or_default().trim()
+}
+
+
+// a transition closing the previous scene marks a deliberate jump and silences the time check
+fn continuity(prev: &Scene, scene: &Scene) -> Option<String> {
+    let time = scene.time_of_day().to_uppercase();
+    let before = prev.time_of_day().to_uppercase();
+    if time == "CONTINUOUS" && !place(prev).eq_ignore_ascii_case(place(scene)) {
+        return Some(format!("CONTINUOUS scene at {} follows a scene at {}", scene.location(), prev.location()))
+    }
+    let cut = prev.elements.last().is_some_and(|e| matches!(e.kind, Kind::Trans{ .. }));
+    let flip = matches!((before.as_str(), time.as_str()), ("NIGHT", "DAY")|("DAY", "NIGHT"));
+    if flip && !cut && prev.location().eq_ignore_ascii_case(scene.location()) {
+        return Some(format!("{time} scene directly follows a {before} scene at {}", scene.location()))
+    }
+    None
+}
+
+
+fn speech_lines(dialogue: &Dialogue) -> impl DoubleEndedIterator<Item = &str> {
+    dialogue.lines.iter().filter_map(|l| match l {
+        DialogueLine::Speech(text) => Some(text.as_str()),
+        DialogueLine::Parens(_) => None,
+    })
+}
+
+
+// a line picked up with '--' must follow a line that was cut off, and a line that was cut off
+// cannot be picked up by its own speaker straight away
+fn interruptions(elements: &[Element]) -> Vec<Diagnostic> {
+    let mut warnings = Vec::new();
+    let mut last: Option<(&str, bool)> = None;
+    let mut adjacent = false;
+    for element in elements {
+        let Kind::Dialogue(dialogue) = &element.kind else {
+            adjacent = adjacent && matches!(element.kind, Kind::Note{ .. }|Kind::Todo{ .. });
+            continue
+        };
+        let name = cue_base(&dialogue.name);
+        let resumes = speech_lines(dialogue).next().is_some_and(|t| t.starts_with("--") || t.starts_with('—'));
+        match last {
+            Some((speaker, true)) if adjacent && speaker == name => warnings.push(Diagnostic::new(element.line,
+                format!("{name} picks up their own interrupted line, use '...' for trailing off"))),
+            Some((_, false))|None if resumes => warnings.push(Diagnostic::new(element.line,
+                format!("{name} picks up an interruption, but the previous line was not cut off with '--'"))),
+            _ => (),
+        }
+        last = Some((name, speech_lines(dialogue).next_back().is_some_and(interrupted)));
+        adjacent = true;
+    }
+    warnings
+}
+
+
+fn push_scene(script: &mut Script, mut scene: Scene, prologue: bool) {
+    scene.number = match &scene.locked {
+        Some(label) => label.trim_end_matches(|c: char| c.is_ascii_uppercase()).parse().unwrap_or(0),
+        None if prologue => 0,
+        None => script.scenes.iter().rev().map(|s| s.number).find(|&n| n > 0).unwrap_or(0) + 1,
+    };
+
+    if scene.number > 0 {
+        let label = scene.display_number();
+        if script.scenes.iter().any(|s| s.number > 0 && s.display_number() == label) {
+            script.warnings.push(Diagnostic::new(scene.line, format!("scene number {label} is used more than once")));
+        }
+    }
+    if let Some(prev) = script.scenes.iter().rev().find(|s| !s.omitted).filter(|_| !scene.omitted) {
+        if let Some(message) = continuity(prev, &scene) {
+            script.warnings.push(Diagnostic::new(scene.line, message));
+        }
+    }
+    script.scenes.push(scene);
+}
+
+
+pub fn parse(src: &str) -> Result<Script, Vec<Diagnostic>> {
+    parse_with(src, &ParseOptions::default())
+}
+
+
+pub fn parse_with(src: &str, opts: &ParseOptions) -> Result<Script, Vec<Diagnostic>> {
+    let (front, skip) = front_matter(src).map_err(|d| vec![d])?.unwrap_or_default();
+    parse_segments(front, Segments::skipping(src, skip), opts).map(|(script, _)| script)
+}
+
+
+// the script is built as its lines come in, only the front matter block is collected first
+pub(crate) fn parse_reader<'a>(reader: impl BufRead + 'a, opts: &ParseOptions) -> Result<(Script, usize), HtmlError> {
+    let failed = Rc::new(RefCell::new(None));
+    let error = failed.clone();
+    let mut lines = reader.lines()
+        .map_while(move |line| line.map_err(|e| *error.borrow_mut() = Some(e)).ok())
+        .map(Cow::<str>::Owned)
+        .enumerate()
+        .peekable();
+
+    let mut head = String::new();
+    if let Some((_, fence)) = lines.next_if(|(_, line)| matches!(line.trim(), "---"|"+++")) {
+        let fence = fence.trim().to_string();
+        head.push_str(&fence);
+        for (_, line) in lines.by_ref() {
+            head.push('\n');
+            head.push_str(&line);
+            if line.trim() == fence {
+                break
+            }
+        }
+    }
+    let front = front_matter(&head).map_err(|d| HtmlError::ParseError(vec![d]))?.map(|(front, _)| front).unwrap_or_default();
+
+    let parsed = parse_segments(front, Segments::from_lines(Box::new(lines)), opts);
+    if let Some(err) = failed.borrow_mut().take() {
+        return Err(err.into())
+    }
+    parsed.map_err(HtmlError::ParseError)
+}
+
+
+fn parse_segments(front: FrontMatter, mut segments: Segments, opts: &ParseOptions) -> Result<(Script, usize), Vec<Diagnostic>> {
+    if let Some(doctype) = front.doctype.as_deref().filter(|d| HouseStyle::from_name(d).is_none()) {
+        return Err(vec![Diagnostic::new(1, format!("unknown doctype '{doctype}' in front matter"))])
+    }
+    if let Some(keyword) = front.keywords.values().find(|k| !MODES.contains(&k.as_str())) {
+        return Err(vec![Diagnostic::new(1, format!("unknown keyword '{keyword}' in front matter"))])
+    }
+    if let Some(prefix) = front.prefixes.values().find(|p| !["INT.", "EXT.", "INT./EXT."].contains(&p.to_uppercase().as_str())) {
+        return Err(vec![Diagnostic::new(1, format!("unknown scene prefix '{prefix}' in front matter (expected INT., EXT. or INT./EXT.)"))])
+    }
+
+    let mut ctx = get_header(&mut segments, opts.title.as_deref().unwrap_or("Untitled"), &front);
+    ctx.vars.extend(opts.defines.iter().cloned());
+
+    let mut script = Script{
+        title: ctx.title.clone(),
+        subtitle: ctx.subtitle.clone(),
+        doctype: front.doctype,
+        episode: front.episode,
+        author: front.author,
+        draft: front.draft,
+        locations: front.locations,
+        ..Default::default()
+    };
+    let mut errors = Vec::new();
+    let mut act: Option<String> = None;
+    let mut prologue: Option<usize> = None;
+    let mut pages = 0;
+    let mut panels = 0;
+
+    for segment in segments.by_ref() {
+        let line = segment.line;
+
+        match get_block(segment, &ctx) {
+            Ok(Block::Scene(heading)) => {
+                let (heading, locked) = match PAT_LOCKED.captures(&heading) {
+                    Some(caps) => (caps[1].to_string(), Some(caps[2].to_string())),
+                    None => (heading, None),
+                };
+                push_scene(&mut script, Scene{ number: 0, locked, omitted: false, restricted: false, line, heading, elements: Vec::new(), tags: Vec::new() }, prologue.is_some());
+            }
+            Ok(Block::Omitted(label)) => {
+                push_scene(&mut script, Scene{ number: 0, locked: Some(label), omitted: true, restricted: false, line, heading: "OMITTED".to_string(), elements: Vec::new(), tags: Vec::new() }, false);
+            }
+            Ok(Block::Element(kind)) if !kind.is_structure() && script.scenes.last().is_some_and(|s| s.omitted) => {
+                errors.push(Diagnostic::new(line, "omitted scenes cannot have content"));
+            }
+            Ok(Block::Element(mut kind)) => {
+                if let Kind::Direct{ text } = &mut kind {
+                    let tags = take_tags(text, line);
+                    match script.scenes.last_mut() {
+                        Some(scene) => scene.tags.extend(tags),
+                        None if !tags.is_empty() => errors.push(Diagnostic::new(line, "breakdown tags must follow a scene heading")),
+                        None => (),
+                    }
+                }
+                match &mut kind {
+                    Kind::ComicPage{ number, .. } => {
+                        pages += 1;
+                        panels = 0;
+                        *number = pages;
+                    }
+                    Kind::Panel{ number, .. } => {
+                        panels += 1;
+                        *number = panels;
+                    }
+                    _ => (),
+                }
+                match &kind {
+                    Kind::Dialogue(dialogue) => ctx.cast.extend(dialogue.speakers().into_iter().map(str::to_string)),
+                    Kind::Act{ .. }|Kind::Episode{ .. }|Kind::End => if let Some(text) = act.take() {
+                        script.push(Element{ line, kind: Kind::ActEnd{ text } });
+                    }
+                    _ => (),
+                }
+                if let Kind::Act{ text } = &kind {
+                    act = Some(text.clone());
+                }
+                script.push(Element{ line, kind });
+            }
+            Ok(Block::Doctype(doctype)) => script.doctype = Some(doctype),
+            Ok(Block::Episode(episode)) => script.episode = Some(episode),
+            Ok(Block::Cast(names)) => ctx.cast.extend(names),
+            Ok(Block::Tags(category, items)) => match script.scenes.last_mut() {
+                Some(scene) => scene.tags.extend(items.into_iter().map(|item| Tag{ line, category: category.clone(), item })),
+                None => errors.push(Diagnostic::new(line, "breakdown tags must follow a scene heading")),
+            }
+            Ok(Block::Restricted) => match script.scenes.last_mut() {
+                Some(scene) => scene.restricted = true,
+                None => errors.push(Diagnostic::new(line, "'restricted' must follow a scene heading")),
+            }
+            Ok(Block::Prologue(true)) => prologue = Some(line),
+            Ok(Block::Prologue(false)) => prologue = None,
+            Ok(Block::Alias(names, target)) => for name in names {
+                ctx.cast.insert(name.clone());
+                ctx.aliases.insert(name, target.clone());
+            }
+            Ok(Block::Define(key, value)) => if !opts.defines.iter().any(|(k, _)| *k == key) {
+                ctx.vars.insert(key, value);
+            }
+            Err(diagnostic) => errors.push(diagnostic),
+        }
+    }
+
+    let (tail, lines) = segments.finish();
+    if let Some(text) = act {
+        script.push(Element{ line: lines, kind: Kind::ActEnd{ text } });
+    }
+
+    let style = opts.style.or_else(|| script.doctype.as_deref().and_then(HouseStyle::from_name)).unwrap_or_default();
+    script.warnings.extend(style.rules().validate(&script));
+    script.warnings.extend(tail);
+    script.warnings.extend(interruptions(&script.prelude));
+    for scene in &script.scenes {
+        script.warnings.extend(interruptions(&scene.elements));
+    }
+    if let Some(line) = prologue {
+        script.warnings.push(Diagnostic::new(line, "prologue is never closed with 'prologue-end', no scenes are numbered"));
+    }
+    script.warnings.sort_by_key(|w| w.line);
+
+    if errors.is_empty() {
+        Ok((script, lines))
+    } else {
+        Err(errors)
+    }
+}
+
+
+// a file truncated while it is mapped raises SIGBUS, which a single run can live with but the daemon cannot
+#[cfg(feature = "mmap")]
+static BUFFERED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+pub(crate) fn use_buffered_reads() {
+    #[cfg(feature = "mmap")]
+    BUFFERED.store(true, std::sync::atomic::Ordering::Relaxed);
+}
+
+
+#[cfg(feature = "mmap")]
+fn parse_single(path: &str, opts: &ParseOptions) -> Result<Option<(Script, usize)>, HtmlError> {
+    if BUFFERED.load(std::sync::atomic::Ordering::Relaxed) {
+        return parse_buffered(path, opts)
+    }
+    let file = fs::File::open(path)?;
+    // SAFETY: the map is private to this call and only read, lines are utf-8 checked as they are taken from it.
+    // An editor rewriting the file meanwhile can garble this one parse, or kill the process with SIGBUS by
+    // truncating it, so only short lived runs map files and the daemon reads through a buffer
+    let map = unsafe { memmap2::Mmap::map(&file)? };
+    if reads_includes(&map[..])? {
+        return Ok(None)
+    }
+    parse_reader(&map[..], opts).map(Some)
+}
+
+
+// a first pass looks for includes, so a plain script is parsed straight from the file without holding it whole
+#[cfg(not(feature = "mmap"))]
+fn parse_single(path: &str, opts: &ParseOptions) -> Result<Option<(Script, usize)>, HtmlError> {
+    parse_buffered(path, opts)
+}
+
+
+fn parse_buffered(path: &str, opts: &ParseOptions) -> Result<Option<(Script, usize)>, HtmlError> {
+    if reads_includes(io::BufReader::new(fs::File::open(path)?))? {
+        return Ok(None)
+    }
+    parse_reader(io::BufReader::new(fs::File::open(path)?), opts).map(Some)
+}
+
+
+pub(crate) fn parse_file(path: &str, defines: &[(String, String)]) -> Result<(Script, usize), HtmlError> {
+    parse_files(&[path], defines, None).map(|(script, lines, _)| (script, lines))
+}
+
+
+// maps a line of the parsed text back to the file and line it came from
+pub(crate) type Locate = Box<dyn Fn(Diagnostic) -> Diagnostic>;
+
+
+pub(crate) fn parse_files(paths: &[&str], defines: &[(String, String)], style: Option<HouseStyle>) -> Result<(Script, usize, Locate), HtmlError> {
+    let opts = ParseOptions{
+        title: paths.first().and_then(|path| Path::new(path).file_stem()).map(|stem| stem.to_string_lossy().into_owned()),
+        defines: defines.to_vec(),
+        style,
+    };
+    if let [path] = paths {
+        if !is_imported(path) {
+            if let Some((script, lines)) = parse_single(path, &opts)? {
+                return Ok((script, lines, Box::new(|d| d)))
+            }
+        }
+    }
+
+    let expanded = expand_all(paths).map_err(|d| HtmlError::ParseError(vec![d]))?;
+    let mut script = parse_with(&expanded.text, &opts)
+        .map_err(|errors| HtmlError::ParseError(errors.into_iter().map(|d| expanded.locate(d)).collect()))?;
+    script.warnings = expanded.warnings.iter().cloned().chain(script.warnings.into_iter().map(|d| expanded.locate(d))).collect();
+
+    let lines = expanded.text.lines().count();
+    Ok((script, lines, Box::new(move |d| expanded.locate(d))))
+}
+
+
+pub fn read_manifest(path: &str) -> io::Result<Vec<String>> {
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(|line| line.split_once('*').map_or(line, |(file, _)| file).trim())
+        .filter(|line| !line.is_empty())
+        .map(|file| dir.join(file).display().to_string())
+        .collect())
+}
+
+
+pub fn read_script(cmd: &CmdInfo) -> Result<Script, HtmlError> {
+    read_located(cmd).map(|(script, _)| script)
+}
+
+
+pub(crate) fn read_located(cmd: &CmdInfo) -> Result<(Script, Locate), HtmlError> {
+    verbose!("parsing {}", cmd.inputs().join(", "));
+    let (mut script, lines, locate) = parse_files(&cmd.inputs(), &cmd.defines, cmd.style)?;
+    debug!("{} line(s), {} scene(s), {} warning(s)", lines, script.scenes.len(), script.warnings.len());
+
+    if let Some(range) = &cmd.range {
+        script.retain_scenes(range);
+    }
+    if let Some(pattern) = &cmd.location {
+        script.retain_location(pattern);
+    }
+    if let Some(name) = &cmd.sides {
+        script.retain_sides(name, cmd.context);
+    }
+    if let Some(start) = cmd.renumber_from {
+        script.renumber(start);
+    }
+    if cmd.episode.is_some() {
+        script.episode = cmd.episode.clone();
+    }
+    if let Some(old) = &cmd.revised_since {
+        script.mark_revisions(&parse_file(old, &cmd.defines)?.0);
+    }
+    if cmd.smart_typography {
+        smarten(&mut script);
+    }
+    let closed = script.has_closure();
+    if !cmd.clearance {
+        script.redact_restricted();
+    }
+    // drafts are often left open, a missing closure is only reported when checking for delivery
+    if cmd.range.is_none() && cmd.sides.is_none() && cmd.location.is_none() && !closed {
+        if cmd.auto_end {
+            script.push(Element{ line: lines, kind: Kind::End });
+        } else if cmd.check {
+            script.warnings.push(locate(Diagnostic::new(lines, "script ends without a closure (add 'end' or use --auto-end)")));
+        }
+    }
+
+    Ok((script, locate))
+}
+
+
+pub fn gen_todos(cmd: &CmdInfo) -> Result<Vec<Todo>, HtmlError> {
+    Ok(read_script(cmd)?.todos())
+}
+
+
+pub fn gen_outline(cmd: &CmdInfo) -> Result<Vec<OutlineItem>, HtmlError> {
+    Ok(read_script(cmd)?.outline())
+}
+
+
+pub fn dialogue_tsv(cues: &[DialogueCue]) -> String {
+    let mut tsv = String::from("number\tscene\tcharacter\ttext\n");
+
+    for cue in cues {
+        let fields = [cue.number.to_string(), cue.scene.clone(), cue.name.clone(), cue.text.clone()];
+        tsv.push_str(&fields.map(|f| f.replace(['\t', '\n'], " ")).join("\t"));
+        tsv.push('\n');
+    }
+
+    tsv
+}
+
+
+pub fn gen_adr(cmd: &CmdInfo) -> Result<Vec<DialogueCue>, HtmlError> {
+    Ok(read_script(cmd)?.adr_cues(cmd.offscreen))
+}
+
+
+pub fn gen_completions(cmd: &CmdInfo) -> Result<String, HtmlError> {
+    Ok(serde_json::to_string_pretty(&read_script(cmd)?.completions())?)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // segments of a source in memory borrow from it, so they compare as plain slices
+    fn whole<'a>(segments: &mut Segments<'a>) -> Option<(usize, Vec<&'a str>)> {
+        segments.next_whole().map(|(line, text)| (line, text.into_iter().map(|t| match t {
+            Cow::Borrowed(t) => t,
+            Cow::Owned(_) => panic!("segment was copied"),
+        }).collect()))
+    }
+
+    #[test]
+    fn comments() {
+        let mut case = Segments::new(" \
+            \n\
+            line with content\n\
+            line with comment * comment\n\
+            \n\
+            \n\
+            * another comment");
+
+        assert_eq!(whole(&mut case), Some((2, vec!["line with content"])));
+        assert_eq!(whole(&mut case), Some((3, vec!["line with comment"])));
+        assert_eq!(whole(&mut case), None);
+    }
+
+    #[test]
+    fn emphasis_not_comment() {
+        let mut case = Segments::new(" \
+            I *really* mean it * comment\n\
+            **bold** and *more* * comment\n\
+            * whole line comment\n\
+            _under_ * comment");
+
+        assert_eq!(whole(&mut case), Some((1, vec!["I *really* mean it"])));
+        assert_eq!(whole(&mut case), Some((2, vec!["**bold** and *more*"])));
+        assert_eq!(whole(&mut case), Some((4, vec!["_under_"])));
+        assert_eq!(whole(&mut case), None);
+    }
+
+    #[test]
+    fn comment_boundary() {
+        let mut case = Segments::new(" \
+            a 5* hotel stay * comment\n\
+            rated 4.5* overall\n\
+            ** whole line comment\n\
+            end");
+
+        assert_eq!(whole(&mut case), Some((1, vec!["a 5* hotel stay"])));
+        assert_eq!(whole(&mut case), Some((2, vec!["rated 4.5* overall"])));
+        assert_eq!(whole(&mut case), Some((4, vec!["end"])));
+    }
+
+    #[test]
+    fn escapes() {
+        let mut case = Segments::new(" \
+            a 5\\* hotel stay * comment\n\
+            ends in a backslash \\\\\n\
+            continued \\ * comment\n\
+            here\n\
+            not continued \\\\ * comment\n\
+            \\*\\* starred \\\\\\\n\
+            line");
+
+        assert_eq!(whole(&mut case), Some((1, vec!["a 5\\* hotel stay"])));
+        assert_eq!(whole(&mut case), Some((2, vec!["ends in a backslash \\\\"])));
+        assert_eq!(whole(&mut case), Some((3, vec!["continued", "here"])));
+        assert_eq!(whole(&mut case), Some((5, vec!["not continued \\\\"])));
+        assert_eq!(whole(&mut case), Some((6, vec!["\\*\\* starred \\\\", "line"])));
+        assert_eq!(whole(&mut case), None);
+    }
+
+    #[test]
+    fn tail_warnings() {
+        let script = parse("title\nsubtitle\ndirect hello\n***\nleftover\n\nmore leftover").expect("parse failed");
+        assert_eq!(script.warnings, vec![
+            Diagnostic::new(4, "2 line(s) after the '***' tail marker are not rendered (lines 5-7)"),
+        ]);
+
+        let script = parse("title\nsubtitle\ndirect hello \\\n***").expect("parse failed");
+        assert_eq!(script.warnings, vec![
+            Diagnostic::new(3, "continued segment is cut short by the '***' tail marker"),
+        ]);
+
+        let script = parse("title\nsubtitle\ndirect hello").expect("parse failed");
+        assert!(script.warnings.is_empty());
+    }
+
+    #[test]
+    fn line_reach() {
+        let mut case = Segments::new(" \
+            line with content \n\
+            \n\
+            line with some content \\ \n\
+            and here some more content\n\
+            \n\
+            line with content \\ \n\
+            more content \\ \n\
+            last bit of content\n\
+            \n\
+            line with some content \\ * same as 2 but with comments \n\
+            and here some more content \n\
+            \n\
+            line with some content * 2 lines because of comment \\ \n\
+            and here some more content");
+
+        assert_eq!(whole(&mut case), Some((1,  vec!["line with content"])));
+        assert_eq!(whole(&mut case), Some((3,  vec!["line with some content", "and here some more content"])));
+        assert_eq!(whole(&mut case), Some((6,  vec!["line with content", "more content", "last bit of content"])));
+        assert_eq!(whole(&mut case), Some((10, vec!["line with some content", "and here some more content"])));
+        assert_eq!(whole(&mut case), Some((13, vec!["line with some content"])));
+        assert_eq!(whole(&mut case), Some((14, vec!["and here some more content"])));
+        assert_eq!(whole(&mut case), None);
+    }
+
+    #[test]
+    fn dialogue_text_kept() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             ALEX: I am speaking (Beat)\n\
+             ALEX: Hello (laughing (a lot)) there\n\
+             ALEX: (Mood) Meet me at 10:30 (whispers) sharp"
+        ).expect("parse failed");
+
+        let lines: Vec<&Vec<DialogueLine>> = script.prelude.iter()
+            .map(|e| match &e.kind { Kind::Dialogue(d) => &d.lines, _ => panic!("expected dialogue") })
+            .collect();
+
+        assert_eq!(lines[0], &vec![DialogueLine::Speech("I am speaking".to_string()), DialogueLine::Parens("(Beat)".to_string())]);
+        assert_eq!(lines[1], &vec![DialogueLine::Speech("Hello".to_string()),
+                                   DialogueLine::Parens("(laughing (a lot))".to_string()),
+                                   DialogueLine::Speech("there".to_string())]);
+        assert_eq!(lines[2], &vec![DialogueLine::Parens("(Mood)".to_string()),
+                                   DialogueLine::Speech("Meet me at 10:30".to_string()),
+                                   DialogueLine::Parens("(whispers)".to_string()),
+                                   DialogueLine::Speech("sharp".to_string())]);
+
+        let errors = parse("title\nsubtitle\nALEX: I am (unfinished").expect_err("parse should fail");
+        assert_eq!(errors, vec![Diagnostic::syntax(3, "closing ')'", "parenthetical")]);
+    }
+
+    #[test]
+    fn cues() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             cast Alex, Old Sam\n\
+             alex: Meet me at 10:30\n\
+             SAM: Hello\n\
+             sam: Hello again\n\
+             old sam: Hello\n\
+             OLD MAN 2: Hello\n\
+             MRS. O'BRIEN (V.O.): Hello\n\
+             COP #1: Freeze\n\
+             alex & old sam: Together\n\
+             ALL: Hooray"
+        ).expect("parse failed");
+
+        let names: Vec<&str> = script.prelude.iter()
+            .filter_map(|e| match &e.kind {
+                Kind::Dialogue(Dialogue{ name, .. }) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, vec!["ALEX", "SAM", "SAM", "OLD SAM", "OLD MAN 2", "MRS. O'BRIEN (V.O.)", "COP #1", "ALEX & OLD SAM", "ALL"]);
+
+        let Kind::Dialogue(group) = &script.prelude[7].kind else {
+            panic!("expected group dialogue")
+        };
+        assert_eq!(group.speakers(), vec!["ALEX", "OLD SAM"]);
+
+        let errors = parse(
+            "title\n\
+             subtitle\n\
+             The sign reads: KEEP OUT\n\
+             Visit https://example.com\n\
+             note: this is not a cue\n\
+             Old man: not declared either"
+        ).expect_err("parse should fail");
+
+        assert_eq!(errors, vec![
+            Diagnostic::syntax(3, "mode declaration", "new line"),
+            Diagnostic::syntax(4, "mode declaration", "new line"),
+            Diagnostic::new(5, "unknown character 'note' (declare it with 'cast' or write the cue in capitals)"),
+            Diagnostic::syntax(6, "mode declaration", "new line"),
+        ]);
+    }
+
+    #[test]
+    fn errors() {
+        let errors = parse(
+            "title\n\
+             subtitle\n\
+             direct\n\
+             scene EXT. LOC - DAY\n\
+             montage now\n\
+             this is not valid"
+        ).expect_err("parse should fail");
+
+        assert_eq!(errors, vec![
+            Diagnostic::syntax(3, "content", "block declaration 'direct'"),
+            Diagnostic::syntax(5, "newline", "montage delimiter 'montage'"),
+            Diagnostic::syntax(6, "mode declaration", "new line"),
+        ]);
+    }
+
+    #[test]
+    fn ast() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             scene EXT. LOC - DAY\n\
+             ALEX: (Mood) I am speaking"
+        ).expect("parse failed");
+
+        assert_eq!(serde_json::to_string(&script).unwrap(),
+            "{\"title\":\"title\",\"subtitle\":\"subtitle\",\"doctype\":null,\"episode\":null,\"author\":null,\"draft\":null,\"locations\":[],\"prelude\":[],\"scenes\":[{\"number\":1,\"line\":3,\"heading\":\"EXT. LOC - DAY\",\
+              \"elements\":[{\"line\":4,\"kind\":\"dialogue\",\"name\":\"ALEX\",\"lines\":\
+              [{\"kind\":\"parens\",\"text\":\"(Mood)\"},{\"kind\":\"speech\",\"text\":\"I am speaking\"}]}]}]}");
+    }
+
+    #[test]
+    fn ast_roundtrip() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             doctype multicam\n\
+             episode-number 12\n\
+             direct before the first scene\n\
+             TODO\n\
+             cold-open\n\
+             scene EXT. LOC - DAY\n\
+             montage\n\
+             ALEX: (Mood) I am speaking (Beat) still speaking\n\
+             trans CUT TO"
+        ).expect("parse failed");
+
+        let json = serde_json::to_string(&script).unwrap();
+        let back: Script = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(back, script);
+
+        let mut marked = script.clone();
+        marked.warnings.push(Diagnostic::new(8, "check this"));
+        marked.revised.insert(8);
+        marked.redacted.insert(8);
+        let json = serde_json::to_string(&marked).unwrap();
+        assert!(json.ends_with(",\"warnings\":[{\"line\":8,\"message\":\"check this\"}],\"revised\":[8],\"redacted\":[8]}"));
+        assert_eq!(serde_json::from_str::<Script>(&json).unwrap(), marked);
+    }
+
+    #[test]
+    fn todos() {
+        let cases = parse(
+            "title\n\
+             subtitle\n\
+             TODO\n\
+             scene EXT. LOC - DAY\n\
+             direct this is directorial info\n\
+             TODO fix the \\\n\
+             pacing here\n\
+             EXT. LOC - NIGHT\n\
+             TODO   punch up"
+        ).expect("parse failed").todos();
+
+        assert_eq!(cases, vec![
+            Todo{ line: 3, scene: 0, label: "0".to_string(), text: "".to_string() },
+            Todo{ line: 6, scene: 1, label: "1".to_string(), text: "fix the pacing here".to_string() },
+            Todo{ line: 9, scene: 2, label: "2".to_string(), text: "punch up".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn outline() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             # Act One\n\
+             = Alex arrives in town\n\
+             scene EXT. LOC - DAY\n\
+             direct Alex looks around.\n\
+             ## The chase\n\
+             scene INT. CAR - DAY"
+        ).expect("parse failed");
+
+        assert_eq!(script.outline(), vec![
+            OutlineItem::Section{ line: 3, depth: 1, text: "Act One".to_string() },
+            OutlineItem::Synopsis{ line: 4, text: "Alex arrives in town".to_string() },
+            OutlineItem::Scene{ line: 5, label: "1".to_string(), heading: "EXT. LOC - DAY".to_string() },
+            OutlineItem::Section{ line: 7, depth: 2, text: "The chase".to_string() },
+            OutlineItem::Scene{ line: 8, label: "2".to_string(), heading: "INT. CAR - DAY".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn acts() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             act one\n\
+             scene EXT. LOC - DAY\n\
+             ALEX: Hello\n\
+             direct Alex leaves.\n\
+             act two\n\
+             scene INT. CAR - DAY\n\
+             ALEX: Goodbye"
+        ).expect("parse failed");
+
+        assert_eq!(script.scenes[0].elements[2].kind, Kind::ActEnd{ text: "ACT ONE".to_string() });
+        assert_eq!(script.scenes[1].elements.last().map(|e| &e.kind), Some(&Kind::ActEnd{ text: "ACT TWO".to_string() }));
+        assert_eq!(script.acts(), vec![
+            ActStats{ name: "ACT ONE".to_string(), scenes: vec![1], elements: 2, dialogue: 1 },
+            ActStats{ name: "ACT TWO".to_string(), scenes: vec![2], elements: 1, dialogue: 1 },
+        ]);
+    }
+
+    #[test]
+    fn prologue() {
+        let mut script = parse(
+            "title\n\
+             subtitle\n\
+             prologue\n\
+             scene EXT. SPACE - NIGHT\n\
+             direct Stars.\n\
+             prologue-end\n\
+             scene INT. SHIP - DAY\n\
+             scene INT. BRIDGE - DAY"
+        ).expect("parse failed");
+
+        let numbers: Vec<u32> = script.scenes.iter().map(|s| s.number).collect();
+        assert_eq!(numbers, vec![0, 1, 2]);
+        assert!(script.warnings.is_empty());
+
+        script.renumber(10);
+        let numbers: Vec<u32> = script.scenes.iter().map(|s| s.number).collect();
+        assert_eq!(numbers, vec![0, 10, 11]);
+
+        let script = parse("title\nsubtitle\nprologue\nscene EXT. SPACE - NIGHT").expect("parse failed");
+        assert_eq!(script.warnings.len(), 1);
+        let script = parse("title\nsubtitle\nprologue\nscene EXT. SPACE - NIGHT\n***\nnotes").expect("parse failed");
+        assert_eq!(script.warnings.iter().map(|w| w.line).collect::<Vec<_>>(), [3, 5]);
+    }
+
+    #[test]
+    fn closure() {
+        let script = parse("title\nsubtitle\ndirect Alex leaves.\nend\nTODO polish").expect("parse failed");
+        assert!(script.has_closure());
+
+        let script = parse("title\nsubtitle\ndirect Alex leaves.\ntrans FADE OUT.").expect("parse failed");
+        assert!(script.has_closure());
+
+        let script = parse("title\nsubtitle\ndirect Alex leaves.").expect("parse failed");
+        assert!(!script.has_closure());
+    }
+
+    #[test]
+    fn inferred_title() {
+        let opts = ParseOptions{ title: Some("my_script".to_string()), ..Default::default() };
+        let script = parse_with("scene EXT. LOC - DAY\ndirect Alex looks around.", &opts).expect("parse failed");
+        assert_eq!((script.title.as_str(), script.subtitle.as_str()), ("my_script", ""));
+        assert_eq!(script.scenes.len(), 1);
+        assert_eq!(script.warnings[0].line, 1);
+
+        let script = parse("My Title\ndirect Alex waits.").expect("parse failed");
+        assert_eq!((script.title.as_str(), script.subtitle.as_str()), ("My Title", ""));
+        assert_eq!(script.prelude.len(), 1);
+        assert_eq!(script.warnings[0].line, 2);
+
+        let script = parse("My Title\nA subtitle\nALEX: Hello").expect("parse failed");
+        assert!(script.warnings.is_empty());
+
+        // a colon in the title or subtitle is not dialogue
+        let script = parse("STAR WARS: A NEW HOPE\nEpisode IV\nscene EXT. SPACE - NIGHT").expect("parse failed");
+        assert_eq!((script.title.as_str(), script.subtitle.as_str()), ("STAR WARS: A NEW HOPE", "Episode IV"));
+        let script = parse("My Film\nWritten by: Jane Doe\nscene EXT. SPACE - NIGHT").expect("parse failed");
+        assert_eq!((script.title.as_str(), script.subtitle.as_str()), ("My Film", "Written by: Jane Doe"));
+        assert!(script.warnings.is_empty());
+    }
+
+    #[test]
+    fn variables() {
+        let opts = ParseOptions{ defines: vec![("TOWN".to_string(), "SPRINGFIELD".to_string())], ..Default::default() };
+        let script = parse_with(
+            "title\n\
+             subtitle\n\
+             define HERO = ALEX\n\
+             define TOWN = SHELBYVILLE\n\
+             scene EXT. $TOWN - DAY\n\
+             $HERO: Welcome to $TOWN, the home of $title. It costs $5."
+        , &opts).expect("parse failed");
+
+        assert_eq!(script.scenes[0].heading, "EXT. SPRINGFIELD - DAY");
+        assert_eq!(script.scenes[0].elements[0].kind, Kind::Dialogue(Dialogue{
+            name: "ALEX".to_string(),
+            lines: vec![DialogueLine::Speech("Welcome to SPRINGFIELD, the home of title. It costs $5.".to_string())],
+            adr: false,
+        }));
+
+        let errors = parse("title\nsubtitle\ndefine 1BAD = x").expect_err("parse should fail");
+        assert_eq!(errors, vec![Diagnostic::syntax(3, "'NAME = VALUE'", "variable definition")]);
+    }
+
+    #[test]
+    fn aliases() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             alias JON, Johnny = Jonathan\n\
+             JON: Hello\n\
+             johnny (V.O.): Hello again\n\
+             JON & SAM: Together"
+        ).expect("parse failed");
+
+        let names: Vec<&str> = script.prelude.iter()
+            .filter_map(|e| match &e.kind {
+                Kind::Dialogue(Dialogue{ name, .. }) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, vec!["JONATHAN", "JONATHAN (V.O.)", "JONATHAN & SAM"]);
+    }
+
+    #[test]
+    fn front_matter_feeds_script() {
+        let script = parse(
+            "---\n\
+             title: Pilot\n\
+             author: A. Vance\n\
+             doctype: multicam\n\
+             characters: [Alex]\n\
+             aliases: {JON: JONATHAN}\n\
+             variables: {TOWN: SPRINGFIELD}\n\
+             ---\n\
+             scene EXT. $TOWN - DAY\n\
+             alex: Hello\n\
+             jon: Hi"
+        ).expect("parse failed");
+
+        assert_eq!((script.title.as_str(), script.subtitle.as_str()), ("Pilot", ""));
+        assert_eq!(script.author.as_deref(), Some("A. Vance"));
+        assert_eq!(script.doctype.as_deref(), Some("multicam"));
+        assert_eq!(script.scenes[0].line, 9);
+        assert_eq!(script.scenes[0].heading, "EXT. SPRINGFIELD - DAY");
+        assert!(matches!(&script.scenes[0].elements[1].kind, Kind::Dialogue(Dialogue{ name, .. }) if name == "JONATHAN"));
+        // the doctype brings the house style's checks with it
+        assert_eq!(script.warnings, [Diagnostic::new(9, "scene comes before the first act, this house style is divided into acts")]);
+    }
+
+    #[test]
+    fn split_scenes() {
+        let script = parse("title\nsubtitle\nscene INT. KITCHEN - DAY\nscene EXT. ROAD, NORTH - NIGHT #2A#").expect("parse failed");
+        let names: Vec<String> = script.split_scenes().into_iter().map(|(name, s)| {
+            assert_eq!(s.scenes.len(), 1);
+            name
+        }).collect();
+        assert_eq!(names, vec!["1-int-kitchen-day", "2a-ext-road-north-night"]);
+    }
+
+    #[test]
+    fn locations() {
+        let mut script = parse(
+            "title\nsubtitle\n\
+             scene INT. KITCHEN - DAY\n\
+             scene EXT. KITCHEN GARDEN - NIGHT\n\
+             scene INT. CAR - DAY\n\
+             scene INT. HOUSE - KITCHEN - DAY"
+        ).expect("parse failed");
+        assert_eq!(script.scenes[3].location(), "HOUSE - KITCHEN");
+
+        script.retain_location(&Regex::new("(?i)^kitchen$|house").unwrap());
+        let numbers: Vec<u32> = script.scenes.iter().map(|s| s.number).collect();
+        assert_eq!(numbers, vec![1, 4]);
+    }
+
+    #[test]
+    fn adr_tags() {
+        let script = parse("title\nsubtitle\nscene INT. A - DAY\nALEX: Hi. #adr\nSAM: Hey.\nSAM (V.O.): Later.").expect("parse failed");
+        assert!(matches!(&script.scenes[0].elements[0].kind, Kind::Dialogue(d) if d.adr && d.lines == [DialogueLine::Speech("Hi.".to_string())]));
+
+        let cues: Vec<(u32, String)> = script.adr_cues(false).into_iter().map(|c| (c.number, c.name)).collect();
+        assert_eq!(cues, [(1, "ALEX".to_string())]);
+        assert_eq!(script.adr_cues(true).last().map(|c| c.number), Some(3));
+    }
+
+    #[test]
+    fn completions() {
+        let script = parse("title\nsubtitle\nscene INT. A - DAY\nALEX: Hi.\nSAM: Hey.\ntrans cut to:\nscene EXT. B - NIGHT\nSAM (V.O.): Later.\nomitted 3\nscene INT. A - NIGHT\nSAM & ALEX: Go.").expect("parse failed");
+        let completions = script.completions();
+
+        assert_eq!(completions.characters, ["SAM", "ALEX"]);
+        assert_eq!(completions.locations, ["A", "B"]);
+        assert_eq!(completions.transitions, ["CUT TO:"]);
+    }
+
+    #[test]
+    fn inline_tags() {
+        let script = parse("title\nsubtitle\nscene EXT. ROAD - NIGHT\ndirect She drops the {prop: revolver} as {sfx: thunder} rolls.\nprops coin").expect("parse failed");
+        let scene = &script.scenes[0];
+
+        assert_eq!(scene.elements[0].kind, Kind::Direct{ text: "She drops the revolver as thunder rolls.".to_string() });
+        let tags: Vec<(&str, &str)> = scene.tags.iter().map(|t| (t.category.as_str(), t.item.as_str())).collect();
+        assert_eq!(tags, [("prop", "revolver"), ("sfx", "thunder"), ("prop", "coin")]);
+
+        assert!(parse("title\nsubtitle\ndirect A {prop: gun}.").is_err());
+    }
+
+    #[test]
+    fn restricted() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let infile = dir.join("twist.txt").display().to_string();
+        fs::write(&infile, "title\nsubtitle\nscene INT. A - DAY\ndirect Hi.\nscene INT. LAIR - NIGHT\nrestricted\nVILLAIN: It was me.\nend\n").unwrap();
+
+        let script = read_script(&CmdInfo{ infile: infile.clone(), ..Default::default() }).expect("read failed");
+        assert!(script.warnings.is_empty());
+        assert_eq!(script.scenes[1].heading, "RESTRICTED");
+        assert!(script.scenes[1].elements.is_empty());
+        assert!(script.redacted.contains(&script.scenes[1].line));
+
+        let cleared = read_script(&CmdInfo{ infile, clearance: true, ..Default::default() }).expect("read failed");
+        assert_eq!(cleared.scenes[1].elements.len(), 2);
+    }
+
+    #[test]
+    fn renumber_from() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let infile = dir.join("draft.txt").display().to_string();
+        let scenes: String = (1..=5).map(|i| format!("scene INT. ROOM {i} - DAY\ndirect Beat {i}.\n")).collect();
+        fs::write(&infile, format!("title\nsubtitle\n{scenes}end\n")).unwrap();
+
+        let numbers = |cmd: CmdInfo| -> Vec<(u32, String)> {
+            read_script(&cmd).expect("read failed").scenes.into_iter().map(|s| (s.number, s.heading)).collect()
+        };
+        let extract = CmdInfo{ infile: infile.clone(), range: Some(3..5), ..Default::default() };
+        assert_eq!(numbers(extract.clone()), [(3, "INT. ROOM 3 - DAY".to_string()), (4, "INT. ROOM 4 - DAY".to_string())]);
+        assert_eq!(numbers(CmdInfo{ renumber_from: Some(1), ..extract }), [(1, "INT. ROOM 3 - DAY".to_string()), (2, "INT. ROOM 4 - DAY".to_string())]);
+    }
+
+    #[test]
+    fn unclosed() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let infile = dir.join("draft.txt").display().to_string();
+        fs::write(&infile, "title\nsubtitle\nscene INT. A - DAY\ndirect Hi.\n").unwrap();
+
+        assert!(read_script(&CmdInfo{ infile: infile.clone(), ..Default::default() }).expect("read failed").warnings.is_empty());
+        let checked = read_script(&CmdInfo{ infile: infile.clone(), check: true, ..Default::default() }).expect("read failed");
+        assert_eq!(checked.warnings, [Diagnostic::new(4, "script ends without a closure (add 'end' or use --auto-end)")]);
+        let closed = read_script(&CmdInfo{ infile, check: true, auto_end: true, ..Default::default() }).expect("read failed");
+        assert!(closed.warnings.is_empty() && closed.has_closure());
+
+        // the last line is reported in the file it belongs to
+        let main = dir.join("main.txt").display().to_string();
+        let act = dir.join("act.txt").display().to_string();
+        fs::write(&main, "title\nsubtitle\ninclude act.txt\n").unwrap();
+        fs::write(&act, "scene INT. A - DAY\ndirect Hi.\n").unwrap();
+        let included = read_script(&CmdInfo{ infile: main, check: true, ..Default::default() }).expect("read failed");
+        assert_eq!(included.warnings[0].file.as_deref(), Some(act.as_str()));
+        assert_eq!(included.warnings[0].line, 2);
+    }
+
+    #[test]
+    fn sides() {
+        let src = "title\nsubtitle\n\
+                   scene EXT. ONE - DAY\nSAM: Hi\n\
+                   scene EXT. TWO - DAY\ndirect Alex runs.\n\
+                   scene EXT. THREE - DAY\nSAM: Bye\n\
+                   scene EXT. FOUR - DAY\nSAM & ALEX: Now\n\
+                   scene EXT. FIVE - DAY\nSAM: End";
+        let headings = |script: &Script| script.scenes.iter().map(|s| s.number).collect::<Vec<u32>>();
+
+        let mut script = parse(src).expect("parse failed");
+        script.retain_sides("alex", 0);
+        assert_eq!(headings(&script), vec![2, 4]);
+
+        let mut script = parse(src).expect("parse failed");
+        script.retain_sides("ALEX", 1);
+        assert_eq!(headings(&script), vec![1, 2, 3, 4, 5]);
+
+        let mut script = parse(src).expect("parse failed");
+        script.retain_sides("BOB", 1);
+        assert!(script.scenes.is_empty());
+    }
+
+    #[test]
+    fn revisions() {
+        let old = parse("title\nsubtitle\nscene EXT. LOC - DAY\nALEX: Hi\ndirect Alex waves.\nscene INT. CAR - DAY\nend").expect("parse failed");
+        let mut new = parse("title\nsubtitle\nscene EXT. LOC - DAY\nALEX: Hello\ndirect Alex waves.\nscene INT. BUS - DAY\ndirect Alex waves.\nend").expect("parse failed");
+        new.mark_revisions(&old);
+
+        let mut lines: Vec<usize> = new.revised.into_iter().collect();
+        lines.sort_unstable();
+        assert_eq!(lines, vec![4, 6, 7]);
+    }
+
+    #[test]
+    fn locked_numbers() {
+        let script = parse(
+            "title\nsubtitle\n\
+             scene EXT. LOC - DAY #11#\n\
+             scene INT. CAR - DAY #11A#\n\
+             omitted 12\n\
+             scene EXT. ROAD - NIGHT\n\
+             scene EXT. ROAD - DAWN #13#\n\
+             end"
+        ).expect("parse failed");
+
+        let labels: Vec<(u32, String, bool)> = script.scenes.iter().map(|s| (s.number, s.display_number(), s.omitted)).collect();
+        assert_eq!(labels, vec![
+            (11, "11".to_string(), false),
+            (11, "11A".to_string(), false),
+            (12, "12".to_string(), true),
+            (13, "13".to_string(), false),
+            (13, "13".to_string(), false),
+        ]);
+        assert_eq!(script.scenes[1].heading, "INT. CAR - DAY");
+        assert_eq!(script.warnings, vec![Diagnostic::new(7, "scene number 13 is used more than once")]);
+
+        let mut renumbered = script.clone();
+        renumbered.renumber(1);
+        let labels: Vec<String> = renumbered.scenes.iter().map(|s| s.display_number()).collect();
+        assert_eq!(labels, ["1", "2", "3", "4"]);
+
+        let errors = parse("title\nsubtitle\nomitted 12\ndirect Gone.").expect_err("content after omitted");
+        assert_eq!(errors[0].message, "omitted scenes cannot have content");
+    }
+
+    #[test]
+    fn episode_number() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             episode-number 105\n\
+             scene EXT. LOC - DAY\n\
+             TODO check continuity"
+        ).expect("parse failed");
+
+        assert_eq!(script.episode.as_deref(), Some("105"));
+        assert_eq!(script.todos()[0].label, "EP105 SC1");
+    }
+
+    #[test]
+    fn continuity() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             scene INT. HOUSE - HALL - NIGHT\n\
+             scene INT. HOUSE - KITCHEN - CONTINUOUS\n\
+             scene EXT. ROAD - CONTINUOUS\n\
+             scene EXT. ROAD - DAY\n\
+             trans CUT TO:\n\
+             scene EXT. ROAD - NIGHT\n\
+             scene EXT. ROAD - DAY"
+        ).expect("parse failed");
+
+        assert_eq!(script.warnings, vec![
+            Diagnostic::new(5, "CONTINUOUS scene at ROAD follows a scene at HOUSE - KITCHEN"),
+            Diagnostic::new(9, "DAY scene directly follows a NIGHT scene at ROAD"),
+        ]);
+    }
+
+    #[test]
+    fn unicode() {
+        let script = parse(
+            "Titel\n\
+             Untertitel\n\
+             scene INT. KÜCHE - NACHT\n\
+             ÉLODIE: Où est-il ?\n\
+             ЖЕНЯ (V.O.): Здесь.\n\
+             王：你好。\n\
+             ÜBERRASCHUNG"
+        ).expect("parse failed");
+
+        let scene = &script.scenes[0];
+        assert_eq!(scene.location(), "KÜCHE");
+        let names: Vec<&str> = scene.elements.iter().filter_map(|e| match &e.kind {
+            Kind::Dialogue(dialogue) => Some(dialogue.name.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(names, vec!["ÉLODIE", "ЖЕНЯ (V.O.)", "王"]);
+        assert_eq!(scene.elements[2].kind, Kind::Dialogue(Dialogue{ name: "王".to_string(), lines: vec![DialogueLine::Speech("你好。".to_string())], adr: false }));
+        assert_eq!(scene.elements[3].kind, Kind::Header{ text: "ÜBERRASCHUNG".to_string() });
+        assert!(parse("title\nsubtitle\nscene INT. KÜCHE - NACHT\nélodie: Hallo.").is_err());
+    }
+
+    #[test]
+    fn keywords() {
+        let script = parse(
+            "---\n\
+             title: Der Besuch\n\
+             keywords: { szene: scene, aktion: direct, ende: end }\n\
+             prefixes: { INNEN.: INT., AUSSEN.: EXT., INNEN./AUSSEN.: INT./EXT. }\n\
+             ---\n\
+             szene Innen. KÜCHE - NACHT\n\
+             aktion Sie wartet.\n\
+             AUSSEN. STRASSE - TAG\n\
+             Innen./Aussen. AUTO - TAG\n\
+             ende"
+        ).expect("parse failed");
+
+        let headings: Vec<&str> = script.scenes.iter().map(|s| s.heading.as_str()).collect();
+        assert_eq!(headings, vec!["INT. KÜCHE - NACHT", "EXT. STRASSE - TAG", "INT./EXT. AUTO - TAG"]);
+        assert_eq!(script.scenes[0].elements[0].kind, Kind::Direct{ text: "Sie wartet.".to_string() });
+        assert!(script.has_closure());
+
+        let errors = parse("---\nkeywords: { szene: scnee }\n---\n").expect_err("unknown keyword");
+        assert_eq!(errors[0].message, "unknown keyword 'scnee' in front matter");
+    }
+
+    #[test]
+    fn interruptions() {
+        let script = parse(
+            "title\n\
+             subtitle\n\
+             scene INT. ROOM - DAY\n\
+             ALEX: I was going to --\n\
+             SAM: -- tell me. I know.\n\
+             SAM: And then --\n\
+             SAM: -- nothing.\n\
+             ALEX: -- what?\n\
+             ALEX: Well...\n\
+             direct A door slams.\n\
+             SAM: Where was I --\n\
+             direct Silence.\n\
+             SAM: -- right."
+        ).expect("parse failed");
+
+        assert_eq!(script.warnings, vec![
+            Diagnostic::new(7, "SAM picks up their own interrupted line, use '...' for trailing off"),
+            Diagnostic::new(8, "ALEX picks up an interruption, but the previous line was not cut off with '--'"),
+        ]);
+    }
+
+    #[test]
+    fn streaming() {
+        let src = "---\ntitle: Pilot\n---\nact one\nscene INT. ROOM - DAY\nALEX: One \\\n  more.\n***\ndirect Cut.\n\n";
+        let (script, lines) = parse_reader(src.as_bytes(), &ParseOptions::default()).expect("parse failed");
+        assert_eq!(script, parse(src).expect("parse failed"));
+        assert_eq!(lines, src.lines().count());
+
+        let err = parse_reader(&b"title\nsubtitle\nscene INT. A - DAY\ndirect \xff\n"[..], &ParseOptions::default()).expect_err("invalid utf-8");
+        assert!(matches!(err, HtmlError::IoError(_)));
+        let err = parse_reader("---\ntitle: Pilot\n".as_bytes(), &ParseOptions::default()).expect_err("unclosed front matter");
+        assert!(matches!(err, HtmlError::ParseError(d) if d[0].message == "front matter is never closed with '---'"));
+    }
+}
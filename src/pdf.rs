@@ -1,20 +1,400 @@
-use std::process::{ Command, ExitStatus };
+use std::{
+    fs,
+    io::{ BufWriter, Write },
+    path::Path,
+    process::{ Command, ExitStatus, Stdio },
+    sync::atomic::{ AtomicUsize, Ordering },
+};
+use crate::{
+    ast::Script,
+    html::*,
+    logging::{ enabled, Verbosity },
+    progress::{ finish_progress, Progress },
+    typeset::{ char_width, typeset, Sheet, LINES_PER_PAGE },
+    debug,
+    verbose,
+    CmdInfo,
+    Encryption,
+    Engine,
+};
 
 
-pub fn gen_pdf(cmd: &super::CmdInfo) -> std::io::Result<ExitStatus> {
+const PAGE_WIDTH: f32 = 612.0;
+const PAGE_HEIGHT: f32 = 792.0;
+const TOP: f32 = 72.0;
+const LEADING: f32 = 12.0;
+const ADVANCE: f32 = 7.2;
+const FONTS: [&str; 4] = ["Courier", "Courier-Bold", "Courier-Oblique", "Courier-BoldOblique"];
+
+
+static INTERMEDIATES: AtomicUsize = AtomicUsize::new(0);
+
+
+// every call gets its own file, so daemon builds running side by side never share one
+pub fn temp_html() -> String {
+    let n = INTERMEDIATES.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("scripts-{}-{n}.html", std::process::id())).display().to_string()
+}
+
+
+pub fn gen_pdf(cmd: &CmdInfo) -> Result<ExitStatus, HtmlError> {
     let program = format!("{}/wkhtmltopdf.exe", cmd.exe_loc);
 
-    Command::new(program)
+    let mut command = Command::new(program);
+    command
         .args(["--margin-top", "1in"])
         .args(["--margin-bottom", "1in"])
         .args(["--margin-left", "0in"])
         .args(["--margin-right", "0in"])
-        .arg("--grayscale")
+        .arg("--grayscale");
+    if let Some(mark) = &cmd.watermark {
+        command
+            .args(["--footer-center", &format!("Prepared for {mark}")])
+            .args(["--footer-font-size", "8"]);
+    }
+    command
         .arg(&cmd.html)
         .args(["--encoding", "utf-8"])
         //.args(["--user-style-sheet", "\"../res/style.css\""])
         .arg("--disable-smart-shrinking")
+        // scene headings, acts and the title are the only h1 elements, subheads stay out of the sidebar
+        .arg("--outline")
+        .args(["--outline-depth", "1"])
         .arg("--enable-local-file-access")
+        .arg(&cmd.outfile);
+
+    if !enabled(Verbosity::Normal) {
+        command.arg("--quiet");
+    }
+    // keep stdout free for machine readable progress events
+    if cmd.progress == Progress::Json {
+        command.stdout(Stdio::null());
+    }
+    // webkit draws its own progress
+    finish_progress(cmd);
+    verbose!("running webkit on {}", cmd.html);
+    debug!("{command:?}");
+    let status = command.status();
+    if !cmd.keep_intermediate {
+        let _ = fs::remove_file(&cmd.html);
+    }
+    let status = status?;
+    verbose!("webkit finished: {status}");
+
+    if let (Some(encryption), true) = (&cmd.encrypt, status.success()) {
+        encrypt_pdf(cmd, encryption)?;
+    }
+    Ok(status)
+}
+
+
+// webkit cannot write encrypted files, qpdf writes a locked copy that replaces the plaintext only once it is complete
+pub(crate) fn encrypt_pdf(cmd: &CmdInfo, encryption: &Encryption) -> Result<(), HtmlError> {
+    let encrypted = format!("{}.encrypting", cmd.outfile);
+    let result = run_qpdf(cmd, encryption, &encrypted)
+        .and_then(|_| fs::rename(&encrypted, &cmd.outfile).map_err(|err| err.to_string()));
+
+    // an unprotected copy must never be left behind
+    if let Err(err) = result {
+        let _ = fs::remove_file(&encrypted);
+        let _ = fs::remove_file(&cmd.outfile);
+        return Err(HtmlError::EncryptError(err))
+    }
+    Ok(())
+}
+
+
+// the passwords go through an argument file so they never show up in the process list
+fn run_qpdf(cmd: &CmdInfo, encryption: &Encryption, encrypted: &str) -> Result<(), String> {
+    let bundled = format!("{}/qpdf.exe", cmd.exe_loc);
+    let program = if Path::new(&bundled).exists() { bundled.as_str() } else { "qpdf" };
+
+    let n = INTERMEDIATES.fetch_add(1, Ordering::Relaxed);
+    let argfile = std::env::temp_dir().join(format!("scripts-{}-{n}.qpdf", std::process::id()));
+    private_file(&argfile)
+        .and_then(|mut file| writeln!(file, "--encrypt\n{}\n{}\n256\n--", encryption.user, encryption.owner))
+        .map_err(|err| format!("could not write qpdf arguments: {err}"))?;
+
+    verbose!("encrypting {} with {program}", cmd.outfile);
+    let status = Command::new(program)
+        .arg(format!("@{}", argfile.display()))
         .arg(&cmd.outfile)
-        .status()
-}
\ No newline at end of file
+        .arg(encrypted)
+        .status();
+    let _ = fs::remove_file(&argfile);
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("qpdf exited with {status}")),
+        Err(err) => Err(format!("could not run {program}: {err}")),
+    }
+}
+
+
+#[cfg(unix)]
+fn private_file(path: &Path) -> std::io::Result<fs::File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)
+}
+
+#[cfg(not(unix))]
+fn private_file(path: &Path) -> std::io::Result<fs::File> {
+    fs::OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+
+pub fn render_pdf(script: &Script, cmd: &CmdInfo) -> Result<ExitStatus, HtmlError> {
+    // the native engine writes the pdf itself, there is no html to print
+    if cmd.engine == Engine::Native {
+        fs::write(&cmd.outfile, write_pdf(&typeset(script, cmd), cmd.watermark.as_deref()))?;
+        if let Some(encryption) = &cmd.encrypt {
+            encrypt_pdf(cmd, encryption)?;
+        }
+        return Ok(ExitStatus::default())
+    }
+    let mut out = BufWriter::new(fs::File::create(&cmd.html)?);
+    write_html(script, cmd, &mut out)?;
+    out.flush()?;
+    gen_pdf(cmd)
+}
+
+
+// the WinAnsi codes that differ from latin-1
+const WIN_ANSI: [(char, u8); 12] = [
+    ('€', 0x80), ('‚', 0x82), ('„', 0x84), ('…', 0x85), ('‘', 0x91), ('’', 0x92),
+    ('“', 0x93), ('”', 0x94), ('•', 0x95), ('–', 0x96), ('—', 0x97), ('™', 0x99),
+];
+
+
+// the standard courier faces need no embedding, text outside WinAnsi falls back to '?'
+fn win_ansi(c: char) -> u8 {
+    match c {
+        ' '..='~' => c as u8,
+        '\u{A0}'..='\u{FF}' => c as u32 as u8,
+        _ => WIN_ANSI.iter().find(|(w, _)| *w == c).map_or(b'?', |(_, b)| *b),
+    }
+}
+
+
+// codes outside WinAnsi come from fonts with their own encoding and cannot be read back
+pub(crate) fn from_win_ansi(b: u8) -> char {
+    match b {
+        b'\t'|b'\n'|b'\r' => ' ',
+        0x20..=0x7E|0xA0..=0xFF => b as char,
+        _ => WIN_ANSI.iter().find(|(_, w)| *w == b).map_or('\u{FFFD}', |(c, _)| *c),
+    }
+}
+
+
+fn pdf_string(text: &str) -> Vec<u8> {
+    let mut result = vec![b'('];
+    for c in text.chars().filter(|c| char_width(*c) > 0) {
+        match win_ansi(c) {
+            b @ (b'(' | b')' | b'\\') => result.extend([b'\\', b]),
+            b if b >= 0x80 => result.extend(format!("\\{b:03o}").bytes()),
+            b => result.push(b),
+        }
+    }
+    result.push(b')');
+    result
+}
+
+
+fn show_text(content: &mut Vec<u8>, font: usize, size: f32, x: f32, y: f32, text: &str) {
+    content.extend(format!("BT /F{} {size} Tf {x:.2} {y:.2} Td ", font + 1).bytes());
+    content.extend(pdf_string(text));
+    content.extend(b" Tj ET\n");
+}
+
+
+fn baseline(row: usize) -> f32 {
+    PAGE_HEIGHT - TOP - LEADING * (row + 1) as f32 + 3.0
+}
+
+
+fn page_content(sheet: &Sheet, watermark: Option<&str>) -> Vec<u8> {
+    let mut content = Vec::new();
+
+    if let Some(mark) = watermark {
+        let half = mark.chars().count() as f32 * 0.6 * 48.0 / 2.0 * std::f32::consts::FRAC_1_SQRT_2;
+        content.extend(format!("q 0.85 g BT /F2 48 Tf 0.7071 0.7071 -0.7071 0.7071 {:.2} {:.2} Tm ", PAGE_WIDTH / 2.0 - half, PAGE_HEIGHT / 2.0 - half).bytes());
+        content.extend(pdf_string(mark));
+        content.extend(b" Tj ET Q\n");
+        let footer = format!("Prepared for {mark}");
+        show_text(&mut content, 0, 8.0, (PAGE_WIDTH - footer.chars().count() as f32 * 4.8) / 2.0, 36.0, &footer);
+    }
+    if let Some(number) = sheet.number {
+        let number = format!("{number}.");
+        show_text(&mut content, 0, 12.0, (75 - number.len()) as f32 * ADVANCE, PAGE_HEIGHT - 36.0 - 9.0, &number);
+    }
+
+    for (i, row) in sheet.rows.iter().enumerate().take(LINES_PER_PAGE) {
+        let y = baseline(i);
+        for span in row {
+            let x = span.col as f32 * ADVANCE;
+            show_text(&mut content, span.style.bold as usize + 2 * span.style.italic as usize, 12.0, x, y, &span.text);
+            if span.style.underline {
+                let end = x + span.text.chars().map(char_width).sum::<usize>() as f32 * ADVANCE;
+                content.extend(format!("0.6 w {x:.2} {:.2} m {end:.2} {:.2} l S\n", y - 2.0, y - 2.0).bytes());
+            }
+        }
+    }
+
+    content
+}
+
+
+pub fn write_pdf(sheets: &[Sheet], watermark: Option<&str>) -> Vec<u8> {
+    let first_page = 3 + FONTS.len();
+    let outline = first_page + 2 * sheets.len();
+    let marks: Vec<(usize, f32, &str)> = sheets.iter().enumerate()
+        .flat_map(|(i, sheet)| sheet.marks.iter().map(move |(row, title)| (first_page + 2 * i, baseline(*row) + LEADING, title.as_str())))
+        .collect();
+
+    let mut objects: Vec<Vec<u8>> = Vec::new();
+    let outlines = if marks.is_empty() { String::new() } else { format!(" /Outlines {outline} 0 R /PageMode /UseOutlines") };
+    objects.push(format!("<< /Type /Catalog /Pages 2 0 R{outlines} >>").into_bytes());
+    let kids: Vec<String> = (0..sheets.len()).map(|i| format!("{} 0 R", first_page + 2 * i)).collect();
+    objects.push(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), sheets.len()).into_bytes());
+    for font in FONTS {
+        objects.push(format!("<< /Type /Font /Subtype /Type1 /BaseFont /{font} /Encoding /WinAnsiEncoding >>").into_bytes());
+    }
+
+    let fonts: Vec<String> = (0..FONTS.len()).map(|i| format!("/F{} {} 0 R", i + 1, i + 3)).collect();
+    for (i, sheet) in sheets.iter().enumerate() {
+        objects.push(format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] /Resources << /Font << {} >> >> /Contents {} 0 R >>",
+                             fonts.join(" "), first_page + 2 * i + 1).into_bytes());
+        let content = page_content(sheet, watermark);
+        let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+        stream.extend(content);
+        stream.extend(b"\nendstream");
+        objects.push(stream);
+    }
+
+    if !marks.is_empty() {
+        objects.push(format!("<< /Type /Outlines /First {} 0 R /Last {} 0 R /Count {} >>", outline + 1, outline + marks.len(), marks.len()).into_bytes());
+        for (i, (page, y, title)) in marks.iter().enumerate() {
+            let id = outline + 1 + i;
+            let mut item = b"<< /Title ".to_vec();
+            item.extend(pdf_string(title));
+            item.extend(format!(" /Parent {outline} 0 R").bytes());
+            if i > 0 {
+                item.extend(format!(" /Prev {} 0 R", id - 1).bytes());
+            }
+            if i + 1 < marks.len() {
+                item.extend(format!(" /Next {} 0 R", id + 1).bytes());
+            }
+            item.extend(format!(" /Dest [{page} 0 R /XYZ 0 {y:.2} null] >>").bytes());
+            objects.push(item);
+        }
+    }
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n", i + 1).bytes());
+        pdf.extend(object);
+        pdf.extend(b"\nendobj\n");
+    }
+
+    let xref = pdf.len();
+    pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).bytes());
+    for offset in offsets {
+        pdf.extend(format!("{offset:010} 00000 n \n").bytes());
+    }
+    pdf.extend(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n", objects.len() + 1).bytes());
+    pdf
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typeset::{ Span, Style };
+
+    #[test]
+    fn native() {
+        let sheet = Sheet{
+            number: Some(2),
+            rows: vec![vec![Span{ col: 15, text: "Café (open) ‘now’".to_string(), style: Style{ underline: true, ..Default::default() } }]],
+            marks: vec![(0, "1 INT. CAFÉ - DAY".to_string())],
+        };
+        let pdf = write_pdf(&[sheet], None);
+        let text = String::from_utf8_lossy(&pdf);
+
+        assert!(text.starts_with("%PDF-1.4\n") && text.ends_with("%%EOF\n"));
+        assert!(text.contains("(Caf\\351 \\(open\\) \\221now\\222) Tj"));
+        assert!(text.contains("/Count 1"));
+        assert!(text.contains("108.00 709.00 m"));
+
+        let xref: usize = text.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        assert!(text[xref..].starts_with("xref\n0 11\n"));
+        let second: usize = text[xref..].lines().nth(4).unwrap()[..10].parse().unwrap();
+        assert!(text[second..].starts_with("2 0 obj"));
+    }
+
+    #[test]
+    fn intermediates() {
+        let (first, second) = (temp_html(), temp_html());
+        assert_ne!(first, second);
+        assert!(Path::new(&first).starts_with(std::env::temp_dir()));
+        assert!(first.ends_with(".html"));
+    }
+
+    #[test]
+    fn passwords_redacted() {
+        let cmd = CmdInfo{ encrypt: Some(Encryption{ user: "secret".to_string(), owner: "owner".to_string() }), ..Default::default() };
+        let logged = format!("{cmd:?}");
+        assert!(logged.contains("Encryption { user: \"<redacted>\", owner: \"<redacted>\" }"));
+        assert!(!logged.contains("secret") && !logged.contains("owner\""));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn encryption() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let qpdf = dir.join("qpdf.exe");
+        let cmd = CmdInfo{ exe_loc: dir.display().to_string(), outfile: dir.join("out.pdf").display().to_string(), ..Default::default() };
+        let encryption = Encryption{ user: "secret".to_string(), owner: "owner".to_string() };
+
+        // the stand-in qpdf records its arguments and copies the input to the output
+        fs::write(&qpdf, format!("#!/bin/sh\ncat \"${{1#@}}\" > {0}/args\necho \"$@\" > {0}/argv\ncp \"$2\" \"$3\"\n", dir.display())).unwrap();
+        fs::set_permissions(&qpdf, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(&cmd.outfile, "plain").unwrap();
+        encrypt_pdf(&cmd, &encryption).expect("encryption failed");
+        assert_eq!(fs::read_to_string(&cmd.outfile).unwrap(), "plain");
+        assert_eq!(fs::read_to_string(dir.join("args")).unwrap(), "--encrypt\nsecret\nowner\n256\n--\n");
+        assert!(!fs::read_to_string(dir.join("argv")).unwrap().contains("secret"));
+        assert!(!Path::new(&format!("{}.encrypting", cmd.outfile)).exists());
+
+        fs::write(&qpdf, "#!/bin/sh\nexit 2\n").unwrap();
+        let err = encrypt_pdf(&cmd, &encryption).unwrap_err();
+        assert!(matches!(err, HtmlError::EncryptError(_)));
+        assert!(!Path::new(&cmd.outfile).exists());
+    }
+
+    #[test]
+    fn native_render() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let script = crate::parse("title\nsubtitle\nscene INT. A - DAY\ndirect Hi.").expect("parse failed");
+        let cmd = CmdInfo{
+            engine: Engine::Native,
+            exe_loc: dir.join("missing").display().to_string(),
+            html: dir.join("out.html").display().to_string(),
+            outfile: dir.join("out.pdf").display().to_string(),
+            watermark: Some("Alex".to_string()),
+            ..Default::default()
+        };
+
+        // neither html nor webkit is involved
+        assert!(render_pdf(&script, &cmd).expect("render failed").success());
+        assert!(fs::read(&cmd.outfile).unwrap().starts_with(b"%PDF-1.4"));
+        assert!(!Path::new(&cmd.html).exists());
+    }
+}
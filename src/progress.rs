@@ -0,0 +1,101 @@
+use std::{
+    io::{ IsTerminal, Write },
+    sync::Mutex,
+};
+use serde_json::json;
+use crate::{
+    logging::{ verbosity, Verbosity },
+    CmdInfo,
+};
+
+
+const BAR_WIDTH: usize = 24;
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    #[default]
+    Human,
+    Json,
+}
+
+
+// the label shown in front of the bar (the file in a batch) and how many characters are on screen
+static BAR: Mutex<(String, usize)> = Mutex::new((String::new(), 0));
+
+
+fn progress_bar(label: &str, phase: &str, percent: usize, scene: Option<&str>) -> String {
+    let filled = BAR_WIDTH * percent.min(100) / 100;
+    let scene = scene.map(|s| format!(" scene {s}")).unwrap_or_default();
+    format!("{label}[{}{}] {:>3}% {phase}{scene}", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled), percent.min(100))
+}
+
+
+// only drawn between progress lines on a terminal, --verbose output would tear it apart
+fn show_bar(cmd: &CmdInfo) -> bool {
+    cmd.progress == Progress::Human && verbosity() == Verbosity::Normal && std::io::stdout().is_terminal()
+}
+
+
+// the bar is redrawn in place with backspaces, which every console understands
+fn draw(text: &str) {
+    let mut bar = BAR.lock().unwrap();
+    let width = text.chars().count();
+    let padding = bar.1.saturating_sub(width);
+    print!("{}{text}{}{}", "\x08".repeat(bar.1), " ".repeat(padding), "\x08".repeat(padding));
+    let _ = std::io::stdout().flush();
+    bar.1 = width;
+}
+
+
+pub fn set_progress_label(label: &str) {
+    BAR.lock().unwrap().0 = label.to_string();
+}
+
+
+pub fn report(cmd: &CmdInfo, phase: &str, percent: usize, scene: Option<&str>) {
+    if cmd.progress == Progress::Json {
+        println!("{}", json!({ "event": "progress", "phase": phase, "percent": percent.min(100), "scene": scene }));
+    } else if show_bar(cmd) {
+        let label = BAR.lock().unwrap().0.clone();
+        draw(&progress_bar(&label, phase, percent, scene));
+    }
+}
+
+
+// erases the bar so the line it was drawn on can be completed
+pub fn finish_progress(cmd: &CmdInfo) {
+    if show_bar(cmd) && BAR.lock().unwrap().1 > 0 {
+        draw("");
+    }
+}
+
+
+// batch builds say which input each output belongs to once it is written or found up to date
+pub fn report_done(cmd: &CmdInfo, input: &str, output: &str) {
+    finish_progress(cmd);
+    if cmd.progress == Progress::Json {
+        println!("{}", json!({ "event": "done", "input": input, "output": output }));
+    }
+}
+
+
+pub fn report_error(cmd: &CmdInfo, phase: &str, message: &str) {
+    finish_progress(cmd);
+    if cmd.progress == Progress::Json {
+        println!("{}", json!({ "event": "error", "phase": phase, "message": message }));
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bar() {
+        assert_eq!(progress_bar("", "render", 50, Some("12")), "[############------------]  50% render scene 12");
+        assert_eq!(progress_bar("[2/3] a.txt ", "pdf", 120, None), "[2/3] a.txt [########################] 100% pdf");
+    }
+}
@@ -0,0 +1,258 @@
+use std::fs;
+use regex::{ Captures, Regex };
+use crate::{
+    front::front_matter,
+    html::HtmlError,
+    source::expand,
+    token::{ tokenize_body, tokenize, TokenKind },
+    CmdInfo,
+};
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rename {
+    Character{ from: String, to: String },
+    Text{ from: String, to: String },
+}
+
+
+// OLD=NEW pairs as given to --character and --replace, an empty OLD would match everywhere
+pub fn parse_renames(arg: &str, character: bool) -> Result<Vec<Rename>, String> {
+    let what = if character { "character rename" } else { "replacement" };
+    arg.split(',').map(|pair| {
+        let (from, to) = pair.split_once('=').ok_or(format!("expected OLD=NEW in {what}: {pair}"))?;
+        if from.trim().is_empty() {
+            return Err(format!("empty OLD in {what}: {pair}"))
+        }
+        Ok(if character {
+            Rename::Character{ from: from.trim().to_string(), to: to.trim().to_string() }
+        } else {
+            Rename::Text{ from: from.to_string(), to: to.to_string() }
+        })
+    }).collect()
+}
+
+
+fn recase(matched: &str, to: &str) -> String {
+    if !matched.chars().any(char::is_lowercase) {
+        to.to_uppercase()
+    } else if !matched.chars().any(char::is_uppercase) {
+        to.to_lowercase()
+    } else {
+        to.split(' ')
+            .map(|word| {
+                let mut chars = word.chars();
+                chars.next().map(|c| c.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect()).unwrap_or_default()
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+}
+
+
+fn names_character(kind: TokenKind, keyword: Option<&str>) -> bool {
+    match kind {
+        TokenKind::Character
+        |TokenKind::Action
+        |TokenKind::Dialogue
+        |TokenKind::Parenthetical
+        |TokenKind::SceneHeading => true,
+        TokenKind::Directive => matches!(keyword, Some("cast"|"alias"|"characters"|"aliases")),
+        _ => false,
+    }
+}
+
+
+// the top level key a front matter line belongs to, in toml every line after a table header is part of the table
+fn front_key<'a>(line: &'a str, indented: bool, key: Option<(&'a str, bool)>) -> Option<(&'a str, bool)> {
+    if let Some(table) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+        Some((table.trim(), true))
+    } else if indented || line.starts_with(['-', ']', '}']) || key.is_some_and(|(_, table)| table) {
+        key
+    } else {
+        line.split([':', '=']).next().map(|k| (k.trim(), false))
+    }
+}
+
+
+fn is_content(kind: TokenKind) -> bool {
+    matches!(kind,
+        TokenKind::Title
+        |TokenKind::Subtitle
+        |TokenKind::SceneHeading
+        |TokenKind::Header
+        |TokenKind::Action
+        |TokenKind::Character
+        |TokenKind::Parenthetical
+        |TokenKind::Dialogue
+        |TokenKind::Transition)
+}
+
+
+pub fn rename(src: &str, renames: &[Rename], header: bool) -> (String, usize) {
+    let characters: Vec<(Regex, &str)> = renames.iter()
+        .filter_map(|r| match r {
+            Rename::Character{ from, to } => Some((Regex::new(&format!(r"(?i)\b{}\b", regex::escape(from))).unwrap(), to.as_str())),
+            Rename::Text{ .. } => None,
+        })
+        .collect();
+
+    let tokens = if header { tokenize(src) } else { tokenize_body(src) };
+    let (front, lines) = front_matter(src).ok().flatten().filter(|_| header).unwrap_or_default();
+    let mut result = String::with_capacity(src.len());
+    let mut count = 0;
+    let mut last = 0;
+    let mut keyword = None;
+    let mut key = None;
+
+    for token in tokens {
+        let text = &src[token.span.clone()];
+        if token.kind == TokenKind::Keyword {
+            keyword = Some((token.line, front.keywords.get(text).map_or(text, String::as_str)));
+            continue
+        }
+        let keyword = if token.line > 1 && token.line < lines {
+            let indented = !src[..token.span.start].ends_with('\n');
+            key = front_key(text, indented, key);
+            key.map(|(k, _)| k)
+        } else {
+            keyword.filter(|(line, _)| *line == token.line).map(|(_, k)| k)
+        };
+
+        let mut replaced = text.to_string();
+        if names_character(token.kind, keyword) {
+            for (pattern, to) in &characters {
+                count += pattern.find_iter(&replaced).count();
+                replaced = pattern.replace_all(&replaced, |c: &Captures| recase(&c[0], to)).into_owned();
+            }
+        }
+        if is_content(token.kind) {
+            for r in renames {
+                if let Rename::Text{ from, to } = r {
+                    count += replaced.matches(from.as_str()).count();
+                    replaced = replaced.replace(from.as_str(), to);
+                }
+            }
+        }
+
+        if replaced != text {
+            result.push_str(&src[last..token.span.start]);
+            result.push_str(&replaced);
+            last = token.span.end;
+        }
+    }
+
+    result.push_str(&src[last..]);
+    (result, count)
+}
+
+
+pub fn gen_rename(cmd: &CmdInfo) -> Result<Vec<(String, usize)>, HtmlError> {
+    let files = expand(&cmd.infile).map_err(|d| HtmlError::ParseError(vec![d]))?.files;
+    let mut changed = Vec::new();
+
+    for (i, file) in files.into_iter().enumerate() {
+        let src = fs::read_to_string(&file)?;
+        let (renamed, count) = rename(&src, &cmd.renames, i == 0);
+        if count > 0 {
+            if !cmd.check {
+                fs::write(&file, renamed)?;
+            }
+            changed.push((file, count));
+        }
+    }
+
+    Ok(changed)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn characters() {
+        let renames = [Rename::Character{ from: "BOB".into(), to: "ROBERT".into() }];
+        let src = "Bob's Day\n\
+                   Pilot\n\
+                   cast bob, alice\n\
+                   alias BOBBY = BOB\n\
+                   define KEY = BOB\n\
+                   scene INT. BOB'S FLAT - DAY * where bob lives\n\
+                   direct Bob enters. BOBBY follows.\n\
+                   bob & ALICE: (to Bob) Hi \\\n\
+                   bob\n\
+                   trans CUT TO BOB";
+
+        let (renamed, count) = rename(src, &renames, true);
+        assert_eq!(renamed, "Bob's Day\n\
+                             Pilot\n\
+                             cast robert, alice\n\
+                             alias BOBBY = ROBERT\n\
+                             define KEY = BOB\n\
+                             scene INT. ROBERT'S FLAT - DAY * where bob lives\n\
+                             direct Robert enters. BOBBY follows.\n\
+                             robert & ALICE: (to Robert) Hi \\\n\
+                             robert\n\
+                             trans CUT TO BOB");
+        assert_eq!(count, 7);
+    }
+
+    #[test]
+    fn arguments() {
+        assert_eq!(parse_renames(" Bob = Robert ,Al=Alan", true).unwrap(), [
+            Rename::Character{ from: "Bob".into(), to: "Robert".into() },
+            Rename::Character{ from: "Al".into(), to: "Alan".into() },
+        ]);
+        assert_eq!(parse_renames("the =a ", false).unwrap(), [Rename::Text{ from: "the ".into(), to: "a ".into() }]);
+        assert_eq!(parse_renames("=X", false), Err("empty OLD in replacement: =X".to_string()));
+        assert_eq!(parse_renames("Bob=Rob, =X", true), Err("empty OLD in character rename:  =X".to_string()));
+        assert!(parse_renames("Bob", true).is_err());
+    }
+
+    #[test]
+    fn text() {
+        let renames = [Rename::Text{ from: "scene".into(), to: "shot".into() }];
+        let (renamed, count) = rename("scene INT. LOC - DAY\ndirect The scene opens. * scene one", &renames, false);
+        assert_eq!(renamed, "scene INT. LOC - DAY\ndirect The shot opens. * scene one");
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn front_matter() {
+        let renames = [Rename::Character{ from: "BOB".into(), to: "ROBERT".into() }];
+        let src = "---\n\
+                   title: Bob's Day\n\
+                   author: Bob Smith\n\
+                   characters:\n\
+                   - Bob\n\
+                   aliases: { BOBBY: BOB }\n\
+                   keywords: { besetzung: cast }\n\
+                   ---\n\
+                   besetzung BOB\n\
+                   scene INT. FLAT - DAY\n\
+                   BOB: Hi";
+        let (renamed, count) = rename(src, &renames, true);
+        assert_eq!(renamed, "---\n\
+                             title: Bob's Day\n\
+                             author: Bob Smith\n\
+                             characters:\n\
+                             - Robert\n\
+                             aliases: { BOBBY: ROBERT }\n\
+                             keywords: { besetzung: cast }\n\
+                             ---\n\
+                             besetzung ROBERT\n\
+                             scene INT. FLAT - DAY\n\
+                             ROBERT: Hi");
+        assert_eq!(count, 4);
+
+        let src = "+++\ntitle = \"Bob\"\ncharacters = [\"Bob\"]\n[aliases]\nBOBBY = \"BOB\"\n+++\nBOB: Hi";
+        let (renamed, _) = rename(src, &renames, true);
+        assert_eq!(renamed, "+++\ntitle = \"Bob\"\ncharacters = [\"Robert\"]\n[aliases]\nBOBBY = \"ROBERT\"\n+++\nROBERT: Hi");
+
+        // a script without a title starts renaming on its first line
+        let (renamed, _) = rename("scene INT. BOB'S FLAT - DAY\nBOB: Hi", &renames, true);
+        assert_eq!(renamed, "scene INT. ROBERT'S FLAT - DAY\nROBERT: Hi");
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    fs,
+    io::{ BufWriter, Write },
+    path::Path,
+};
+use crate::{
+    ast::Script,
+    breakdown::schedule_csv,
+    html::{ write_html, HtmlError },
+    latex::latex,
+    markdown::markdown,
+    outline::cards_html,
+    parse::*,
+    pdf::write_pdf,
+    rtf::rtf,
+    speech::{ read_text, Normalize },
+    typeset::typeset,
+    CmdInfo,
+    DialogueNumbering,
+    Emit,
+    Engine,
+};
+
+
+pub trait Renderer {
+    fn render(&self, script: &Script, out: &mut dyn Write) -> Result<(), HtmlError>;
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Format {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    pub emit: Emit,
+    pub label: &'static str,
+}
+
+
+// a new output format is a variant of 'Emit', an entry here and a renderer below
+pub const FORMATS: [Format; 9] = [
+    Format{ name: "pdf", extensions: &["pdf"], emit: Emit::Pdf, label: "pdf" },
+    Format{ name: "ast-json", extensions: &["json"], emit: Emit::AstJson, label: "ast" },
+    Format{ name: "html", extensions: &["html", "htm"], emit: Emit::Html, label: "preview" },
+    Format{ name: "dialogue-tsv", extensions: &["tsv"], emit: Emit::DialogueTsv, label: "dialogue list" },
+    Format{ name: "read", extensions: &[], emit: Emit::Read, label: "table read" },
+    Format{ name: "schedule-csv", extensions: &["csv"], emit: Emit::ScheduleCsv, label: "schedule" },
+    Format{ name: "latex", extensions: &["tex"], emit: Emit::Latex, label: "latex" },
+    Format{ name: "rtf", extensions: &["rtf"], emit: Emit::Rtf, label: "rtf" },
+    Format{ name: "markdown", extensions: &["md", "markdown"], emit: Emit::Markdown, label: "markdown" },
+];
+
+
+impl Emit {
+    pub fn from_name(name: &str) -> Option<Self> {
+        FORMATS.iter().find(|f| f.name == name).map(|f| f.emit)
+    }
+
+    pub fn from_output(path: &str) -> Option<Self> {
+        let ext = Path::new(path).extension()?.to_str()?.to_lowercase();
+        FORMATS.iter().find(|f| f.extensions.contains(&ext.as_str())).map(|f| f.emit)
+    }
+
+    pub fn format(self) -> &'static Format {
+        FORMATS.iter().find(|f| f.emit == self).unwrap_or(&FORMATS[0])
+    }
+}
+
+
+struct HtmlPage<'a>(&'a CmdInfo);
+
+impl Renderer for HtmlPage<'_> {
+    fn render(&self, script: &Script, out: &mut dyn Write) -> Result<(), HtmlError> {
+        if self.0.cards {
+            out.write_all(cards_html(script)?.as_bytes())?;
+            return Ok(())
+        }
+        write_html(script, self.0, out)
+    }
+}
+
+
+struct NativePdf<'a>(&'a CmdInfo);
+
+impl Renderer for NativePdf<'_> {
+    fn render(&self, script: &Script, out: &mut dyn Write) -> Result<(), HtmlError> {
+        out.write_all(&write_pdf(&typeset(script, self.0), self.0.watermark.as_deref()))?;
+        Ok(())
+    }
+}
+
+
+struct AstJson;
+
+impl Renderer for AstJson {
+    fn render(&self, script: &Script, out: &mut dyn Write) -> Result<(), HtmlError> {
+        serde_json::to_writer_pretty(out, script)?;
+        Ok(())
+    }
+}
+
+
+struct DialogueTsv(DialogueNumbering);
+
+impl Renderer for DialogueTsv {
+    fn render(&self, script: &Script, out: &mut dyn Write) -> Result<(), HtmlError> {
+        out.write_all(dialogue_tsv(&script.dialogue_cues(self.0)).as_bytes())?;
+        Ok(())
+    }
+}
+
+
+struct TableRead(Normalize);
+
+impl Renderer for TableRead {
+    fn render(&self, script: &Script, out: &mut dyn Write) -> Result<(), HtmlError> {
+        out.write_all(read_text(script, &self.0).as_bytes())?;
+        Ok(())
+    }
+}
+
+
+struct ScheduleCsv;
+
+impl Renderer for ScheduleCsv {
+    fn render(&self, script: &Script, out: &mut dyn Write) -> Result<(), HtmlError> {
+        out.write_all(schedule_csv(script).as_bytes())?;
+        Ok(())
+    }
+}
+
+
+struct Latex;
+
+impl Renderer for Latex {
+    fn render(&self, script: &Script, out: &mut dyn Write) -> Result<(), HtmlError> {
+        out.write_all(latex(script)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+
+struct Rtf;
+
+impl Renderer for Rtf {
+    fn render(&self, script: &Script, out: &mut dyn Write) -> Result<(), HtmlError> {
+        out.write_all(rtf(script)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+
+struct Markdown;
+
+impl Renderer for Markdown {
+    fn render(&self, script: &Script, out: &mut dyn Write) -> Result<(), HtmlError> {
+        out.write_all(markdown(script)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+
+// webkit pdfs are printed from the html by an external program, so they have no renderer
+pub fn renderer(cmd: &CmdInfo) -> Option<Box<dyn Renderer + '_>> {
+    Some(match cmd.emit {
+        Emit::Pdf if cmd.engine == Engine::Native => Box::new(NativePdf(cmd)),
+        Emit::Pdf => return None,
+        Emit::Html => Box::new(HtmlPage(cmd)),
+        Emit::AstJson => Box::new(AstJson),
+        Emit::DialogueTsv => Box::new(DialogueTsv(cmd.number_dialogue.unwrap_or_default())),
+        Emit::Read => Box::new(TableRead(cmd.normalize)),
+        Emit::ScheduleCsv => Box::new(ScheduleCsv),
+        Emit::Latex => Box::new(Latex),
+        Emit::Rtf => Box::new(Rtf),
+        Emit::Markdown => Box::new(Markdown),
+    })
+}
+
+
+pub fn gen_output(cmd: &CmdInfo) -> Result<Vec<Diagnostic>, HtmlError> {
+    let renderer = renderer(cmd).ok_or_else(|| std::io::Error::other("webkit pdfs are printed from html"))?;
+    let script = read_script(cmd)?;
+
+    let mut out = BufWriter::new(fs::File::create(&cmd.outfile)?);
+    renderer.render(&script, &mut out)?;
+    out.flush()?;
+    Ok(script.warnings)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        assert_eq!(Emit::from_name("dialogue-tsv"), Some(Emit::DialogueTsv));
+        assert_eq!(Emit::from_name("docx"), None);
+        assert_eq!(Emit::from_output("out/Script.HTML"), Some(Emit::Html));
+        assert_eq!(Emit::from_output("schedule.csv"), Some(Emit::ScheduleCsv));
+        assert_eq!(Emit::from_output("script"), None);
+        assert_eq!(Emit::Read.format().label, "table read");
+
+        let script = parse("title\nsubtitle\nscene INT. A - DAY\nALEX: Hi.\nend").expect("parse failed");
+        let cmd = CmdInfo{ emit: Emit::DialogueTsv, ..Default::default() };
+        let mut out = Vec::new();
+        renderer(&cmd).expect("no renderer").render(&script, &mut out).expect("render failed");
+        assert_eq!(String::from_utf8(out).unwrap(), "number\tscene\tcharacter\ttext\n1\t1\tALEX\tHi.\n");
+        assert!(renderer(&CmdInfo::default()).is_none());
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    fs,
+    path::Path,
+};
+use serde::{ Serialize, Deserialize };
+use crate::{
+    ast::Script,
+    html::HtmlError,
+    parse::{ read_located, Diagnostic, Locate },
+    storage::Storage,
+    CmdInfo,
+};
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReviewItem {
+    pub kind: String,
+    pub file: Option<String>,
+    pub line: usize,
+    pub message: String,
+    #[serde(default)]
+    pub scene: Option<String>,
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sidecar {
+    pub resolved: Vec<ReviewItem>,
+}
+
+impl Sidecar {
+    pub fn path(cmd: &CmdInfo) -> String {
+        format!("{}.notes.json", cmd.file_root)
+    }
+
+    pub fn load(storage: &dyn Storage, path: &str) -> Self {
+        storage.load(Path::new(path))
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &dyn Storage, path: &str) -> Result<(), HtmlError> {
+        storage.save(Path::new(path), &serde_json::to_string_pretty(self)?)
+    }
+
+    // lines move as the script is edited, an item stays resolved as long as its scene and message do
+    pub fn is_resolved(&self, item: &ReviewItem) -> bool {
+        self.resolved.iter().any(|r| r.kind == item.kind && r.scene == item.scene && r.message == item.message)
+    }
+}
+
+
+pub fn review_items(cmd: &CmdInfo) -> Result<Vec<ReviewItem>, HtmlError> {
+    let (script, locate) = read_located(cmd)?;
+    Ok(script_items(&script, &locate))
+}
+
+
+pub(crate) fn script_items(script: &Script, locate: &Locate) -> Vec<ReviewItem> {
+    // each item belongs to the last scene heading before it in the same file
+    let headings: Vec<(Diagnostic, &str)> = script.scenes.iter()
+        .map(|s| (locate(Diagnostic::new(s.line, "")), s.heading.as_str()))
+        .collect();
+    let item = |kind: &str, at: Diagnostic| ReviewItem{
+        kind: kind.to_string(),
+        scene: headings.iter().rev()
+            .find(|(h, _)| h.file == at.file && h.line <= at.line)
+            .map(|(_, heading)| heading.to_string()),
+        file: at.file,
+        line: at.line,
+        message: at.message,
+    };
+
+    let warnings = script.warnings.iter().map(|w| item("warning", w.clone()));
+    let todos = script.todos().into_iter().map(|t| {
+        let message = if t.text.is_empty() { format!("TODO in scene {}", t.label) } else { t.text };
+        item("todo", locate(Diagnostic::new(t.line, message)))
+    });
+
+    let mut items: Vec<ReviewItem> = warnings.chain(todos).collect();
+    items.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    items
+}
+
+
+pub fn context(path: &str, line: usize, radius: usize) -> Vec<(usize, String)> {
+    let Ok(src) = fs::read_to_string(Path::new(path)) else {
+        return Vec::new()
+    };
+
+    let lines = line.saturating_sub(radius).max(1)..=line + radius;
+    src.lines()
+        .enumerate()
+        .map(|(i, text)| (i + 1, text.to_string()))
+        .filter(|(n, _)| lines.contains(n))
+        .collect()
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileStorage;
+
+    #[test]
+    fn items() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let root = dir.join("script").display().to_string();
+        fs::write(format!("{root}.txt"), "title\nsubtitle\nscene EXT. LOC - DAY\nTODO fix the ending\ndirect Hi.\n").unwrap();
+
+        let cmd = CmdInfo{ infile: format!("{root}.txt"), file_root: root.clone(), check: true, ..Default::default() };
+        let items = review_items(&cmd).expect("review failed");
+        assert_eq!(items.len(), 2);
+        assert_eq!((items[0].kind.as_str(), items[0].line), ("todo", 4));
+        assert_eq!(items[1].kind, "warning");
+
+        let mut sidecar = Sidecar::default();
+        sidecar.resolved.push(items[0].clone());
+        sidecar.save(&FileStorage, &Sidecar::path(&cmd)).unwrap();
+        let sidecar = Sidecar::load(&FileStorage, &Sidecar::path(&cmd));
+        assert!(sidecar.is_resolved(&items[0]));
+        assert!(!sidecar.is_resolved(&items[1]));
+        assert_eq!(items[0].scene.as_deref(), Some("EXT. LOC - DAY"));
+
+        // a resolved item survives lines added above it, but not a move to another scene
+        fs::write(format!("{root}.txt"), "title\nsubtitle\nscene EXT. LOC - DAY\ndirect Hello.\nTODO fix the ending\nscene INT. CAR - DAY\nTODO fix the ending\n").unwrap();
+        let moved = review_items(&cmd).expect("review failed");
+        assert_eq!((moved[0].line, sidecar.is_resolved(&moved[0])), (5, true));
+        assert_eq!((moved[1].line, sidecar.is_resolved(&moved[1])), (7, false));
+
+        let lines: Vec<usize> = context(&cmd.infile, 1, 2).into_iter().map(|(n, _)| n).collect();
+        assert_eq!(lines, vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt::Write;
+use crate::{
+    ast::*,
+    html::{ markup, HtmlError },
+};
+
+
+// letter paper in twips with the usual 1.5 inch binding margin
+const PAGE: &str = "\\paperw12240\\paperh15840\\margl2160\\margr1440\\margt1440\\margb1440";
+
+// named paragraph styles so word processors and screenwriting tools can map them back to elements
+const STYLES: [(&str, &str); 7] = [
+    ("Scene Heading", "\\sb240\\keepn"),
+    ("Action", "\\sb240"),
+    ("Character", "\\sb240\\li3168\\keepn"),
+    ("Parenthetical", "\\li2304\\ri2880\\keepn"),
+    ("Dialogue", "\\li1440\\ri2160"),
+    ("Transition", "\\sb240\\qr"),
+    ("Centered", "\\sb240\\qc"),
+];
+
+const HEADING: usize = 1;
+const ACTION: usize = 2;
+const CHARACTER: usize = 3;
+const PARENTHETICAL: usize = 4;
+const DIALOGUE: usize = 5;
+const TRANSITION: usize = 6;
+const CENTERED: usize = 7;
+
+// markup is swapped for private use characters first so the escaping below leaves the groups alone
+const TAGS: [(&str, &str); 3] = [("\u{E010}", "\u{E013}"), ("\u{E011}", "\u{E013}"), ("\u{E012}", "\u{E013}")];
+
+
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\'|'{'|'}' => {
+                result.push('\\');
+                result.push(c);
+            }
+            '\u{E010}' => result.push_str("{\\b "),
+            '\u{E011}' => result.push_str("{\\i "),
+            '\u{E012}' => result.push_str("{\\ul "),
+            '\u{E013}' => result.push('}'),
+            c if c.is_ascii() => result.push(c),
+            // unicode escapes are signed 16 bit, with '?' as the fallback for old readers
+            c => for unit in c.encode_utf16(&mut [0; 2]) {
+                let _ = write!(result, "\\u{}?", *unit as i16);
+            }
+        }
+    }
+    result
+}
+
+
+fn inline(text: &str) -> String {
+    escape(&markup(text, TAGS))
+}
+
+
+fn para(style: usize, text: &str) -> String {
+    format!("{{\\pard\\plain\\s{style}{}\\f0\\fs24 {text}\\par}}\n", STYLES[style - 1].1)
+}
+
+
+// notes, TODOs and beats are left out, submissions carry only what is on the page
+fn render_element(kind: &Kind) -> String {
+    match kind {
+        Kind::Montage => para(ACTION, "BEGIN MONTAGE:"),
+        Kind::MonEnd => para(ACTION, "END MONTAGE."),
+        Kind::PageBreak => "\\page\n".to_string(),
+        Kind::End => para(TRANSITION, "FADE OUT.") + &para(CENTERED, "{\\ul THE END}"),
+        Kind::Todo{ .. }|Kind::Note{ .. }|Kind::Section{ .. }|Kind::Synopsis{ .. } => String::new(),
+        Kind::Direct{ text } => para(ACTION, &inline(text)),
+        Kind::Parens{ text } => para(PARENTHETICAL, &format!("({})", inline(text))),
+        Kind::Speech{ text } => para(DIALOGUE, &inline(text)),
+        Kind::Lyrics{ text } => para(DIALOGUE, &format!("{{\\i {}}}", text.split(" / ").map(inline).collect::<Vec<_>>().join("\\line "))),
+        Kind::Subhead{ text } => para(HEADING, &escape(&text.to_uppercase())),
+        Kind::Trans{ text } => para(TRANSITION, &escape(&text.to_uppercase())),
+        Kind::Chyron{ text } => para(ACTION, &format!("CHYRON: {}", inline(text))),
+        Kind::Sfx{ text } => para(ACTION, &format!("SFX: {}", inline(&text.to_uppercase()))),
+        Kind::Music{ text } => para(ACTION, &format!("MUSIC: {}", inline(&text.to_uppercase()))),
+        Kind::Header{ text } => para(ACTION, &format!("{{\\b {}}}", escape(text))),
+        Kind::ActTag{ text }|Kind::Act{ text } => para(CENTERED, &format!("{{\\b\\ul {}}}", escape(text))),
+        Kind::ActEnd{ text } => para(CENTERED, &format!("{{\\ul END OF {}}}", escape(text))),
+        Kind::Episode{ text } => para(CENTERED, &format!("{{\\b {}}}", escape(text))),
+        Kind::ComicPage{ number, text } if text.is_empty() => para(HEADING, &format!("{{\\ul PAGE {number}}}")),
+        Kind::ComicPage{ number, text } => para(HEADING, &format!("{{\\ul PAGE {number}}} - {}", escape(&text.to_uppercase()))),
+        Kind::Panel{ number, text } if text.is_empty() => para(ACTION, &format!("PANEL {number}")),
+        Kind::Panel{ number, text } => para(ACTION, &format!("PANEL {number}")) + &para(ACTION, &inline(text)),
+        Kind::Caption{ text } => para(CHARACTER, "CAPTION") + &para(DIALOGUE, &inline(text)),
+        Kind::Dialogue(dialogue) => {
+            let mut result = para(CHARACTER, &escape(&dialogue.name.to_uppercase()));
+            for line in &dialogue.lines {
+                result.push_str(&match line {
+                    DialogueLine::Parens(text) => para(PARENTHETICAL, &inline(text)),
+                    DialogueLine::Speech(text) => para(DIALOGUE, &inline(text)),
+                });
+            }
+            result
+        }
+    }
+}
+
+
+pub fn rtf(script: &Script) -> Result<String, HtmlError> {
+    let mut result = String::from("{\\rtf1\\ansi\\deff0\n{\\fonttbl{\\f0\\fmodern Courier New;}}\n{\\stylesheet{\\s0\\f0\\fs24 Normal;}");
+    for (i, (name, format)) in STYLES.iter().enumerate() {
+        write!(result, "{{\\s{}{format}\\f0\\fs24 {name};}}", i + 1)?;
+    }
+    writeln!(result, "}}\n{PAGE}")?;
+
+    result.push_str(&para(CENTERED, &format!("{{\\b {}}}", escape(&script.title.to_uppercase()))));
+    if !script.subtitle.is_empty() {
+        result.push_str(&para(CENTERED, &escape(&script.subtitle)));
+    }
+    if let Some(author) = &script.author {
+        result.push_str(&para(CENTERED, "by"));
+        result.push_str(&para(CENTERED, &escape(author)));
+    }
+    result.push_str("\\page\n");
+
+    for e in &script.prelude {
+        result.push_str(&render_element(&e.kind));
+    }
+    for scene in &script.scenes {
+        if scene.omitted {
+            result.push_str(&para(HEADING, &format!("SCENE {} OMITTED", escape(&script.scene_label(&scene.display_number())))));
+        } else {
+            result.push_str(&para(HEADING, &escape(&scene.heading.to_uppercase())));
+        }
+        for e in &scene.elements {
+            result.push_str(&render_element(&e.kind));
+        }
+    }
+
+    result.push_str("}\n");
+    Ok(result)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn document() {
+        let script = parse("title\nsubtitle\nscene INT. CAFÉ - DAY\nnote cut this?\nALEX: (quietly) The {*real*} one.\nend").expect("parse failed");
+        let rtf = rtf(&script).expect("render failed");
+
+        assert!(rtf.starts_with("{\\rtf1\\ansi\\deff0\n{\\fonttbl{\\f0\\fmodern Courier New;}}\n{\\stylesheet{\\s0\\f0\\fs24 Normal;}{\\s1\\sb240\\keepn\\f0\\fs24 Scene Heading;}"));
+        assert!(rtf.contains("{\\pard\\plain\\s1\\sb240\\keepn\\f0\\fs24 INT. CAF\\u201? - DAY\\par}\n"));
+        assert!(rtf.contains("{\\pard\\plain\\s3\\sb240\\li3168\\keepn\\f0\\fs24 ALEX\\par}\n\
+                              {\\pard\\plain\\s4\\li2304\\ri2880\\keepn\\f0\\fs24 (quietly)\\par}\n\
+                              {\\pard\\plain\\s5\\li1440\\ri2160\\f0\\fs24 The \\{{\\i real}\\} one.\\par}\n"));
+        assert!(!rtf.contains("cut this"));
+        assert!(rtf.ends_with("{\\ul THE END}\\par}\n}\n"));
+        assert_eq!(escape("😀"), "\\u-10179?\\u-8704?");
+    }
+}
@@ -0,0 +1,220 @@
+use std::fs;
+use crate::{
+    ast::*,
+    html::HtmlError,
+    parse::{ comment_start, parse, strip_continuation, trim_ignored, Diagnostic },
+    source::has_includes,
+    token::{ tokenize_body, TokenKind },
+    CmdInfo,
+};
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SceneEdit {
+    InsertAfter{ number: String, file: String },
+    Delete{ number: String },
+}
+
+
+// the letters count like spreadsheet columns, so Z rolls over to AA
+fn next_label(after: &str, used: &[String]) -> String {
+    let number = after.trim_end_matches(|c: char| c.is_ascii_uppercase());
+    let mut letters: Vec<u8> = after[number.len()..].bytes().collect();
+    match letters.iter().rposition(|&c| c != b'Z') {
+        Some(i) => {
+            letters[i] += 1;
+            letters[i + 1..].fill(b'A');
+        }
+        None => letters = vec![b'A'; letters.len() + 1],
+    }
+    let label = format!("{number}{}", String::from_utf8(letters).unwrap());
+    if used.contains(&label) {
+        next_label(&label, used)
+    } else {
+        label
+    }
+}
+
+
+fn lock_line(line: &str, label: &str) -> String {
+    let end = comment_start(line).unwrap_or(line.len());
+    let (heading, comment) = line.split_at(end);
+    let space = if comment.is_empty() { "" } else { " " };
+    format!("{} #{label}#{space}{comment}", heading.trim_end())
+}
+
+
+// the last physical line of the segment starting at 'start', following '\\' continuations
+fn last_line(lines: &[String], start: usize) -> usize {
+    let mut end = start;
+    while strip_continuation(trim_ignored((0, &lines[end])).1).is_some() {
+        match (end + 1..lines.len()).find(|&i| !trim_ignored((0, &lines[i])).1.is_empty()) {
+            Some(next) => end = next,
+            None => break,
+        }
+    }
+    end
+}
+
+
+// a scene runs from its heading to its last element, so act, episode and closure lines around it are kept
+fn scene_lines(script: &Script, lines: &[String]) -> Vec<(usize, usize)> {
+    script.scenes.iter()
+        .map(|scene| {
+            let last = scene.elements.iter()
+                .take_while(|e| !e.kind.is_structure())
+                .last()
+                .map_or(scene.line, |e| e.line);
+            (scene.line - 1, last_line(lines, last - 1) + 1)
+        })
+        .collect()
+}
+
+
+pub fn edit_scenes(src: &str, edit: &SceneEdit, insert: &str) -> Result<String, HtmlError> {
+    let script = parse(src).map_err(HtmlError::ParseError)?;
+    let mut lines: Vec<String> = src.lines().map(str::to_string).collect();
+    let spans = scene_lines(&script, &lines);
+
+    let number = match edit {
+        SceneEdit::InsertAfter{ number, .. }|SceneEdit::Delete{ number } => number,
+    };
+    let index = script.scenes.iter()
+        .position(|s| s.number > 0 && s.display_number() == *number)
+        .ok_or_else(|| HtmlError::SceneNotFound(number.clone()))?;
+
+    for scene in script.scenes.iter().filter(|s| s.number > 0 && s.locked.is_none()) {
+        let end = last_line(&lines, scene.line - 1);
+        let line = &mut lines[end];
+        *line = lock_line(line, &scene.display_number());
+    }
+
+    match edit {
+        SceneEdit::Delete{ number } => {
+            let (start, end) = spans[index];
+            lines.splice(start..end, [format!("omitted {number}")]);
+        }
+        SceneEdit::InsertAfter{ number, .. } => {
+            let used: Vec<String> = script.scenes.iter().map(Scene::display_number).collect();
+            let mut label = number.clone();
+            let mut added: Vec<String> = insert.lines().map(str::to_string).collect();
+
+            let headings: Vec<usize> = tokenize_body(insert).into_iter()
+                .filter(|t| t.kind == TokenKind::SceneHeading)
+                .map(|t| t.line - 1)
+                .collect();
+            if headings.first() != Some(&0) {
+                return Err(HtmlError::ParseError(vec![Diagnostic::new(1, "inserted text must start with a scene heading")]))
+            }
+            for line in headings {
+                label = next_label(&label, &used);
+                added[line] = lock_line(&added[line], &label);
+            }
+
+            let last = script.scenes[index..].iter().take_while(|s| s.number == script.scenes[index].number).count();
+            let (_, end) = spans[index + last - 1];
+            lines.splice(end..end, added);
+        }
+    }
+
+    let mut result = lines.join("\n");
+    if src.ends_with('\n') {
+        result.push('\n');
+    }
+    // the source is rewritten in place, so an edit that breaks it is never written
+    parse(&result).map_err(HtmlError::ParseError)?;
+    Ok(result)
+}
+
+
+pub fn gen_scene_edit(cmd: &CmdInfo, edit: &SceneEdit) -> Result<(), HtmlError> {
+    let src = fs::read_to_string(&cmd.infile)?;
+    if has_includes(&src) {
+        return Err(HtmlError::Unsupported("scene editing does not support scripts with includes"))
+    }
+
+    let insert = match edit {
+        SceneEdit::InsertAfter{ file, .. } => fs::read_to_string(file)?,
+        SceneEdit::Delete{ .. } => String::new(),
+    };
+
+    fs::write(&cmd.infile, edit_scenes(&src, edit, &insert)?)?;
+    Ok(())
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = "title\n\
+                       subtitle\n\
+                       scene EXT. LOC - DAY\n\
+                       direct One.\n\
+                       scene INT. CAR - DAY * parked\n\
+                       direct Two.\n\
+                       scene EXT. ROAD - NIGHT\n\
+                       direct Three.\n\
+                       end\n";
+
+    #[test]
+    fn delete() {
+        let edited = edit_scenes(SRC, &SceneEdit::Delete{ number: "2".into() }, "").expect("delete failed");
+        assert_eq!(edited, "title\n\
+                            subtitle\n\
+                            scene EXT. LOC - DAY #1#\n\
+                            direct One.\n\
+                            omitted 2\n\
+                            scene EXT. ROAD - NIGHT #3#\n\
+                            direct Three.\n\
+                            end\n");
+    }
+
+    #[test]
+    fn insert_after() {
+        let edit = SceneEdit::InsertAfter{ number: "2".into(), file: String::new() };
+        let edited = edit_scenes(SRC, &edit, "scene INT. BUS - DAY\ndirect New.").expect("insert failed");
+        assert_eq!(edited, "title\n\
+                            subtitle\n\
+                            scene EXT. LOC - DAY #1#\n\
+                            direct One.\n\
+                            scene INT. CAR - DAY #2# * parked\n\
+                            direct Two.\n\
+                            scene INT. BUS - DAY #2A#\n\
+                            direct New.\n\
+                            scene EXT. ROAD - NIGHT #3#\n\
+                            direct Three.\n\
+                            end\n");
+
+        let edit = SceneEdit::InsertAfter{ number: "2".into(), file: String::new() };
+        let again = edit_scenes(&edited, &edit, "scene INT. TRAIN - DAY").expect("insert failed");
+        assert!(again.contains("scene INT. BUS - DAY #2A#\ndirect New.\nscene INT. TRAIN - DAY #2B#\nscene EXT. ROAD"));
+
+        assert!(matches!(edit_scenes(SRC, &SceneEdit::Delete{ number: "9".into() }, ""), Err(HtmlError::SceneNotFound(_))));
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(next_label("12", &[]), "12A");
+        assert_eq!(next_label("12", &["12A".to_string(), "12B".to_string()]), "12C");
+        assert_eq!(next_label("12Z", &[]), "12AA");
+        assert_eq!(next_label("12AZ", &["12BA".to_string()]), "12BB");
+        assert_eq!(next_label("12ZZ", &[]), "12AAA");
+    }
+
+    #[test]
+    fn structure_kept() {
+        let src = "title\nsubtitle\nact one\nscene EXT. A - DAY\ndirect One.\nact two\nscene EXT. B - DAY\ndirect Two.\nend\n";
+        let edited = edit_scenes(src, &SceneEdit::Delete{ number: "1".into() }, "").expect("delete failed");
+        assert_eq!(edited, "title\nsubtitle\nact one\nomitted 1\nact two\nscene EXT. B - DAY #2#\ndirect Two.\nend\n");
+
+        let edited = edit_scenes(SRC, &SceneEdit::Delete{ number: "3".into() }, "").expect("delete failed");
+        assert!(edited.ends_with("direct Two.\nomitted 3\nend\n"));
+
+        let src = "title\nsubtitle\nscene INT. CAR - \\\nDAY\ndirect One.\n";
+        let edit = SceneEdit::InsertAfter{ number: "1".into(), file: String::new() };
+        let edited = edit_scenes(src, &edit, "scene INT. BUS - DAY").expect("insert failed");
+        assert_eq!(edited, "title\nsubtitle\nscene INT. CAR - \\\nDAY #1#\ndirect One.\nscene INT. BUS - DAY #1A#\n");
+    }
+}
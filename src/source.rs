@@ -0,0 +1,221 @@
+use std::{
+    fs,
+    io::{ self, BufRead },
+    path::{ Path, PathBuf },
+};
+use crate::{
+    import::{ import, is_imported },
+    parse::{ trim_ignored, Diagnostic },
+};
+
+
+pub(crate) struct Expanded {
+    pub text: String,
+    origins: Vec<(usize, usize)>,
+    pub files: Vec<String>,
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl Expanded {
+    pub fn locate(&self, mut diagnostic: Diagnostic) -> Diagnostic {
+        if let Some(&(file, line)) = diagnostic.line.checked_sub(1).and_then(|i| self.origins.get(i)) {
+            diagnostic.line = line;
+            if file > 0 {
+                diagnostic.file = Some(self.files[file].clone());
+            }
+        }
+        diagnostic
+    }
+}
+
+
+fn include_path(line: &str) -> Option<&str> {
+    let (_, line) = trim_ignored((0, line));
+    line.strip_prefix("include ").map(str::trim).filter(|p| !p.is_empty())
+}
+
+
+fn is_tail(line: &str) -> bool {
+    trim_ignored((0, line)).1 == "***"
+}
+
+
+// nothing after the tail marker is read, includes there included
+pub(crate) fn has_includes(src: &str) -> bool {
+    src.lines().take_while(|line| !is_tail(line)).any(|line| include_path(line).is_some())
+}
+
+
+pub(crate) fn reads_includes(reader: impl BufRead) -> io::Result<bool> {
+    for line in reader.lines() {
+        let line = line?;
+        if is_tail(&line) {
+            return Ok(false)
+        }
+        if include_path(&line).is_some() {
+            return Ok(true)
+        }
+    }
+    Ok(false)
+}
+
+
+// a tail only ends the file it is in, it is kept for the parser when nothing follows it and dropped otherwise
+fn expand_file(path: &Path, stack: &mut Vec<PathBuf>, out: &mut Expanded, last: bool) -> Result<(), Diagnostic> {
+    let unreadable = |e: io::Error| Diagnostic::new(0, format!("cannot read '{}': {e}", path.display()));
+    let file = out.files.len();
+    out.files.push(path.display().to_string());
+    let in_file = |mut diagnostic: Diagnostic, files: &[String]| {
+        diagnostic.file = (file > 0).then(|| files[file].clone());
+        diagnostic
+    };
+
+    // scripts from other programs are converted first, only the first file keeps its title
+    let (src, origins) = if is_imported(&path.display().to_string()) {
+        let imported = import(path, &fs::read(path).map_err(unreadable)?, file == 0).unwrap()
+            .map_err(|d| in_file(d, &out.files))?;
+        out.warnings.extend(imported.warnings.into_iter().map(|d| in_file(d, &out.files)));
+        (imported.text, imported.lines)
+    } else {
+        let src = fs::read_to_string(path).map_err(unreadable)?;
+        let origins = (1..=src.lines().count()).collect();
+        (src, origins)
+    };
+
+    let mut tail = Vec::new();
+    for (line, num) in src.lines().zip(origins) {
+        if !tail.is_empty() || is_tail(line) {
+            tail.push((line, num));
+            continue
+        }
+        let Some(include) = include_path(line) else {
+            out.text.push_str(line);
+            out.text.push('\n');
+            out.origins.push((file, num));
+            continue
+        };
+
+        let located = |message: String| in_file(Diagnostic::new(num, message), &out.files);
+        let target = path.parent().unwrap_or(Path::new("")).join(include);
+        let canonical = target.canonicalize()
+            .map_err(|e| located(format!("cannot include '{include}': {e}")))?;
+
+        if stack.contains(&canonical) {
+            let cycle: Vec<String> = stack.iter().chain([&canonical]).map(|p| p.display().to_string()).collect();
+            return Err(located(format!("include cycle ({})", cycle.join(" -> "))))
+        }
+
+        stack.push(canonical);
+        expand_file(&target, stack, out, false)?;
+        stack.pop();
+    }
+
+    if last {
+        for (line, num) in tail {
+            out.text.push_str(line);
+            out.text.push('\n');
+            out.origins.push((file, num));
+        }
+    } else if let Some(&(_, marker)) = tail.first() {
+        let skipped: Vec<usize> = tail[1..].iter().filter(|(line, _)| !trim_ignored((0, line)).1.is_empty()).map(|&(_, num)| num).collect();
+        if let (Some(first), Some(end)) = (skipped.first(), skipped.last()) {
+            let message = format!("{} line(s) after the '***' tail marker are not rendered (lines {first}-{end})", skipped.len());
+            out.warnings.push(in_file(Diagnostic::new(marker, message), &out.files));
+        }
+    }
+
+    Ok(())
+}
+
+
+pub(crate) fn expand(path: &str) -> Result<Expanded, Diagnostic> {
+    expand_all(&[path])
+}
+
+
+// later inputs are appended like includes, so only the first one supplies the title lines
+pub(crate) fn expand_all(paths: &[&str]) -> Result<Expanded, Diagnostic> {
+    let mut out = Expanded{ text: String::new(), origins: Vec::new(), files: Vec::new(), warnings: Vec::new() };
+
+    for (i, path) in paths.iter().map(Path::new).enumerate() {
+        let mut stack = vec![path.canonicalize().unwrap_or_else(|_| path.to_path_buf())];
+        expand_file(path, &mut stack, &mut out, i + 1 == paths.len())?;
+    }
+
+    Ok(out)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn write(dir: &Path, name: &str, content: &str) -> String {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path.display().to_string()
+    }
+
+    #[test]
+    fn includes() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        fs::create_dir_all(dir.join("acts")).unwrap();
+
+        let main = write(dir, "main.txt", "title\nsubtitle\ninclude acts/one.txt\nscene INT. CAR - DAY\n");
+        let one = write(dir, "acts/one.txt", "scene EXT. LOC - DAY\n\ndirect\n");
+
+        let expanded = expand(&main).expect("expand failed");
+        let errors = parse(&expanded.text).expect_err("parse should fail");
+        let located: Vec<Diagnostic> = errors.into_iter().map(|d| expanded.locate(d)).collect();
+        assert_eq!(located.len(), 1);
+        assert_eq!(located[0].file.as_deref(), Some(one.as_str()));
+        assert_eq!(located[0].line, 3);
+
+        write(dir, "acts/one.txt", "include ../main.txt\n");
+        let error = expand(&main).err().expect("cycle not detected");
+        assert_eq!(error.line, 1);
+        assert!(error.message.starts_with("include cycle"));
+
+        // tail notes are never followed, even when they look like an include
+        let notes = write(dir, "notes.txt", "title\nsubtitle\nscene INT. CAR - DAY\n***\ninclude missing.txt\n");
+        assert!(!has_includes(&fs::read_to_string(&notes).unwrap()));
+        assert!(!reads_includes(fs::read_to_string(&notes).unwrap().as_bytes()).unwrap());
+        assert!(expand(&notes).expect("tail expanded").text.ends_with("***\ninclude missing.txt\n"));
+
+        // a tail only ends the file it is in
+        let notes = write(dir, "notes.txt", "scene INT. CAR - DAY\n***\nleftover\n\nmore leftover\n");
+        write(dir, "acts/two.txt", "scene EXT. ROAD - DAY\n");
+        let main = write(dir, "main.txt", "title\nsubtitle\ninclude notes.txt\ninclude acts/two.txt\nscene INT. HOUSE - DAY\n");
+        let expanded = expand(&main).expect("expand failed");
+        assert_eq!(parse(&expanded.text).expect("parse failed").scenes.len(), 3);
+        assert_eq!(expanded.warnings, [Diagnostic{ file: Some(notes.clone()), ..Diagnostic::new(2, "2 line(s) after the '***' tail marker are not rendered (lines 3-5)") }]);
+        let first = write(dir, "first.txt", "title\nsubtitle\nscene INT. CAR - DAY\n***\nleftover\n");
+        let merged = expand_all(&[&first, &dir.join("acts/two.txt").display().to_string()]).expect("expand failed");
+        assert_eq!(parse(&merged.text).expect("parse failed").scenes.len(), 2);
+        assert_eq!(merged.warnings, [Diagnostic::new(4, "1 line(s) after the '***' tail marker are not rendered (lines 5-5)")]);
+    }
+
+    #[test]
+    fn merged() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+
+        let first = write(dir, "a.txt", "title\nsubtitle\nscene INT. CAR - DAY\n");
+        let second = write(dir, "b.txt", "scene EXT. LOC - DAY\n\ndirect\n");
+
+        let expanded = expand_all(&[&first, &second]).expect("expand failed");
+        assert_eq!(expanded.files, [first.clone(), second.clone()]);
+        let errors = parse(&expanded.text).expect_err("parse should fail");
+        let located = expanded.locate(errors.into_iter().next().unwrap());
+        assert_eq!(located.file.as_deref(), Some(second.as_str()));
+        assert_eq!(located.line, 3);
+
+        write(dir, "b.txt", "scene EXT. LOC - DAY\n");
+        let script = parse(&expand_all(&[&first, &second]).unwrap().text).expect("parse failed");
+        assert_eq!(script.title, "title");
+        assert_eq!(script.scenes.iter().map(|s| s.number).collect::<Vec<_>>(), [1, 2]);
+    }
+}
@@ -0,0 +1,231 @@
+use lazy_static::lazy_static;
+use regex::{ Captures, Regex };
+use crate::ast::*;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Normalize {
+    pub numbers: bool,
+    pub currency: bool,
+    pub abbreviations: bool,
+}
+
+impl Default for Normalize {
+    fn default() -> Self {
+        Self{ numbers: true, currency: true, abbreviations: true }
+    }
+}
+
+impl Normalize {
+    pub fn from_names(names: &str) -> Option<Self> {
+        let mut normalize = Self{ numbers: false, currency: false, abbreviations: false };
+        for name in names.split(',').map(str::trim) {
+            match name {
+                "numbers" => normalize.numbers = true,
+                "currency" => normalize.currency = true,
+                "abbreviations" => normalize.abbreviations = true,
+                "all" => normalize = Self::default(),
+                "none" => (),
+                _ => return None,
+            }
+        }
+        Some(normalize)
+    }
+}
+
+
+lazy_static! {
+    static ref PAT_NUMBER: Regex = Regex::new(r"(?P<cur>[$£€])?\b(?P<num>\d{1,3}(?:,\d{3})+|\d+)(?:\.(?P<frac>\d+))?(?P<suf>st\b|nd\b|rd\b|th\b|%)?").unwrap();
+    static ref PAT_ABBREV: Regex = Regex::new(r"\b(Dr|Mr|Mrs|Ms|Jr|Sr|vs|etc|approx|Lt|Sgt|Capt|Col|Gen|Prof)\.").unwrap();
+    static ref PAT_MARKUP: Regex = Regex::new(r"\\([*_\\])|\*\*|[*_]").unwrap();
+}
+
+
+const ONES: [&str; 20] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+    "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+];
+const TENS: [&str; 10] = ["", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+const SCALES: [(u64, &str); 4] = [(1_000_000_000_000, "trillion"), (1_000_000_000, "billion"), (1_000_000, "million"), (1_000, "thousand")];
+
+
+pub fn number_words(n: u64) -> String {
+    match n {
+        0..=19 => ONES[n as usize].to_string(),
+        20..=99 if n.is_multiple_of(10) => TENS[n as usize / 10].to_string(),
+        20..=99 => format!("{}-{}", TENS[n as usize / 10], ONES[n as usize % 10]),
+        100..=999 if n.is_multiple_of(100) => format!("{} hundred", ONES[n as usize / 100]),
+        100..=999 => format!("{} hundred {}", ONES[n as usize / 100], number_words(n % 100)),
+        _ => {
+            let (size, name) = SCALES.iter().find(|(size, _)| n >= *size).copied().unwrap_or(SCALES[0]);
+            match n % size {
+                0 => format!("{} {name}", number_words(n / size)),
+                rest => format!("{} {name} {}", number_words(n / size), number_words(rest)),
+            }
+        }
+    }
+}
+
+
+fn ordinal_words(n: u64) -> String {
+    let words = number_words(n);
+    let split = words.rfind([' ', '-']).map_or(0, |i| i + 1);
+    let (head, last) = words.split_at(split);
+    let last = match last {
+        "one" => "first".to_string(),
+        "two" => "second".to_string(),
+        "three" => "third".to_string(),
+        "five" => "fifth".to_string(),
+        "eight" => "eighth".to_string(),
+        "nine" => "ninth".to_string(),
+        "twelve" => "twelfth".to_string(),
+        last if last.ends_with('y') => format!("{}ieth", &last[..last.len() - 1]),
+        last => format!("{last}th"),
+    };
+    format!("{head}{last}")
+}
+
+
+fn currency_words(symbol: &str, whole: u64, frac: Option<&str>) -> String {
+    let (unit, units, minor) = match symbol {
+        "£" => ("pound", "pounds", "pence"),
+        "€" => ("euro", "euros", "cents"),
+        _ => ("dollar", "dollars", "cents"),
+    };
+    let mut result = format!("{} {}", number_words(whole), if whole == 1 { unit } else { units });
+    if let Some(cents) = frac.and_then(|f| format!("{f:0<2}")[..2].parse::<u64>().ok()).filter(|&c| c > 0) {
+        result.push_str(&format!(" and {} {}", number_words(cents), if cents == 1 && minor == "cents" { "cent" } else { minor }));
+    }
+    result
+}
+
+
+fn expand_number(caps: &Captures, normalize: &Normalize) -> String {
+    let whole = caps["num"].replace(',', "");
+    let Ok(n) = whole.parse::<u64>() else {
+        return caps[0].to_string()
+    };
+    let frac = caps.name("frac").map(|f| f.as_str());
+    let suffix = caps.name("suf").map(|s| s.as_str());
+
+    if let Some(symbol) = caps.name("cur") {
+        if !normalize.currency {
+            return caps[0].to_string()
+        }
+        let spoken = currency_words(symbol.as_str(), n, frac);
+        return if suffix.is_some() { format!("{spoken}{}", &caps["suf"]) } else { spoken }
+    }
+
+    let mut spoken = match suffix {
+        Some("st"|"nd"|"rd"|"th") => return ordinal_words(n),
+        _ => number_words(n),
+    };
+    if let Some(frac) = frac {
+        let digits: Vec<&str> = frac.bytes().map(|b| ONES[(b - b'0') as usize]).collect();
+        spoken = format!("{spoken} point {}", digits.join(" "));
+    }
+    if suffix == Some("%") {
+        spoken.push_str(" percent");
+    }
+    spoken
+}
+
+
+fn expand_abbreviation(abbreviation: &str) -> &'static str {
+    match abbreviation {
+        "Dr" => "Doctor",
+        "Mr" => "Mister",
+        "Mrs" => "Missus",
+        "Ms" => "Miz",
+        "Jr" => "Junior",
+        "Sr" => "Senior",
+        "vs" => "versus",
+        "etc" => "et cetera",
+        "approx" => "approximately",
+        "Lt" => "Lieutenant",
+        "Sgt" => "Sergeant",
+        "Capt" => "Captain",
+        "Col" => "Colonel",
+        "Gen" => "General",
+        _ => "Professor",
+    }
+}
+
+
+pub fn speakable(text: &str, normalize: &Normalize) -> String {
+    let mut text = PAT_MARKUP.replace_all(text, "$1").into_owned();
+
+    if normalize.abbreviations {
+        text = PAT_ABBREV.replace_all(&text, |caps: &Captures| expand_abbreviation(&caps[1])).replace(" & ", " and ");
+    }
+    if normalize.numbers || normalize.currency {
+        text = PAT_NUMBER.replace_all(&text, |caps: &Captures| match caps.name("cur") {
+            None if !normalize.numbers => caps[0].to_string(),
+            _ => expand_number(caps, normalize),
+        }).into_owned();
+    }
+
+    text
+}
+
+
+// the narrator reads headings and action, parentheticals and editorial elements are left out
+pub fn read_text(script: &Script, normalize: &Normalize) -> String {
+    let mut result = format!("{}.\n", speakable(&script.title, normalize));
+    let line = |text: &str| format!("\n{}\n", speakable(text, normalize));
+
+    let scenes = std::iter::once((None, &script.prelude)).chain(script.scenes.iter().map(|s| (Some(s), &s.elements)));
+    for (scene, elements) in scenes {
+        if let Some(scene) = scene.filter(|s| !s.omitted) {
+            result.push_str(&line(&format!("{}.", scene.heading)));
+        }
+        for e in elements {
+            match &e.kind {
+                Kind::Direct{ text }|Kind::Chyron{ text }|Kind::Subhead{ text }|Kind::Caption{ text }|Kind::Speech{ text }|Kind::Lyrics{ text } => {
+                    result.push_str(&line(text));
+                }
+                Kind::Dialogue(dialogue) => {
+                    let speech: Vec<&str> = dialogue.lines.iter()
+                        .filter_map(|l| match l {
+                            DialogueLine::Speech(text) => Some(text.as_str()),
+                            DialogueLine::Parens(_) => None,
+                        })
+                        .collect();
+                    result.push_str(&line(&format!("{}: {}", dialogue.name, speech.join(" "))));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    result
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn numbers() {
+        assert_eq!(number_words(0), "zero");
+        assert_eq!(number_words(42), "forty-two");
+        assert_eq!(number_words(1_305), "one thousand three hundred five");
+        assert_eq!(number_words(2_000_000), "two million");
+
+        let all = Normalize::default();
+        assert_eq!(speakable("Meet Dr. Li at 3.5 past the 21st, 10% off & $1,250.05 or £1.", &all),
+                   "Meet Doctor Li at three point five past the twenty-first, ten percent off and one thousand two hundred fifty dollars and five cents or one pound.");
+        assert_eq!(speakable("*Only* $5 on the 12th.", &Normalize{ currency: false, ..all }), "Only $5 on the twelfth.");
+        assert_eq!(speakable("Dr. Who, 7", &Normalize::from_names("none").unwrap()), "Dr. Who, 7");
+    }
+
+    #[test]
+    fn read() {
+        let script = parse("title\nsubtitle\nscene INT. ROOM 4 - DAY\ndirect Mr. Li waits.\nALEX: (quietly) I owe you $20.\nnote skip").expect("parse failed");
+        assert_eq!(read_text(&script, &Normalize::default()),
+                   "title.\n\nINT. ROOM four - DAY.\n\nMister Li waits.\n\nALEX: I owe you twenty dollars.\n");
+    }
+}
@@ -0,0 +1,340 @@
+use std::{
+    collections::{ HashMap, HashSet },
+    fs,
+    io,
+    path::Path,
+};
+use crate::{
+    ast::*,
+    parse::*,
+    CmdInfo,
+    HtmlError,
+};
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Condition {
+    Any,
+    Char(char),
+    Set(bool, Vec<char>),
+}
+
+impl Condition {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Char(x) => *x == c,
+            Self::Set(negated, chars) => chars.contains(&c) != *negated,
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Affix {
+    strip: String,
+    add: String,
+    condition: Vec<Condition>,
+}
+
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct AffixClass {
+    cross: bool,
+    rules: Vec<Affix>,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FlagType {
+    Char,
+    Long,
+    Num,
+}
+
+
+fn parse_condition(text: &str) -> Vec<Condition> {
+    let mut result = Vec::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => result.push(Condition::Any),
+            '[' => {
+                let set: String = chars.by_ref().take_while(|c| *c != ']').collect();
+                match set.strip_prefix('^') {
+                    Some(rest) => result.push(Condition::Set(true, rest.chars().collect())),
+                    None => result.push(Condition::Set(false, set.chars().collect())),
+                }
+            }
+            c => result.push(Condition::Char(c)),
+        }
+    }
+    result
+}
+
+
+fn parse_flags(flags: &str, kind: FlagType) -> Vec<String> {
+    match kind {
+        FlagType::Char => flags.chars().map(String::from).collect(),
+        FlagType::Long => flags.chars().collect::<Vec<_>>().chunks(2).map(|c| c.iter().collect()).collect(),
+        FlagType::Num => flags.split(',').map(|f| f.trim().to_string()).collect(),
+    }
+}
+
+
+// the subset of hunspell dictionaries that covers plain word lists: prefix and suffix rules
+// with conditions and cross products, compounding and suggestion tables are ignored
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    pub fn parse(aff: &str, dic: &str) -> Self {
+        let mut kind = FlagType::Char;
+        let mut prefixes: HashMap<String, AffixClass> = HashMap::new();
+        let mut suffixes: HashMap<String, AffixClass> = HashMap::new();
+
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["FLAG", "long", ..] => kind = FlagType::Long,
+                ["FLAG", "num", ..] => kind = FlagType::Num,
+                [which @ ("PFX"|"SFX"), flag, cross, count] if count.parse::<usize>().is_ok() => {
+                    let classes = if *which == "PFX" { &mut prefixes } else { &mut suffixes };
+                    classes.insert(flag.to_string(), AffixClass{ cross: *cross == "Y", rules: Vec::new() });
+                }
+                [which @ ("PFX"|"SFX"), flag, strip, add, condition, ..] => {
+                    let classes = if *which == "PFX" { &mut prefixes } else { &mut suffixes };
+                    let none = |s: &str| if s == "0" { String::new() } else { s.to_string() };
+                    let add = add.split_once('/').map_or(*add, |(add, _)| add);
+                    if let Some(class) = classes.get_mut(*flag) {
+                        class.rules.push(Affix{ strip: none(strip), add: none(add), condition: parse_condition(condition) });
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let mut words = HashSet::new();
+        for line in dic.lines().skip_while(|l| l.trim().parse::<usize>().is_ok()) {
+            let Some(entry) = line.split_whitespace().next() else {
+                continue
+            };
+            let (stem, flags) = entry.split_once('/').map_or((entry, Vec::new()), |(stem, flags)| (stem, parse_flags(flags, kind)));
+
+            let suffixed: Vec<(String, bool)> = flags.iter()
+                .filter_map(|f| suffixes.get(f))
+                .flat_map(|class| class.rules.iter().filter_map(|rule| apply_suffix(stem, rule)).map(|w| (w, class.cross)))
+                .collect();
+            for class in flags.iter().filter_map(|f| prefixes.get(f)) {
+                for rule in &class.rules {
+                    words.extend(apply_prefix(stem, rule));
+                    if class.cross {
+                        words.extend(suffixed.iter().filter(|(_, cross)| *cross).filter_map(|(w, _)| apply_prefix(w, rule)));
+                    }
+                }
+            }
+            words.extend(suffixed.into_iter().map(|(w, _)| w));
+            words.insert(stem.to_string());
+        }
+
+        Self{ words }
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let root = path.strip_suffix(".dic").unwrap_or(path);
+        let dic = fs::read(format!("{root}.dic"))?;
+        let aff = fs::read(format!("{root}.aff")).unwrap_or_default();
+        Ok(Self::parse(&String::from_utf8_lossy(&aff), &String::from_utf8_lossy(&dic)))
+    }
+
+    // one word per line, '*' starts a comment as in manifests
+    pub fn add_words(&mut self, list: &str) {
+        for line in list.lines() {
+            let word = line.split_once('*').map_or(line, |(word, _)| word).trim();
+            if !word.is_empty() {
+                self.words.insert(word.to_string());
+                self.words.insert(word.to_lowercase());
+            }
+        }
+    }
+
+    pub fn check(&self, word: &str) -> bool {
+        let word = word.trim_matches(['\'', '’']);
+        if word.is_empty() || word.chars().any(|c| c.is_numeric()) || word.chars().all(|c| !c.is_lowercase()) {
+            return true
+        }
+        if self.words.contains(word) || self.words.contains(&word.to_lowercase()) {
+            return true
+        }
+        match word.strip_suffix("'s").or_else(|| word.strip_suffix("’s")) {
+            Some(base) => self.check(base),
+            None => false,
+        }
+    }
+
+    // the closest known word one edit away, keeping the capitalization of the original
+    pub fn suggest(&self, word: &str) -> Option<String> {
+        let lower = word.to_lowercase();
+        let chars: Vec<char> = lower.chars().collect();
+        let letters = ('a'..='z').chain(lower.chars().filter(|c| !c.is_ascii()));
+
+        let mut candidates = Vec::new();
+        for i in 0..=chars.len() {
+            let (head, tail) = chars.split_at(i);
+            let head: String = head.iter().collect();
+            if !tail.is_empty() {
+                candidates.push(format!("{head}{}", tail[1..].iter().collect::<String>()));
+            }
+            if tail.len() > 1 {
+                candidates.push(format!("{head}{}{}{}", tail[1], tail[0], tail[2..].iter().collect::<String>()));
+            }
+            for c in letters.clone() {
+                candidates.push(format!("{head}{c}{}", tail.iter().collect::<String>()));
+                if !tail.is_empty() {
+                    candidates.push(format!("{head}{c}{}", tail[1..].iter().collect::<String>()));
+                }
+            }
+        }
+
+        let found = candidates.into_iter().find(|c| *c != lower && self.words.contains(c))?;
+        if word.starts_with(char::is_uppercase) {
+            let mut chars = found.chars();
+            return chars.next().map(|first| first.to_uppercase().chain(chars).collect())
+        }
+        Some(found)
+    }
+}
+
+
+fn matches_end(word: &str, condition: &[Condition]) -> bool {
+    let chars: Vec<char> = word.chars().collect();
+    chars.len() >= condition.len() && chars[chars.len() - condition.len()..].iter().zip(condition).all(|(c, cond)| cond.matches(*c))
+}
+
+
+fn matches_start(word: &str, condition: &[Condition]) -> bool {
+    let chars: Vec<char> = word.chars().collect();
+    chars.len() >= condition.len() && chars.iter().zip(condition).all(|(c, cond)| cond.matches(*c))
+}
+
+
+fn apply_suffix(stem: &str, rule: &Affix) -> Option<String> {
+    if !matches_end(stem, &rule.condition) {
+        return None
+    }
+    stem.strip_suffix(rule.strip.as_str()).map(|base| format!("{base}{}", rule.add))
+}
+
+
+fn apply_prefix(stem: &str, rule: &Affix) -> Option<String> {
+    if !matches_start(stem, &rule.condition) {
+        return None
+    }
+    stem.strip_prefix(rule.strip.as_str()).map(|base| format!("{}{base}", rule.add))
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Misspelling {
+    pub line: usize,
+    pub scene: String,
+    pub word: String,
+    pub suggestion: Option<String>,
+}
+
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '\'' || c == '’'))
+        .map(|w| w.trim_matches(['\'', '’']))
+        .filter(|w| !w.is_empty())
+}
+
+
+// dialogue and action only, cue names, locations and the project word list are accepted as spelled
+pub fn misspellings(script: &Script, dictionary: &Dictionary) -> Vec<Misspelling> {
+    let mut dictionary = dictionary.clone();
+    let known = script.completions();
+    for name in known.characters.iter().chain(&known.locations) {
+        dictionary.add_words(&words(name).collect::<Vec<_>>().join("\n"));
+    }
+
+    let scenes = std::iter::once((None, &script.prelude)).chain(script.scenes.iter().map(|s| (Some(s), &s.elements)));
+    let mut result = Vec::new();
+    for (scene, elements) in scenes {
+        let label = script.scene_label(&scene.map_or("0".to_string(), Scene::display_number));
+        for element in elements {
+            let texts: Vec<&str> = match &element.kind {
+                Kind::Direct{ text }|Kind::Chyron{ text }|Kind::Speech{ text }|Kind::Parens{ text }|Kind::Lyrics{ text }|Kind::Caption{ text } => vec![text],
+                Kind::Dialogue(dialogue) => dialogue.lines.iter()
+                    .map(|l| match l {
+                        DialogueLine::Parens(text)|DialogueLine::Speech(text) => text.as_str(),
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            for word in texts.into_iter().flat_map(words).filter(|w| !dictionary.check(w)) {
+                result.push(Misspelling{ line: element.line, scene: label.clone(), word: word.to_string(), suggestion: dictionary.suggest(word) });
+            }
+        }
+    }
+    result
+}
+
+
+// the project word list defaults to '<input>.words.txt' next to the script
+pub fn gen_spelling(cmd: &CmdInfo, dictionary: &str, word_list: Option<&str>) -> Result<Vec<Misspelling>, HtmlError> {
+    let mut dict = Dictionary::load(dictionary)?;
+    let default = format!("{}.words.txt", cmd.file_root);
+    match word_list {
+        Some(path) => dict.add_words(&fs::read_to_string(path)?),
+        None if Path::new(&default).exists() => dict.add_words(&fs::read_to_string(&default)?),
+        None => (),
+    }
+
+    let script = read_script(cmd)?;
+    Ok(misspellings(&script, &dict))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AFF: &str = "SET UTF-8\n\
+                       PFX U Y 1\n\
+                       PFX U 0 un .\n\
+                       SFX S Y 2\n\
+                       SFX S y ies [^aeiou]y\n\
+                       SFX S 0 s [^y]\n\
+                       SFX D N 1\n\
+                       SFX D 0 ed [^e]\n";
+    const DIC: &str = "7\ndoor/S\nlock/USD\ncity/S\nthe\nand\nslam/S\nParis\n";
+
+    #[test]
+    fn affixes() {
+        let dict = Dictionary::parse(AFF, DIC);
+        for word in ["doors", "cities", "unlocks", "unlock", "locked", "The", "Paris", "Paris's", "NYPD", "42nd"] {
+            assert!(dict.check(word), "{word}");
+        }
+        for word in ["citys", "unlocked", "paris", "dor"] {
+            assert!(!dict.check(word), "{word}");
+        }
+        assert_eq!(dict.suggest("dor").as_deref(), Some("door"));
+        assert_eq!(dict.suggest("Slma").as_deref(), Some("Slam"));
+    }
+
+    #[test]
+    fn script() {
+        let mut dict = Dictionary::parse(AFF, DIC);
+        dict.add_words("Zorg * villain\n");
+        let script = parse("title\nsubtitle\nscene INT. CITY - DAY\ndirect The dor slams. Zorg locks the doors.\nALEX: The *citys* and Alex's lock.").expect("parse failed");
+
+        let found = misspellings(&script, &dict);
+        assert_eq!(found.len(), 2);
+        assert_eq!((found[0].line, found[0].word.as_str(), found[0].suggestion.as_deref()), (4, "dor", Some("door")));
+        assert_eq!((found[1].line, found[1].word.as_str()), (5, "citys"));
+    }
+}
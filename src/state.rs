@@ -0,0 +1,118 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{ Path, PathBuf },
+};
+use serde::{ Serialize, Deserialize };
+use crate::{
+    html::{ HtmlError, STYLESHEET },
+    progress::Progress,
+    source::{ expand_all, has_includes },
+    storage::{ StateBackend, Storage },
+    CmdInfo,
+};
+
+
+pub const STATE_FILE: &str = ".scripts-state.json";
+
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildState {
+    pub outputs: BTreeMap<String, String>,
+}
+
+impl BuildState {
+    pub fn load(storage: &dyn Storage, path: &Path) -> Self {
+        storage.load(path)
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &dyn Storage, path: &Path) -> Result<(), HtmlError> {
+        storage.save(path, &serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn is_current(&self, output: &str, key: &str) -> bool {
+        Path::new(output).exists() && self.outputs.get(output).is_some_and(|k| k == key)
+    }
+
+    pub fn record(&mut self, output: &str, key: String) {
+        self.outputs.insert(output.to_string(), key);
+    }
+}
+
+
+pub fn state_path(output: &str) -> PathBuf {
+    Path::new(output).parent().unwrap_or(Path::new("")).join(STATE_FILE)
+}
+
+
+// FNV-1a, stable across builds unlike the std hasher
+pub(crate) fn fnv(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+
+pub fn build_key(cmd: &CmdInfo) -> Result<String, HtmlError> {
+    let src = fs::read_to_string(&cmd.infile)?;
+    let src = if has_includes(&src) || !cmd.merge.is_empty() {
+        expand_all(&cmd.inputs()).map_err(|d| HtmlError::ParseError(vec![d]))?.text
+    } else {
+        src
+    };
+
+    let options = CmdInfo{
+        infile: String::new(),
+        merge: Vec::new(),
+        outfile: String::new(),
+        html: String::new(),
+        file_root: String::new(),
+        exe_loc: String::new(),
+        nopen: false,
+        viewer: None,
+        resume: false,
+        force: false,
+        progress: Progress::Human,
+        state: StateBackend::File,
+        ..cmd.clone()
+    };
+
+    let input = fnv(0xcbf29ce484222325, src.as_bytes());
+    let options = fnv(0xcbf29ce484222325, format!("{options:?}{}", env!("CARGO_PKG_VERSION")).as_bytes());
+    let options = fnv(options, STYLESHEET.as_bytes());
+    Ok(format!("{input:016x}-{options:016x}"))
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileStorage;
+
+    #[test]
+    fn keys() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let input = dir.join("a.txt");
+        fs::write(&input, "title\nsubtitle\nscene EXT. LOC - DAY\n").unwrap();
+
+        let mut cmd = CmdInfo{ infile: input.display().to_string(), ..Default::default() };
+        let key = build_key(&cmd).expect("key failed");
+        cmd.outfile = "elsewhere.pdf".to_string();
+        assert_eq!(build_key(&cmd).unwrap(), key);
+        cmd.paginate = true;
+        assert_ne!(build_key(&cmd).unwrap(), key);
+
+        let output = dir.join("a.pdf").display().to_string();
+        let mut state = BuildState::default();
+        state.record(&output, key.clone());
+        assert!(!state.is_current(&output, &key));
+        fs::write(&output, "").unwrap();
+        assert!(state.is_current(&output, &key));
+
+        let path = dir.join(STATE_FILE);
+        state.save(&FileStorage, &path).unwrap();
+        assert_eq!(BuildState::load(&FileStorage, &path), state);
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    fs,
+    path::Path,
+    time::{ Duration, SystemTime },
+};
+use crate::{
+    ast::*,
+    layout::*,
+    parse::*,
+    review::{ script_items, Sidecar },
+    source::expand_all,
+    state::{ state_path, BuildState },
+    storage::storage,
+    CmdInfo,
+    HtmlError,
+};
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceStatus {
+    pub path: String,
+    pub lines: usize,
+    pub modified: Option<SystemTime>,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputStatus {
+    pub path: String,
+    pub modified: Option<SystemTime>,
+    pub stale: bool,
+}
+
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SceneDeltas {
+    pub scenes: usize,
+    pub frozen: usize,
+    pub added: usize,
+    pub omitted: usize,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectStatus {
+    pub title: String,
+    pub sources: Vec<SourceStatus>,
+    pub outputs: Vec<OutputStatus>,
+    pub scenes: SceneDeltas,
+    pub pages: String,
+    pub warnings: Vec<Diagnostic>,
+    pub todos: usize,
+    pub resolved: usize,
+    pub notes: usize,
+}
+
+
+// scene numbers are frozen by locking them (scene insert-after/delete locks every scene),
+// so unlocked scenes are the ones added since, omitted ones the ones removed
+pub fn scene_deltas(script: &Script) -> SceneDeltas {
+    let mut deltas = SceneDeltas::default();
+    for scene in &script.scenes {
+        if scene.omitted {
+            deltas.omitted += 1;
+            continue
+        }
+        deltas.scenes += 1;
+        match scene.locked {
+            Some(_) => deltas.frozen += 1,
+            None => deltas.added += 1,
+        }
+    }
+    deltas
+}
+
+
+pub fn format_age(age: Duration) -> String {
+    match age.as_secs() {
+        0..=59 => "just now".to_string(),
+        secs @ 60..=3599 => format!("{}m ago", secs / 60),
+        secs @ 3600..=86399 => format!("{}h ago", secs / 3600),
+        secs => format!("{}d ago", secs / 86400),
+    }
+}
+
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+
+// outputs are taken from the build state next to the input and next to '-o', when given,
+// keeping those named after the input and the '-o' file itself
+fn recorded_outputs(cmd: &CmdInfo) -> Vec<String> {
+    let storage = storage(cmd.state);
+    let stem = Path::new(&cmd.infile).file_stem().unwrap_or_default();
+
+    let mut dirs = vec![state_path(&cmd.infile)];
+    if !cmd.outfile.is_empty() && !dirs.contains(&state_path(&cmd.outfile)) {
+        dirs.push(state_path(&cmd.outfile));
+    }
+
+    let mut outputs = Vec::new();
+    for path in dirs {
+        for output in BuildState::load(&*storage, &path).outputs.into_keys() {
+            if (output == cmd.outfile || Path::new(&output).file_stem() == Some(stem)) && !outputs.contains(&output) {
+                outputs.push(output);
+            }
+        }
+    }
+    outputs
+}
+
+
+pub fn gen_status(cmd: &CmdInfo) -> Result<ProjectStatus, HtmlError> {
+    let expanded = expand_all(&cmd.inputs()).map_err(|d| HtmlError::ParseError(vec![d]))?;
+    let mut sources = Vec::new();
+    for path in &expanded.files {
+        sources.push(SourceStatus{ path: path.clone(), lines: fs::read_to_string(path)?.lines().count(), modified: modified(path) });
+    }
+    let newest = sources.iter().filter_map(|s| s.modified).max();
+
+    let outputs = recorded_outputs(cmd).into_iter()
+        .map(|path| {
+            let modified = modified(&path);
+            let stale = match (modified, newest) {
+                (Some(built), Some(source)) => built < source,
+                _ => true,
+            };
+            OutputStatus{ path, modified, stale }
+        })
+        .collect();
+
+    let (script, locate) = read_located(cmd)?;
+    let sidecar = Sidecar::load(&*storage(cmd.state), &Sidecar::path(cmd));
+    let todos: Vec<_> = script_items(&script, &locate).into_iter().filter(|i| i.kind == "todo").collect();
+    let resolved = todos.iter().filter(|i| sidecar.is_resolved(i)).count();
+    let notes = script.prelude.iter()
+        .chain(script.scenes.iter().flat_map(|s| &s.elements))
+        .filter(|e| matches!(e.kind, Kind::Note{ .. }))
+        .count();
+    let pages: u32 = script.scenes.iter().filter(|s| !s.omitted).map(scene_eighths).sum();
+
+    Ok(ProjectStatus{
+        title: script.title.clone(),
+        sources,
+        outputs,
+        scenes: scene_deltas(&script),
+        pages: format_eighths(pages),
+        todos: todos.len() - resolved,
+        resolved,
+        notes,
+        warnings: script.warnings,
+    })
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deltas() {
+        let script = parse("title\nsubtitle\nscene INT. A - DAY #1#\nscene INT. B - DAY #2#\nscene INT. NEW - DAY\nomitted 3\nscene INT. C - DAY #4#").expect("parse failed");
+
+        assert_eq!(scene_deltas(&script), SceneDeltas{ scenes: 4, frozen: 3, added: 1, omitted: 1 });
+        assert_eq!(format_age(Duration::from_secs(30)), "just now");
+        assert_eq!(format_age(Duration::from_secs(7300)), "2h ago");
+        assert_eq!(format_age(Duration::from_secs(3 * 86400)), "3d ago");
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    fs,
+    path::Path,
+};
+use crate::html::HtmlError;
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StateBackend {
+    #[default]
+    File,
+    Sqlite,
+}
+
+
+// project state documents (build state, review notes) are stored as json under their usual file path,
+// the backend decides whether that path is a file of its own or a row in the directory's database
+pub trait Storage {
+    fn load(&self, path: &Path) -> Option<String>;
+    fn save(&self, path: &Path, document: &str) -> Result<(), HtmlError>;
+}
+
+
+pub struct FileStorage;
+
+impl Storage for FileStorage {
+    fn load(&self, path: &Path) -> Option<String> {
+        fs::read_to_string(path).ok()
+    }
+
+    fn save(&self, path: &Path, document: &str) -> Result<(), HtmlError> {
+        fs::write(path, document)?;
+        Ok(())
+    }
+}
+
+
+#[cfg(feature = "sqlite")]
+pub struct SqliteStorage;
+
+#[cfg(feature = "sqlite")]
+impl SqliteStorage {
+    pub const DATABASE: &'static str = ".scripts-state.db";
+
+    fn open(path: &Path) -> rusqlite::Result<rusqlite::Connection> {
+        let db = rusqlite::Connection::open(path.parent().unwrap_or(Path::new("")).join(Self::DATABASE))?;
+        db.execute("CREATE TABLE IF NOT EXISTS documents (path TEXT PRIMARY KEY, document TEXT NOT NULL)", [])?;
+        Ok(db)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl Storage for SqliteStorage {
+    fn load(&self, path: &Path) -> Option<String> {
+        let name = path.file_name()?.to_string_lossy().into_owned();
+        Self::open(path).ok()?
+            .query_row("SELECT document FROM documents WHERE path = ?1", [name], |row| row.get(0))
+            .ok()
+    }
+
+    fn save(&self, path: &Path, document: &str) -> Result<(), HtmlError> {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        Self::open(path)
+            .and_then(|db| db.execute("INSERT OR REPLACE INTO documents (path, document) VALUES (?1, ?2)", [name.as_str(), document]))
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
+        Ok(())
+    }
+}
+
+
+pub fn storage(backend: StateBackend) -> Box<dyn Storage> {
+    match backend {
+        #[cfg(feature = "sqlite")]
+        StateBackend::Sqlite => Box::new(SqliteStorage),
+        _ => Box::new(FileStorage),
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_storage() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("storage.json");
+
+        assert_eq!(FileStorage.load(&path), None);
+        FileStorage.save(&path, "{}").unwrap();
+        assert_eq!(FileStorage.load(&path).as_deref(), Some("{}"));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_storage() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("script.notes.json");
+
+        assert_eq!(SqliteStorage.load(&path), None);
+        SqliteStorage.save(&path, "{}").unwrap();
+        SqliteStorage.save(&path, "[]").unwrap();
+        assert_eq!(SqliteStorage.load(&path).as_deref(), Some("[]"));
+        assert!(!path.exists() && dir.join(SqliteStorage::DATABASE).exists());
+    }
+}
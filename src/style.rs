@@ -0,0 +1,192 @@
+use std::fmt::Write;
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::{
+    ast::{ Kind, Script },
+    parse::Diagnostic,
+};
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HouseStyle {
+    #[default]
+    UsFeature,
+    Bbc,
+    Multicam,
+    Audio,
+    Comic,
+}
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SceneNumbers {
+    #[default]
+    Left,
+    Both,
+    Hidden,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rules {
+    pub css: &'static str,
+    pub uppercase_action: bool,
+    pub underline_headings: bool,
+    pub underline_entrances: bool,
+    pub scene_letters: bool,
+    pub contd: bool,
+    pub number_cues: bool,
+    pub number_dialogue: bool,
+    pub scene_numbers: SceneNumbers,
+    pub acts: bool,
+}
+
+
+const BBC_CSS: &str = "\
+.name { margin-left: 0.5in; }
+.parens { margin-left: 2in; }
+.speech { margin-left: 2in; margin-right: 0.5in; }
+.lyrics { margin-left: 2in; margin-right: 0.5in; }";
+
+const MULTICAM_CSS: &str = "\
+.speech { line-height: 2; }
+.parens { line-height: 2; }
+.lyrics { line-height: 2; }";
+
+const AUDIO_CSS: &str = "\
+.cue { margin-left: 0.5in; text-decoration: underline; }";
+
+const COMIC_CSS: &str = "\
+.name { margin-left: 0.5in; }
+.parens { margin-left: 1in; }
+.speech { margin-left: 1in; margin-right: 0.5in; }";
+
+
+impl HouseStyle {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "us-feature" => Some(Self::UsFeature),
+            "bbc"        => Some(Self::Bbc),
+            "multicam"|"sitcom" => Some(Self::Multicam),
+            "audio"|"podcast" => Some(Self::Audio),
+            "comic"      => Some(Self::Comic),
+            _ => None,
+        }
+    }
+
+    pub fn rules(&self) -> Rules {
+        match self {
+            Self::UsFeature => Rules{ css: "", uppercase_action: false, underline_headings: false, underline_entrances: false, scene_letters: false, contd: true, number_cues: false, number_dialogue: false, scene_numbers: SceneNumbers::Left, acts: false },
+            Self::Bbc       => Rules{ css: BBC_CSS, uppercase_action: false, underline_headings: true, underline_entrances: false, scene_letters: false, contd: false, number_cues: false, number_dialogue: false, scene_numbers: SceneNumbers::Left, acts: false },
+            Self::Multicam  => Rules{ css: MULTICAM_CSS, uppercase_action: true, underline_headings: true, underline_entrances: true, scene_letters: true, contd: true, number_cues: false, number_dialogue: false, scene_numbers: SceneNumbers::Left, acts: true },
+            Self::Audio     => Rules{ css: AUDIO_CSS, uppercase_action: false, underline_headings: false, underline_entrances: false, scene_letters: false, contd: false, number_cues: true, number_dialogue: true, scene_numbers: SceneNumbers::Left, acts: false },
+            Self::Comic     => Rules{ css: COMIC_CSS, uppercase_action: false, underline_headings: false, underline_entrances: false, scene_letters: false, contd: false, number_cues: false, number_dialogue: true, scene_numbers: SceneNumbers::Left, acts: false },
+        }
+    }
+}
+
+
+impl Rules {
+    pub fn action(&self, text: &str) -> String {
+        lazy_static! {
+            // names are found before uppercasing, so the words leading up to one are not taken for part of it
+            static ref PAT_ENTRANCE: Regex = Regex::new(r"\b\p{Lu}[\p{L}'.]*(?: \p{Lu}[\p{L}'.]*)* (?i:enters|enter|exits|exit)\b").unwrap();
+        }
+
+        let case = |text: &str| if self.uppercase_action { text.to_uppercase() } else { text.to_string() };
+        if !self.underline_entrances {
+            return case(text)
+        }
+
+        let mut result = String::new();
+        let mut last = 0;
+        for found in PAT_ENTRANCE.find_iter(text) {
+            result.push_str(&case(&text[last..found.start()]));
+            write!(result, "<u>{}</u>", case(found.as_str())).unwrap();
+            last = found.end();
+        }
+        result.push_str(&case(&text[last..]));
+        result
+    }
+
+    // styles played in acts want every scene inside one, the cold open counts as the first
+    pub fn validate(&self, script: &Script) -> Vec<Diagnostic> {
+        let opened = script.prelude.iter().any(|e| matches!(e.kind, Kind::Act{ .. }|Kind::ActTag{ .. }));
+        match script.scenes.iter().find(|s| !s.omitted) {
+            Some(scene) if self.acts && !opened => vec![Diagnostic::new(scene.line, "scene comes before the first act, this house style is divided into acts")],
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn scene_label(&self, number: u32) -> String {
+        if !self.scene_letters || number == 0 {
+            return number.to_string()
+        }
+
+        let mut label = Vec::new();
+        let mut n = number;
+        while n > 0 {
+            n -= 1;
+            label.push(b'A' + (n % 26) as u8);
+            n /= 26;
+        }
+        label.reverse();
+        String::from_utf8(label).unwrap()
+    }
+
+    pub fn heading(&self, text: &str) -> String {
+        if self.underline_headings {
+            format!("<u>{text}</u>")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn entrances() {
+        let rules = HouseStyle::Multicam.rules();
+
+        assert_eq!(rules.action("Alex enters, followed by Old Sam."), "<u>ALEX ENTERS</u>, FOLLOWED BY OLD SAM.");
+        assert_eq!(rules.action("Mrs. O'Brien exits"), "<u>MRS. O'BRIEN EXITS</u>");
+        assert_eq!(rules.action("The door opens and Alex enters."), "THE DOOR OPENS AND <u>ALEX ENTERS</u>.");
+        assert_eq!(rules.action("Sam waves as ALEX Exits"), "SAM WAVES AS <u>ALEX EXITS</u>");
+        assert_eq!(HouseStyle::UsFeature.rules().action("Alex enters."), "Alex enters.");
+    }
+
+    #[test]
+    fn scene_letters() {
+        let rules = HouseStyle::Multicam.rules();
+
+        assert_eq!(rules.scene_label(1), "A");
+        assert_eq!(rules.scene_label(26), "Z");
+        assert_eq!(rules.scene_label(27), "AA");
+        assert_eq!(HouseStyle::UsFeature.rules().scene_label(27), "27");
+    }
+
+    #[test]
+    fn acts() {
+        let rules = HouseStyle::Multicam.rules();
+
+        let script = parse("title\nsubtitle\nscene INT. BAR - NIGHT\nact one\nscene INT. BAR - DAY\nend").expect("parse failed");
+        assert_eq!(rules.validate(&script), [Diagnostic::new(3, "scene comes before the first act, this house style is divided into acts")]);
+        assert!(HouseStyle::UsFeature.rules().validate(&script).is_empty());
+
+        let script = parse("title\nsubtitle\ncold-open\nscene INT. BAR - NIGHT\nact one\nscene INT. BAR - DAY\nend").expect("parse failed");
+        assert!(rules.validate(&script).is_empty());
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(HouseStyle::from_name("sitcom"), Some(HouseStyle::Multicam));
+        assert_eq!(HouseStyle::from_name("bbc"), Some(HouseStyle::Bbc));
+        assert_eq!(HouseStyle::from_name("sitcoms"), None);
+    }
+}
@@ -0,0 +1,304 @@
+use std::ops::Range;
+use crate::{
+    front::front_matter,
+    parse::{
+        comment_start,
+        dialogue_spans,
+        header_lines,
+        split_cue,
+        strip_continuation,
+        Context,
+        PAT_HEAD,
+        PAT_SCENE,
+        PAT_SPEECH,
+    },
+};
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Title,
+    Subtitle,
+    Keyword,
+    Directive,
+    SceneHeading,
+    Header,
+    Action,
+    Character,
+    Parenthetical,
+    Dialogue,
+    Transition,
+    Todo,
+    Section,
+    Synopsis,
+    Comment,
+    Continuation,
+    Invalid,
+}
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+    pub span: Range<usize>,
+}
+
+
+fn trimmed(src: &str, span: Range<usize>) -> Range<usize> {
+    let text = &src[span.clone()];
+    let start = span.start + (text.len() - text.trim_start().len());
+    let end = span.end - (text.len() - text.trim_end().len());
+    start..end.max(start)
+}
+
+
+fn mode_kind(mode: &str) -> Option<TokenKind> {
+    match mode {
+        "scene" => Some(TokenKind::SceneHeading),
+        "trans" => Some(TokenKind::Transition),
+        "direct"|"chyron"|"sfx"|"music" => Some(TokenKind::Action),
+        "parens" => Some(TokenKind::Parenthetical),
+        "speech"|"lyrics" => Some(TokenKind::Dialogue),
+        "subhead"|"act"|"episode"|"page"|"panel"|"omitted" => Some(TokenKind::Header),
+        "caption" => Some(TokenKind::Dialogue),
+        "TODO" => Some(TokenKind::Todo),
+        "note" => Some(TokenKind::Comment),
+        "=" => Some(TokenKind::Synopsis),
+        _ if !mode.is_empty() && mode.bytes().all(|b| b == b'#') => Some(TokenKind::Section),
+        "doctype"|"episode-number"|"cast"|"alias"|"include"|"define" => Some(TokenKind::Directive),
+        "props"|"wardrobe"|"vehicles"|"extras"|"restricted" => Some(TokenKind::Directive),
+        _ => None,
+    }
+}
+
+
+// keywords that stand alone on their line
+fn bare_kind(mode: &str) -> Option<TokenKind> {
+    match mode {
+        "montage"|"mon-end"|"pagebreak"|"end" => Some(TokenKind::Keyword),
+        "cold-open"|"tag"|"prologue"|"prologue-end" => Some(TokenKind::Header),
+        _ => None,
+    }
+}
+
+
+fn classify(src: &str, line: usize, span: Range<usize>, ctx: &Context, tokens: &mut Vec<Token>) -> TokenKind {
+    let text = &src[span.clone()];
+    let mut push = |kind, span: Range<usize>| {
+        let span = trimmed(src, span);
+        if !span.is_empty() {
+            tokens.push(Token{ kind, line, span });
+        }
+    };
+
+    let (mode, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    if let Some(kind) = bare_kind(ctx.keyword(mode)) {
+        push(kind, span.start..span.start + mode.len());
+        push(TokenKind::Invalid, span.end - rest.len()..span.end);
+        return kind
+    }
+    if let Some(kind) = mode_kind(ctx.keyword(mode)) {
+        push(TokenKind::Keyword, span.start..span.start + mode.len());
+        push(kind, span.end - rest.len()..span.end);
+        return kind
+    }
+
+    if PAT_SCENE.is_match(&ctx.heading(text)) {
+        push(TokenKind::SceneHeading, span);
+        TokenKind::SceneHeading
+    } else if PAT_HEAD.is_match(text) {
+        push(TokenKind::Header, span);
+        TokenKind::Header
+    } else if PAT_SPEECH.is_match(text) {
+        let (name, rest) = split_cue(text).unwrap();
+        push(TokenKind::Character, span.start..span.start + name.len());
+
+        let content = span.start + text.len() - rest.len();
+        match dialogue_spans(rest) {
+            Ok(spans) => for (parens, span) in spans {
+                let kind = if parens { TokenKind::Parenthetical } else { TokenKind::Dialogue };
+                push(kind, content + span.start..content + span.end);
+            }
+            Err(_) => push(TokenKind::Invalid, content..span.end),
+        }
+        TokenKind::Dialogue
+    } else {
+        push(TokenKind::Invalid, span);
+        TokenKind::Invalid
+    }
+}
+
+
+pub fn tokenize(src: &str) -> Vec<Token> {
+    tokens(src, true)
+}
+
+
+pub(crate) fn tokenize_body(src: &str) -> Vec<Token> {
+    tokens(src, false)
+}
+
+
+// the title lines, keywords and scene prefixes are read the way the parser reads them
+fn tokens(src: &str, header: bool) -> Vec<Token> {
+    let (matter, skip) = front_matter(src).ok().flatten().unwrap_or_default();
+    let ctx = Context::new(&matter);
+    let (title, subtitle) = if header { header_lines(src, skip, &matter, &ctx) } else { (None, None) };
+
+    let mut tokens = Vec::new();
+    let mut offset = 0;
+    let mut tail = false;
+    let mut front: Option<&str> = None;
+    let mut carry: Option<TokenKind> = None;
+
+    for (num, raw) in src.split_inclusive('\n').enumerate() {
+        let line = num + 1;
+        let start = offset;
+        offset += raw.len();
+        let end = start + raw.trim_end_matches(['\n', '\r']).len();
+
+        let text = src[start..end].trim();
+        if num == 0 && matches!(text, "---"|"+++") {
+            front = Some(text);
+        }
+        if let Some(fence) = front {
+            let span = trimmed(src, start..end);
+            if !span.is_empty() {
+                tokens.push(Token{ kind: TokenKind::Directive, line, span });
+            }
+            if num > 0 && text == fence {
+                front = None;
+            }
+            continue
+        }
+
+        if tail {
+            let span = trimmed(src, start..end);
+            if !span.is_empty() {
+                tokens.push(Token{ kind: TokenKind::Comment, line, span });
+            }
+            continue
+        }
+
+        let comment = comment_start(&src[start..end]).map(|i| start + i);
+        let body = trimmed(src, start..comment.unwrap_or(end));
+
+        if &src[body.clone()] == "***" {
+            tail = true;
+            tokens.push(Token{ kind: TokenKind::Comment, line, span: body });
+            continue
+        }
+
+        if !body.is_empty() {
+            let continues = strip_continuation(&src[body.clone()]).is_some();
+            let content = if continues { body.start..body.end - 1 } else { body.clone() };
+
+            let kind = if let Some(kind) = carry {
+                let span = trimmed(src, content);
+                if !span.is_empty() {
+                    tokens.push(Token{ kind, line, span });
+                }
+                kind
+            } else if title == Some(line) || subtitle == Some(line) {
+                let kind = if title == Some(line) { TokenKind::Title } else { TokenKind::Subtitle };
+                tokens.push(Token{ kind, line, span: trimmed(src, content) });
+                kind
+            } else {
+                classify(src, line, content, &ctx, &mut tokens)
+            };
+
+            if continues {
+                tokens.push(Token{ kind: TokenKind::Continuation, line, span: body.end - 1..body.end });
+                carry = Some(kind);
+            } else {
+                carry = None;
+            }
+        }
+
+        if let Some(comment) = comment {
+            tokens.push(Token{ kind: TokenKind::Comment, line, span: comment..end });
+        }
+    }
+
+    tokens
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens() {
+        let src = "Title\n\
+                   Subtitle\n\
+                   \n\
+                   scene EXT. LOC - DAY * opening\n\
+                   alex: (Mood) I am \\\n\
+                   still speaking\n\
+                   trans CUT TO\n\
+                   ***\n\
+                   notes";
+
+        let tokens: Vec<(TokenKind, usize, &str)> = tokenize(src)
+            .into_iter()
+            .map(|t| (t.kind, t.line, &src[t.span]))
+            .collect();
+
+        assert_eq!(tokens, vec![
+            (TokenKind::Title, 1, "Title"),
+            (TokenKind::Subtitle, 2, "Subtitle"),
+            (TokenKind::Keyword, 4, "scene"),
+            (TokenKind::SceneHeading, 4, "EXT. LOC - DAY"),
+            (TokenKind::Comment, 4, "* opening"),
+            (TokenKind::Character, 5, "alex"),
+            (TokenKind::Parenthetical, 5, "(Mood)"),
+            (TokenKind::Dialogue, 5, "I am"),
+            (TokenKind::Continuation, 5, "\\"),
+            (TokenKind::Dialogue, 6, "still speaking"),
+            (TokenKind::Keyword, 7, "trans"),
+            (TokenKind::Transition, 7, "CUT TO"),
+            (TokenKind::Comment, 8, "***"),
+            (TokenKind::Comment, 9, "notes"),
+        ]);
+
+        let src = "+++\ntitle = \"Pilot\"\n+++\nscene EXT. LOC - DAY";
+        let kinds: Vec<TokenKind> = tokenize(src).into_iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, vec![
+            TokenKind::Directive,
+            TokenKind::Directive,
+            TokenKind::Directive,
+            TokenKind::Keyword,
+            TokenKind::SceneHeading,
+        ]);
+
+        // without a title the script starts straight away, and keywords can be localized
+        let src = "---\nkeywords: { szene: scene, ende: end }\nprefixes: { INNEN.: INT. }\n---\n\
+                   szene INNEN. FLUR - TAG\n\
+                   montage\n\
+                   cold-open\n\
+                   INNEN. KÜCHE - NACHT\n\
+                   ende";
+        let tokens: Vec<(TokenKind, &str)> = tokenize(src).into_iter().skip(4).map(|t| (t.kind, &src[t.span])).collect();
+        assert_eq!(tokens, vec![
+            (TokenKind::Keyword, "szene"),
+            (TokenKind::SceneHeading, "INNEN. FLUR - TAG"),
+            (TokenKind::Keyword, "montage"),
+            (TokenKind::Header, "cold-open"),
+            (TokenKind::SceneHeading, "INNEN. KÜCHE - NACHT"),
+            (TokenKind::Keyword, "ende"),
+        ]);
+
+        let src = "Pilot\nscene EXT. LOC - DAY\nend now";
+        let tokens: Vec<(TokenKind, &str)> = tokenize(src).into_iter().map(|t| (t.kind, &src[t.span])).collect();
+        assert_eq!(tokens, vec![
+            (TokenKind::Title, "Pilot"),
+            (TokenKind::Keyword, "scene"),
+            (TokenKind::SceneHeading, "EXT. LOC - DAY"),
+            (TokenKind::Keyword, "end"),
+            (TokenKind::Invalid, "now"),
+        ]);
+    }
+}
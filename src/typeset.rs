@@ -0,0 +1,608 @@
+use std::fs;
+use unicode_width::UnicodeWidthChar;
+use crate::{
+    ast::*,
+    html::{ get_rules, has_title_page, markup, HtmlError },
+    layout::{ attach_ending, continued },
+    parse::*,
+    pdf::{ encrypt_pdf, write_pdf },
+    progress::{ finish_progress, report },
+    style::*,
+    verbose,
+    CmdInfo,
+};
+
+
+pub const LINES_PER_PAGE: usize = 55;
+
+
+// columns are counted from the left edge of the page, courier 12pt sets ten to the inch
+const LEFT: usize = 15;
+const ACTION_WIDTH: usize = 60;
+const CUE: usize = 37;
+const PARENS: usize = 31;
+const PARENS_WIDTH: usize = 25;
+const DIALOGUE: usize = 25;
+const DIALOGUE_WIDTH: usize = 35;
+const RIGHT: usize = 75;
+const NUMBER_LEFT: usize = 10;
+const NUMBER_RIGHT: usize = 77;
+const REVISED: usize = 79;
+
+const MARKS: [(&str, &str); 3] = [("\u{E010}", "\u{E011}"), ("\u{E012}", "\u{E013}"), ("\u{E014}", "\u{E015}")];
+
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+const PLAIN: Style = Style{ bold: false, italic: false, underline: false };
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub col: usize,
+    pub text: String,
+    pub style: Style,
+}
+
+
+pub type Row = Vec<Span>;
+
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Sheet {
+    pub number: Option<usize>,
+    pub rows: Vec<Row>,
+    pub marks: Vec<(usize, String)>,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    Heading,
+    Action,
+    Dialogue,
+    Fixed,
+    Break,
+}
+
+
+#[derive(Debug, Clone)]
+struct Block {
+    flow: Flow,
+    space: usize,
+    rows: Vec<Row>,
+    parens: Vec<bool>,
+    name: String,
+    mark: Option<String>,
+}
+
+impl Block {
+    fn new(flow: Flow, space: usize, rows: Vec<Row>) -> Self {
+        Self{ flow, space, rows, parens: Vec::new(), name: String::new(), mark: None }
+    }
+}
+
+
+pub(crate) fn char_width(c: char) -> usize {
+    if c.is_whitespace() { 1 } else { c.width().unwrap_or(0).min(2) }
+}
+
+
+fn width(chars: &[(char, Style)]) -> usize {
+    chars.iter().map(|&(c, _)| char_width(c)).sum()
+}
+
+
+fn styled(text: &str, base: Style) -> Vec<(char, Style)> {
+    let text = markup(text, MARKS).replace("<u>", MARKS[2].0).replace("</u>", MARKS[2].1);
+    let mut style = base;
+    let mut result = Vec::new();
+    for c in text.chars() {
+        match c {
+            '\u{E010}' => style.bold = true,
+            '\u{E011}' => style.bold = base.bold,
+            '\u{E012}' => style.italic = true,
+            '\u{E013}' => style.italic = base.italic,
+            '\u{E014}' => style.underline = true,
+            '\u{E015}' => style.underline = base.underline,
+            c if c.is_whitespace() => result.push((' ', style)),
+            c => result.push((c, style)),
+        }
+    }
+    result
+}
+
+
+// greedy word wrap, words wider than the column are broken where they overflow
+fn wrap(chars: &[(char, Style)], columns: usize) -> Vec<&[(char, Style)]> {
+    let mut lines = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut end = 0;
+    let mut col = 0;
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i].0 == ' ' {
+            i += 1;
+            continue
+        }
+        let word = i;
+        while i < chars.len() && chars[i].0 != ' ' {
+            i += 1;
+        }
+
+        let gap = width(&chars[end..word]);
+        let len = width(&chars[word..i]);
+        if start.is_some() && col + gap + len <= columns {
+            col += gap + len;
+            end = i;
+            continue
+        }
+        if let Some(start) = start {
+            lines.push(&chars[start..end]);
+        }
+
+        let mut from = word;
+        col = 0;
+        for j in word..i {
+            let w = char_width(chars[j].0);
+            if col > 0 && col + w > columns {
+                lines.push(&chars[from..j]);
+                from = j;
+                col = 0;
+            }
+            col += w;
+        }
+        start = Some(from);
+        end = i;
+    }
+
+    match start {
+        Some(start) => lines.push(&chars[start..end]),
+        None if lines.is_empty() => lines.push(&[]),
+        None => (),
+    }
+    lines
+}
+
+
+fn spans(mut col: usize, chars: &[(char, Style)]) -> Row {
+    let mut row: Row = Vec::new();
+    for &(c, style) in chars {
+        match row.last_mut() {
+            Some(span) if span.style == style => span.text.push(c),
+            _ => row.push(Span{ col, text: c.to_string(), style }),
+        }
+        col += char_width(c);
+    }
+    row
+}
+
+
+fn paragraph(text: &str, col: usize, columns: usize, style: Style) -> Vec<Row> {
+    let chars = styled(text, style);
+    wrap(&chars, columns).into_iter().map(|line| spans(col, line)).collect()
+}
+
+
+fn centered(text: &str, style: Style) -> Vec<Row> {
+    let chars = styled(text, style);
+    wrap(&chars, ACTION_WIDTH).into_iter().map(|line| spans(LEFT + (ACTION_WIDTH - width(line)) / 2, line)).collect()
+}
+
+
+fn flush_right(text: &str, style: Style) -> Vec<Row> {
+    let chars = styled(text, style);
+    wrap(&chars, ACTION_WIDTH).into_iter().map(|line| spans(RIGHT - width(line), line)).collect()
+}
+
+
+fn action(text: &str, style: Style) -> Block {
+    Block::new(Flow::Action, 1, paragraph(text, LEFT, ACTION_WIDTH, style))
+}
+
+
+fn cue_row(name: &str) -> Row {
+    paragraph(name, CUE, RIGHT - CUE, PLAIN).swap_remove(0)
+}
+
+
+fn dialogue(name: &str, contd: bool, lines: &[DialogueLine]) -> Block {
+    let cue = if contd { format!("{name} (CONT'D)") } else { name.to_string() };
+    let mut block = Block{ name: name.to_string(), ..Block::new(Flow::Dialogue, 1, vec![cue_row(&cue)]) };
+    block.parens.push(false);
+
+    for line in lines {
+        let (rows, parens) = match line {
+            DialogueLine::Parens(text) => (paragraph(text, PARENS, PARENS_WIDTH, PLAIN), true),
+            DialogueLine::Speech(text) => (paragraph(&attach_ending(text), DIALOGUE, DIALOGUE_WIDTH, PLAIN), false),
+        };
+        block.parens.extend(rows.iter().map(|_| parens));
+        block.rows.extend(rows);
+    }
+    block
+}
+
+
+fn heading(script: &Script, scene: &Scene, rules: &Rules) -> Vec<Block> {
+    let label = script.scene_label(&scene.locked.clone().unwrap_or_else(|| rules.scene_label(scene.number)));
+    let style = Style{ underline: rules.underline_headings, ..PLAIN };
+
+    let (text, numbered) = if scene.omitted {
+        ("OMITTED".to_string(), true)
+    } else if script.redacted.contains(&scene.line) {
+        ("RESTRICTED".to_string(), true)
+    } else {
+        (scene.heading.clone(), scene.number > 0 && rules.scene_numbers != SceneNumbers::Hidden)
+    };
+
+    let mut rows = paragraph(&text, LEFT, ACTION_WIDTH, style);
+    if numbered {
+        rows[0].insert(0, Span{ col: NUMBER_LEFT, text: label.clone(), style: PLAIN });
+        if rules.scene_numbers == SceneNumbers::Both {
+            rows[0].push(Span{ col: NUMBER_RIGHT, text: label.clone(), style: PLAIN });
+        }
+    }
+    if script.revised.contains(&scene.line) {
+        rows[0].push(Span{ col: REVISED, text: "*".to_string(), style: PLAIN });
+    }
+
+    let mark = if numbered { format!("{label} {text}") } else { text };
+    let block = Block{ mark: Some(mark), ..Block::new(Flow::Heading, 1, rows) };
+    if script.redacted.contains(&scene.line) {
+        vec![Block::new(Flow::Break, 0, Vec::new()), block, Block::new(Flow::Break, 0, Vec::new())]
+    } else {
+        vec![block]
+    }
+}
+
+
+fn element_blocks(element: &Element, rules: &Rules, contd: bool) -> Vec<Block> {
+    let bold = Style{ bold: true, ..PLAIN };
+    let underlined = Style{ underline: true, ..PLAIN };
+    let page_break = || Block::new(Flow::Break, 0, Vec::new());
+    let marked = |block: Block, mark: &str| Block{ mark: Some(mark.to_string()), ..block };
+
+    match &element.kind {
+        Kind::Montage => vec![action("BEGIN MONTAGE:", PLAIN)],
+        Kind::MonEnd => vec![action("END MONTAGE.", PLAIN)],
+        Kind::PageBreak => vec![page_break()],
+        Kind::End => vec![
+            Block::new(Flow::Fixed, 1, flush_right("FADE OUT.", PLAIN)),
+            Block::new(Flow::Fixed, 1, centered("THE END", underlined)),
+        ],
+        Kind::Todo{ text } if text.is_empty() => vec![action("TODO ==============================", PLAIN)],
+        Kind::Todo{ text } => vec![action(&format!("TODO == {}", text.to_uppercase()), PLAIN)],
+        Kind::Note{ text } => vec![action(text, Style{ italic: true, ..PLAIN })],
+        Kind::Section{ .. }|Kind::Synopsis{ .. } => Vec::new(),
+        Kind::Direct{ text } => vec![action(&rules.action(text), PLAIN)],
+        Kind::Parens{ text } => vec![Block::new(Flow::Fixed, 0, paragraph(&format!("({text})"), PARENS, PARENS_WIDTH, PLAIN))],
+        Kind::Speech{ text } => vec![Block::new(Flow::Fixed, 0, paragraph(text, DIALOGUE, DIALOGUE_WIDTH, PLAIN))],
+        Kind::Lyrics{ text } => {
+            let rows = text.split(" / ").flat_map(|line| paragraph(line, DIALOGUE, DIALOGUE_WIDTH, Style{ italic: true, ..PLAIN })).collect();
+            vec![Block::new(Flow::Fixed, 0, rows)]
+        }
+        Kind::Subhead{ text } => vec![action(&text.to_uppercase(), bold)],
+        Kind::Trans{ text } => vec![Block::new(Flow::Fixed, 1, flush_right(&text.to_uppercase(), PLAIN))],
+        Kind::Chyron{ text } => vec![action(&format!("CHYRON: {text}"), PLAIN)],
+        Kind::Sfx{ text } => vec![action(&format!("SFX: {}", text.to_uppercase()), PLAIN)],
+        Kind::Music{ text } => vec![action(&format!("MUSIC: {}", text.to_uppercase()), PLAIN)],
+        Kind::Header{ text } => vec![action(text, PLAIN)],
+        Kind::ActTag{ text }|Kind::Act{ text } => vec![page_break(), marked(Block::new(Flow::Fixed, 1, centered(text, Style{ bold: true, underline: true, ..PLAIN })), text)],
+        Kind::ActEnd{ text } => vec![Block::new(Flow::Fixed, 1, centered(&format!("END OF {text}"), underlined))],
+        Kind::Episode{ text } => vec![page_break(), marked(Block::new(Flow::Fixed, 1, centered(text, bold)), text)],
+        Kind::ComicPage{ number, text } => {
+            let title = if text.is_empty() { format!("PAGE {number}") } else { format!("PAGE {number} - {}", text.to_uppercase()) };
+            vec![page_break(), marked(action(&title, Style{ bold: true, underline: true, ..PLAIN }), &title)]
+        }
+        Kind::Panel{ number, text } if text.is_empty() => vec![action(&format!("PANEL {number}"), PLAIN)],
+        Kind::Panel{ number, text } => vec![action(&format!("PANEL {number}"), PLAIN), action(text, PLAIN)],
+        Kind::Caption{ text } => vec![dialogue("CAPTION", false, &[DialogueLine::Speech(text.clone())])],
+        Kind::Dialogue(Dialogue{ name, lines, .. }) => vec![dialogue(name, contd, lines)],
+    }
+}
+
+
+// at least 'keep' lines stay on either side of a split, dialogue never breaks after a parenthetical
+fn split(block: &Block, room: usize, keep: usize) -> Option<(Block, Block)> {
+    match block.flow {
+        Flow::Action => {
+            let fit = room.min(block.rows.len().saturating_sub(keep));
+            if fit < keep {
+                return None
+            }
+            let head = Block{ rows: block.rows[..fit].to_vec(), ..block.clone() };
+            let tail = Block{ rows: block.rows[fit..].to_vec(), space: 0, mark: None, ..block.clone() };
+            Some((head, tail))
+        }
+        Flow::Dialogue => {
+            let body = block.rows.len() - 1;
+            let fit = (keep..=room.saturating_sub(2).min(body.saturating_sub(keep)))
+                .rev()
+                .find(|&k| !block.parens[k])?;
+
+            let mut head = Block{ rows: block.rows[..=fit].to_vec(), parens: block.parens[..=fit].to_vec(), ..block.clone() };
+            head.rows.push(cue_row("(MORE)"));
+            head.parens.push(false);
+
+            let mut tail = Block{ rows: vec![cue_row(&format!("{} (CONT'D)", block.name))], parens: vec![false], space: 0, mark: None, ..block.clone() };
+            tail.rows.extend_from_slice(&block.rows[fit + 1..]);
+            tail.parens.extend_from_slice(&block.parens[fit + 1..]);
+            Some((head, tail))
+        }
+        _ => None,
+    }
+}
+
+
+struct Typesetter {
+    sheets: Vec<Sheet>,
+    numbered: usize,
+    keep_lines: usize,
+}
+
+impl Typesetter {
+    fn current(&mut self) -> &mut Sheet {
+        self.sheets.last_mut().unwrap()
+    }
+
+    fn is_fresh(&self) -> bool {
+        self.sheets.last().unwrap().rows.is_empty()
+    }
+
+    fn room(&self) -> usize {
+        LINES_PER_PAGE.saturating_sub(self.sheets.last().unwrap().rows.len())
+    }
+
+    // the first page of the script carries no number, the rest are numbered from 2
+    fn break_page(&mut self) {
+        if !self.is_fresh() {
+            self.numbered += 1;
+            self.sheets.push(Sheet{ number: Some(self.numbered), ..Default::default() });
+        }
+    }
+
+    fn push(&mut self, block: Block) {
+        let space = if self.is_fresh() { 0 } else { block.space };
+        let sheet = self.current();
+        sheet.rows.extend(std::iter::repeat_n(Vec::new(), space));
+        if let Some(mark) = block.mark {
+            sheet.marks.push((sheet.rows.len(), mark));
+        }
+        sheet.rows.extend(block.rows);
+    }
+
+    fn place(&mut self, mut block: Block, keep: usize) {
+        if block.flow == Flow::Break {
+            self.break_page();
+            return
+        }
+
+        loop {
+            let space = if self.is_fresh() { 0 } else { block.space };
+            let keep = if self.is_fresh() { 0 } else { keep };
+            let room = self.room();
+            if space + block.rows.len() + keep <= room {
+                self.push(block);
+                return
+            }
+
+            let parts = match split(&block, room.saturating_sub(space), self.keep_lines) {
+                Some(parts) if keep == 0 => Some(parts),
+                _ if self.is_fresh() => {
+                    let mut head = block.clone();
+                    let tail = Block{ rows: head.rows.split_off(room), parens: head.parens.split_off(room.min(head.parens.len())), space: 0, mark: None, ..block.clone() };
+                    Some((head, tail))
+                }
+                _ => None,
+            };
+            if let Some((head, tail)) = parts {
+                self.push(head);
+                block = tail;
+            }
+            self.break_page();
+        }
+    }
+}
+
+
+fn title_sheet(script: &Script) -> Sheet {
+    let mut rows: Vec<Row> = vec![Vec::new(); LINES_PER_PAGE];
+    // blocks are pushed down past anything long above them, rows off the page are dropped
+    let mut at = |row: usize, lines: Vec<Row>| {
+        let end = row + lines.len();
+        for (slot, line) in rows.iter_mut().skip(row).zip(lines) {
+            *slot = line;
+        }
+        end
+    };
+
+    let next = at(18, centered(&script.title.to_uppercase(), Style{ bold: true, ..PLAIN }));
+    let next = at(21.max(next + 2), centered(&script.subtitle, PLAIN));
+    if let Some(author) = &script.author {
+        let next = at(25.max(next + 3), centered("Written by", PLAIN));
+        at(next + 1, centered(author, PLAIN));
+    }
+    if let Some(draft) = &script.draft {
+        let lines = paragraph(draft, LEFT, ACTION_WIDTH, PLAIN);
+        at((LINES_PER_PAGE - 1).saturating_sub(lines.len()), lines);
+    }
+
+    Sheet{ number: None, rows, marks: vec![(18, script.title.clone())] }
+}
+
+
+fn flow_blocks(script: &Script, elements: &[Element], rules: &Rules, cmd: &CmdInfo) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    for (element, contd) in elements.iter().zip(continued(elements, rules.contd && !cmd.no_contd)) {
+        if matches!(element.kind, Kind::Note{ .. }) && !cmd.with_notes {
+            continue
+        }
+        let mut parts = element_blocks(element, rules, contd);
+        if script.revised.contains(&element.line) {
+            if let Some(row) = parts.iter_mut().find_map(|b| b.rows.first_mut()) {
+                row.push(Span{ col: REVISED, text: "*".to_string(), style: PLAIN });
+            }
+        }
+        blocks.extend(parts);
+    }
+    blocks
+}
+
+
+pub fn typeset(script: &Script, cmd: &CmdInfo) -> Vec<Sheet> {
+    let rules = get_rules(script, cmd);
+
+    let mut blocks = flow_blocks(script, &script.prelude, &rules, cmd);
+    let mut scenes = Vec::new();
+    for scene in &script.scenes {
+        let start = blocks.len();
+        blocks.extend(heading(script, scene, &rules));
+        blocks.extend(flow_blocks(script, &scene.elements, &rules, cmd));
+        scenes.push((start, blocks[start..].iter().map(|b| b.space + b.rows.len()).sum::<usize>()));
+    }
+
+    let keep_lines = cmd.keep_lines.unwrap_or(2).max(1) as usize;
+    let mut typesetter = Typesetter{ sheets: vec![Sheet{ number: None, ..Default::default() }], numbered: 1, keep_lines };
+    let mut scenes = scenes.into_iter().peekable();
+    for (i, block) in blocks.iter().enumerate() {
+        // short scenes move to the next page whole rather than being split
+        if let Some((_, rows)) = scenes.next_if(|&(start, _)| start == i) {
+            if cmd.keep_scenes.is_some_and(|keep| rows < keep as usize) && rows > typesetter.room() {
+                typesetter.break_page();
+            }
+        }
+        // a scene heading is kept with the start of what follows it, a cue counting on top of its lines
+        let keep = match (block.flow, blocks.get(i + 1)) {
+            (Flow::Heading, Some(next)) if next.flow != Flow::Break => next.space + next.rows.len().min(keep_lines + (next.flow == Flow::Dialogue) as usize),
+            _ => 0,
+        };
+        typesetter.place(block.clone(), keep);
+    }
+
+    let mut sheets = typesetter.sheets;
+    if sheets.last().is_some_and(|s| s.rows.is_empty()) && sheets.len() > 1 {
+        sheets.pop();
+    }
+    if has_title_page(cmd) {
+        sheets.insert(0, title_sheet(script));
+    }
+    sheets
+}
+
+
+pub fn gen_native(cmd: &CmdInfo) -> Result<Vec<Diagnostic>, HtmlError> {
+    report(cmd, "parse", 0, None);
+    let script = read_script(cmd)?;
+    report(cmd, "typeset", 20, None);
+    let sheets = typeset(&script, cmd);
+    verbose!("typeset {} page(s)", sheets.len());
+    report(cmd, "write", 80, None);
+    fs::write(&cmd.outfile, write_pdf(&sheets, cmd.watermark.as_deref()))?;
+    finish_progress(cmd);
+
+    if let Some(encryption) = &cmd.encrypt {
+        encrypt_pdf(cmd, encryption)?;
+    }
+
+    Ok(script.warnings)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(row: &Row) -> String {
+        let mut line = String::new();
+        for span in row {
+            line.push_str(&" ".repeat(span.col.saturating_sub(line.chars().count())));
+            line.push_str(&span.text);
+        }
+        line
+    }
+
+    #[test]
+    fn wrapping() {
+        let rows = paragraph("The **door** _slams_ shut behind them, hard.", LEFT, 20, PLAIN);
+        assert_eq!(rows.iter().map(text).collect::<Vec<_>>(), [
+            "               The door slams shut",
+            "               behind them, hard.",
+        ]);
+        assert!(rows[0].iter().any(|s| s.text == "door" && s.style.bold));
+        assert!(rows[0].iter().any(|s| s.text == "slams" && s.style.underline));
+        assert_eq!(paragraph("abcdefghij", 0, 4, PLAIN).len(), 3);
+    }
+
+    #[test]
+    fn pages() {
+        let speech = "Words and more words to fill the page. ".repeat(60);
+        let script = parse(&format!("title\nsubtitle\nscene INT. ROOM - DAY\ndirect Alex waits.\nALEX: {speech}\nend")).expect("parse failed");
+
+        let sheets = typeset(&script, &CmdInfo::default());
+        assert_eq!(sheets.len(), 3);
+        assert_eq!(sheets[0].number, None);
+        assert_eq!(sheets[1].number, None);
+        assert_eq!(sheets[2].number, Some(2));
+        assert!(sheets.iter().all(|s| s.rows.len() <= LINES_PER_PAGE));
+        assert_eq!(text(&sheets[1].rows[0]), "          1    INT. ROOM - DAY");
+        assert_eq!(sheets[1].marks, [(0, "1 INT. ROOM - DAY".to_string())]);
+
+        assert_eq!(text(sheets[1].rows.last().unwrap()).trim(), "(MORE)");
+        assert_eq!(text(&sheets[2].rows[0]).trim(), "ALEX (CONT'D)");
+    }
+
+    #[test]
+    fn heading_kept() {
+        let action = "direct Line.\n".repeat(27);
+        let script = parse(&format!("title\nsubtitle\n{action}scene INT. ROOM - DAY\ndirect Alex waits.")).expect("parse failed");
+
+        let sheets = typeset(&script, &CmdInfo{ title_page: crate::TitlePage::Never, ..Default::default() });
+        assert_eq!(sheets.len(), 2);
+        assert_eq!(sheets[1].marks.len(), 1);
+    }
+
+    #[test]
+    fn title_sheet_overflow() {
+        let mut script = parse("title\nsubtitle\nscene INT. ROOM - DAY\ndirect Alex waits.").expect("parse failed");
+        script.title = "A very long title that goes on ".repeat(10);
+        script.author = Some("Alex".to_string());
+        script.draft = Some("Draft notes that run on and on. ".repeat(40));
+
+        let sheet = title_sheet(&script);
+        assert_eq!(sheet.rows.len(), LINES_PER_PAGE);
+        assert!(sheet.rows[LINES_PER_PAGE - 1].is_empty());
+        assert!(text(&sheet.rows[LINES_PER_PAGE - 2]).trim().ends_with("on."));
+        let written = sheet.rows.iter().position(|r| text(r).trim() == "Written by").unwrap();
+        assert!(written > 18 + paragraph(&script.title, LEFT, ACTION_WIDTH, PLAIN).len());
+
+        script.draft = Some("First draft".to_string());
+        assert_eq!(text(&title_sheet(&script).rows[LINES_PER_PAGE - 2]).trim(), "First draft");
+        assert_eq!(crate::page_count(&script, &CmdInfo{ engine: crate::Engine::Native, ..Default::default() }), 2);
+    }
+
+    #[test]
+    fn keep_settings() {
+        let lines = "direct Line.\n".repeat(26);
+        let long = format!("direct {}\n", "Words to fill. ".repeat(40));
+        let script = parse(&format!("title\nsubtitle\n{lines}{long}")).expect("parse failed");
+        let cmd = CmdInfo{ title_page: crate::TitlePage::Never, ..Default::default() };
+
+        // three rows are left, enough to split with two kept on the page but not four
+        assert_eq!(typeset(&script, &cmd)[0].rows.len(), 55);
+        assert_eq!(typeset(&script, &CmdInfo{ keep_lines: Some(4), ..cmd.clone() })[0].rows.len(), 51);
+
+        let scene = "scene INT. ROOM - DAY\ndirect One.\ndirect Two.\ndirect Three.\n";
+        let script = parse(&format!("title\nsubtitle\n{lines}{scene}")).expect("parse failed");
+        assert_eq!(typeset(&script, &cmd)[0].marks.len(), 1);
+        let sheets = typeset(&script, &CmdInfo{ keep_scenes: Some(10), ..cmd });
+        assert!(sheets[0].marks.is_empty());
+        assert_eq!(sheets[1].marks.len(), 1);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::ast::*;
+
+
+fn opens(before: Option<char>) -> bool {
+    before.is_none_or(|c| c.is_whitespace() || "([{—–/".contains(c))
+}
+
+
+// quotes open at the start of a word and close everywhere else, so apostrophes come out right
+pub fn smart_text(text: &str) -> String {
+    let text = text.replace("...", "…").replace("--", "—");
+    let mut result = String::with_capacity(text.len());
+    let mut before = None;
+    for c in text.chars() {
+        let c = match c {
+            '"' if opens(before) => '“',
+            '"' => '”',
+            '\'' if opens(before) => '‘',
+            '\'' => '’',
+            c => c,
+        };
+        result.push(c);
+        before = Some(c);
+    }
+    result
+}
+
+
+pub fn smarten(script: &mut Script) {
+    let elements = script.prelude.iter_mut().chain(script.scenes.iter_mut().flat_map(|s| &mut s.elements));
+    for element in elements {
+        match &mut element.kind {
+            Kind::Direct{ text }|Kind::Speech{ text }|Kind::Parens{ text }|Kind::Lyrics{ text } => *text = smart_text(text),
+            Kind::Dialogue(dialogue) => for line in &mut dialogue.lines {
+                match line {
+                    DialogueLine::Parens(text)|DialogueLine::Speech(text) => *text = smart_text(text),
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitutions() {
+        assert_eq!(smart_text(r#"She said "don't go"... then--"Wait!""#), "She said “don’t go”… then—“Wait!”");
+        assert_eq!(smart_text("the 'best' **band's** song"), "the ‘best’ **band’s** song");
+        assert_eq!(smart_text("(\"quiet\")"), "(“quiet”)");
+    }
+}